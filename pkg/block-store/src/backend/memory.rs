@@ -0,0 +1,78 @@
+use std::{collections::BTreeMap, ops::Bound, sync::RwLock};
+
+use super::{Backend, Direction, Entry, WriteBatch};
+use crate::Result;
+
+/// A [`Backend`] that keeps data in memory, which is useful for tests
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    map: RwLock<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.map.read().unwrap().get(key).cloned())
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.map
+            .write()
+            .unwrap()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        self.map.write().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut map = self.map.write().unwrap();
+
+        for (key, value) in batch.ops {
+            match value {
+                Some(value) => map.insert(key, value),
+                None => map.remove(&key),
+            };
+        }
+
+        Ok(())
+    }
+
+    fn iter_range<'a>(
+        &'a self,
+        lower: Vec<u8>,
+        upper: Option<Vec<u8>>,
+        direction: Direction,
+    ) -> Box<dyn Iterator<Item = Result<Entry>> + Send + 'a> {
+        let upper = match upper {
+            // `BTreeMap::range` panics if the range is backwards, rocksdb returns nothing
+            Some(upper) if upper <= lower => return Box::new(std::iter::empty()),
+            Some(upper) => Bound::Excluded(upper),
+            None => Bound::Unbounded,
+        };
+
+        // Copy the entries, so the lock isn't held while iterating. Like a rocksdb iterator, this
+        // means later changes aren't visible to the iterator
+        let entries = self
+            .map
+            .read()
+            .unwrap()
+            .range((Bound::Included(lower), upper))
+            .map(|(key, value)| Ok((key.clone().into(), value.clone().into())))
+            .collect::<Vec<_>>();
+
+        match direction {
+            Direction::Forward => Box::new(entries.into_iter()),
+            Direction::Reverse => Box::new(entries.into_iter().rev()),
+        }
+    }
+}
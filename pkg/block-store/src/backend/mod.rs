@@ -0,0 +1,157 @@
+use crate::Result;
+
+pub use memory::MemoryBackend;
+pub use rocks::RocksDbBackend;
+
+mod memory;
+mod rocks;
+
+/// A key/value pair returned when iterating over a [`Backend`]
+pub type Entry = (Box<[u8]>, Box<[u8]>);
+
+/// An ordered key/value store that a [`BlockStore`][crate::BlockStore] is stored in
+///
+/// Keys are ordered lexicographically by their bytes
+pub trait Backend: Send + Sync {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    fn delete(&self, key: &[u8]) -> Result<()>;
+
+    /// Apply all the changes in `batch` atomically
+    fn write(&self, batch: WriteBatch) -> Result<()>;
+
+    /// Iterate over the entries with keys in `lower..upper`, or `lower..` if `upper` is `None`
+    ///
+    /// Changes made while iterating are not visible to the iterator
+    fn iter_range<'a>(
+        &'a self,
+        lower: Vec<u8>,
+        upper: Option<Vec<u8>>,
+        direction: Direction,
+    ) -> Box<dyn Iterator<Item = Result<Entry>> + Send + 'a>;
+
+    /// Iterate over the entries with keys that start with `prefix`
+    fn iter_prefix<'a>(
+        &'a self,
+        prefix: &[u8],
+        direction: Direction,
+    ) -> Box<dyn Iterator<Item = Result<Entry>> + Send + 'a> {
+        self.iter_range(prefix.to_vec(), prefix_successor(prefix), direction)
+    }
+}
+
+/// The order to iterate over keys in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the lowest key to the highest
+    Forward,
+    /// From the highest key to the lowest
+    Reverse,
+}
+
+/// A set of changes that are applied atomically by [`Backend::write`]
+#[derive(Debug, Default)]
+pub struct WriteBatch {
+    /// Changes in the order they were made, `None` deletes the key
+    pub(crate) ops: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl WriteBatch {
+    pub fn put(&mut self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) {
+        self.ops
+            .push((key.as_ref().to_vec(), Some(value.as_ref().to_vec())));
+    }
+
+    pub fn delete(&mut self, key: impl AsRef<[u8]>) {
+        self.ops.push((key.as_ref().to_vec(), None));
+    }
+}
+
+/// The lowest key that is greater than every key starting with `prefix`, or `None` if there isn't
+/// one (i.e. the prefix is all `0xff`)
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut successor = prefix.to_vec();
+
+    while let Some(last) = successor.pop() {
+        if last < u8::MAX {
+            successor.push(last + 1);
+            return Some(successor);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn keys(iter: impl Iterator<Item = Result<Entry>>) -> Vec<Vec<u8>> {
+        iter.map(|r| r.unwrap().0.into_vec()).collect()
+    }
+
+    fn iterate_in_both_directions(backend: &dyn Backend) {
+        let mut batch = WriteBatch::default();
+        for key in [
+            &[0, 1][..],
+            &[1],
+            &[1, 0],
+            &[1, 255],
+            &[2],
+            &[255, 255],
+            &[255, 255, 0],
+        ] {
+            batch.put(key, b"value");
+        }
+        batch.delete([2u8]);
+        backend.write(batch).unwrap();
+
+        assert_eq!(backend.get(&[1, 0]).unwrap(), Some(b"value".to_vec()));
+        assert_eq!(backend.get(&[2]).unwrap(), None);
+
+        assert_eq!(
+            keys(backend.iter_prefix(&[1], Direction::Forward)),
+            [vec![1], vec![1, 0], vec![1, 255]]
+        );
+        assert_eq!(
+            keys(backend.iter_prefix(&[1], Direction::Reverse)),
+            [vec![1, 255], vec![1, 0], vec![1]]
+        );
+        assert_eq!(
+            keys(backend.iter_prefix(&[255, 255], Direction::Reverse)),
+            [vec![255, 255, 0], vec![255, 255]]
+        );
+        assert_eq!(
+            keys(backend.iter_range(vec![0, 2], Some(vec![1, 1]), Direction::Forward)),
+            [vec![1], vec![1, 0]]
+        );
+
+        // empty and backwards ranges have no entries
+        for direction in [Direction::Forward, Direction::Reverse] {
+            assert!(keys(backend.iter_range(vec![1], Some(vec![1]), direction)).is_empty());
+            assert!(keys(backend.iter_range(vec![2], Some(vec![1]), direction)).is_empty());
+        }
+    }
+
+    #[test]
+    fn rocksdb() {
+        let temp_dir = TempDir::new("block-store").unwrap();
+        iterate_in_both_directions(&RocksDbBackend::open(temp_dir.path(), true).unwrap());
+    }
+
+    #[test]
+    fn memory() {
+        iterate_in_both_directions(&MemoryBackend::new());
+    }
+
+    #[test]
+    fn successor_of_prefix() {
+        assert_eq!(prefix_successor(&[1, 2]), Some(vec![1, 3]));
+        assert_eq!(prefix_successor(&[1, 255]), Some(vec![2]));
+        assert_eq!(prefix_successor(&[255, 255]), None);
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use rocksdb::DB;
+
+use super::{Backend, Direction, Entry, WriteBatch};
+use crate::Result;
+
+/// A [`Backend`] that persists data to a rocksdb database
+pub struct RocksDbBackend {
+    db: DB,
+}
+
+impl RocksDbBackend {
+    pub fn open(path: &Path, create_if_missing: bool) -> Result<Self> {
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(create_if_missing);
+
+        Ok(Self {
+            db: DB::open(&opts, path)?,
+        })
+    }
+
+    /// Open the existing database at `path` read-only, see
+    /// [`BlockStore::open_read_only`][crate::BlockStore::open_read_only]
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        Ok(Self {
+            db: DB::open_for_read_only(&rocksdb::Options::default(), path, false)?,
+        })
+    }
+
+    /// Open a read-only secondary instance that follows the database at `primary_path`, see
+    /// [`BlockStore::open_secondary`][crate::BlockStore::open_secondary]
+    pub fn open_as_secondary(primary_path: &Path, secondary_path: &Path) -> Result<Self> {
+        let mut opts = rocksdb::Options::default();
+        // required by secondary instances, so they can see files added by the primary
+        opts.set_max_open_files(-1);
+
+        Ok(Self {
+            db: DB::open_as_secondary(&opts, primary_path, secondary_path)?,
+        })
+    }
+
+    /// Read the changes made by the primary since this secondary instance was opened or last
+    /// caught up
+    pub fn catch_up_with_primary(&self) -> Result<()> {
+        self.db.try_catch_up_with_primary()?;
+        Ok(())
+    }
+
+    /// Create a consistent copy of the database at `path`, see
+    /// [`BlockStore::create_checkpoint`][crate::BlockStore::create_checkpoint]
+    pub fn create_checkpoint(&self, path: &Path) -> Result<()> {
+        rocksdb::checkpoint::Checkpoint::new(&self.db)?.create_checkpoint(path)?;
+        Ok(())
+    }
+}
+
+impl Backend for RocksDbBackend {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.db.get(key)?)
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        Ok(self.db.put(key, value)?)
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        Ok(self.db.delete(key)?)
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut rocks_batch = rocksdb::WriteBatchWithTransaction::<false>::default();
+
+        for (key, value) in batch.ops {
+            match value {
+                Some(value) => rocks_batch.put(key, value),
+                None => rocks_batch.delete(key),
+            }
+        }
+
+        Ok(self.db.write(rocks_batch)?)
+    }
+
+    fn iter_range<'a>(
+        &'a self,
+        lower: Vec<u8>,
+        upper: Option<Vec<u8>>,
+        direction: Direction,
+    ) -> Box<dyn Iterator<Item = Result<Entry>> + Send + 'a> {
+        let mut read_opts = rocksdb::ReadOptions::default();
+
+        read_opts.set_iterate_lower_bound(lower);
+        if let Some(upper) = upper {
+            read_opts.set_iterate_upper_bound(upper);
+        }
+
+        let iter = self.db.iterator_opt(
+            match direction {
+                Direction::Forward => rocksdb::IteratorMode::Start,
+                Direction::Reverse => rocksdb::IteratorMode::End,
+            },
+            read_opts,
+        );
+
+        Box::new(iter.map(|r| Ok(r?)))
+    }
+}
@@ -1,14 +1,14 @@
 use std::{
+    fmt,
     marker::PhantomData,
     ops::{Bound, RangeBounds},
 };
 
 use crate::list::StoreList;
 use primitives::{block_height::BlockHeight, pagination::CursorChoice};
-use rocksdb::DB;
 use wire_message::WireMessage;
 
-use crate::{list::List, Block, Error, Result};
+use crate::{list::List, Backend, Block, Error, Result};
 
 pub(crate) trait StoreKey: Clone {
     fn to_key(&self) -> Key;
@@ -57,7 +57,7 @@ where
     fn max_value() -> Self;
 
     fn list<'db>(
-        db: &'db DB,
+        db: &'db dyn Backend,
         range: impl RangeBounds<Self>,
         order: &Self::Order,
     ) -> List<'db, Value>
@@ -92,7 +92,7 @@ where
     }
 
     fn list_paginated(
-        db: &DB,
+        db: &dyn Backend,
         cursor: &Option<CursorChoice<Self>>,
         order: Self::Order,
         limit: usize,
@@ -138,6 +138,7 @@ where
     }
 }
 
+/// A key of a [`BlockStore`][crate::BlockStore], serialized as its kind followed by its data
 #[derive(Debug, Clone)]
 pub enum Key {
     Block(KeyBlock),
@@ -147,6 +148,10 @@ pub enum Key {
     TxnByHash([u8; 32]),
     StoreVersion,
     NonEmptyBlock(KeyNonEmptyBlock),
+    PendingCommit,
+    ElementToTxn([u8; 32]),
+    PrunedHeight,
+    MigrationCheckpoint,
 }
 
 impl Key {
@@ -159,10 +164,29 @@ impl Key {
             Self::TxnByHash(_) => 4,
             Self::StoreVersion => 5,
             Self::NonEmptyBlock(_) => 6,
+            Self::PendingCommit => 7,
+            Self::ElementToTxn(_) => 8,
+            Self::PrunedHeight => 9,
+            Self::MigrationCheckpoint => 10,
         }
     }
 
-    pub(crate) fn serialize(&self) -> Vec<u8> {
+    /// The type of the value stored with this key
+    pub fn value_kind(&self) -> ValueKind {
+        match self {
+            Self::Block(_) | Self::PendingBlock | Self::NonEmptyBlock(_) => ValueKind::Block,
+            Self::TxnByHash(_) => ValueKind::Txn,
+            Self::MaxHeight
+            | Self::BlockHashToHeight(_)
+            | Self::PendingCommit
+            | Self::PrunedHeight => ValueKind::BlockHeight,
+            Self::StoreVersion => ValueKind::StoreVersion,
+            Self::ElementToTxn(_) => ValueKind::TxnLocation,
+            Self::MigrationCheckpoint => ValueKind::MigrationCheckpoint,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
         let mut out = vec![self.kind()];
 
         match self {
@@ -181,12 +205,18 @@ impl Key {
             Self::NonEmptyBlock(block_number) => {
                 block_number.serialize_to(&mut out);
             }
+            Self::PendingCommit => {}
+            Self::ElementToTxn(element) => {
+                out.extend_from_slice(element);
+            }
+            Self::PrunedHeight => {}
+            Self::MigrationCheckpoint => {}
         }
 
         out
     }
 
-    pub(crate) fn deserialize(bytes: &[u8]) -> Result<Self> {
+    pub fn deserialize(bytes: &[u8]) -> Result<Self> {
         let Some((kind, bytes)) = bytes.split_first() else {
             return Err(Error::InvalidKey);
         };
@@ -194,19 +224,21 @@ impl Key {
         match *kind {
             0 => KeyBlock::deserialize(bytes).map(Self::Block),
             1 => Ok(Self::MaxHeight),
-            2 => {
-                let mut block_hash = [0u8; 32];
-                block_hash.copy_from_slice(&bytes[0..32]);
-                Ok(Self::BlockHashToHeight(block_hash))
-            }
+            2 => Ok(Self::BlockHashToHeight(
+                bytes.try_into().map_err(|_| Error::InvalidKey)?,
+            )),
             3 => Ok(Self::PendingBlock),
-            4 => {
-                let mut txn_hash = [0u8; 32];
-                txn_hash.copy_from_slice(&bytes[0..32]);
-                Ok(Self::TxnByHash(txn_hash))
-            }
+            4 => Ok(Self::TxnByHash(
+                bytes.try_into().map_err(|_| Error::InvalidKey)?,
+            )),
             5 => Ok(Self::StoreVersion),
             6 => KeyNonEmptyBlock::deserialize(bytes).map(Self::NonEmptyBlock),
+            7 => Ok(Self::PendingCommit),
+            8 => Ok(Self::ElementToTxn(
+                bytes.try_into().map_err(|_| Error::InvalidKey)?,
+            )),
+            9 => Ok(Self::PrunedHeight),
+            10 => Ok(Self::MigrationCheckpoint),
             _ => Err(Error::InvalidKey),
         }
     }
@@ -218,6 +250,55 @@ impl Key {
     }
 }
 
+/// Displays the key's name and data, e.g. `block/10` or `txn_by_hash/<hex hash>`
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn hex(f: &mut fmt::Formatter<'_>, bytes: &[u8; 32]) -> fmt::Result {
+            bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+        }
+
+        match self {
+            Self::Block(KeyBlock(height)) => write!(f, "block/{height}"),
+            Self::MaxHeight => write!(f, "max_height"),
+            Self::BlockHashToHeight(block_hash) => {
+                write!(f, "block_hash_to_height/")?;
+                hex(f, block_hash)
+            }
+            Self::PendingBlock => write!(f, "pending_block"),
+            Self::TxnByHash(txn_hash) => {
+                write!(f, "txn_by_hash/")?;
+                hex(f, txn_hash)
+            }
+            Self::StoreVersion => write!(f, "store_version"),
+            Self::NonEmptyBlock(KeyNonEmptyBlock(height)) => write!(f, "non_empty_block/{height}"),
+            Self::PendingCommit => write!(f, "pending_commit"),
+            Self::ElementToTxn(element) => {
+                write!(f, "element_to_txn/")?;
+                hex(f, element)
+            }
+            Self::PrunedHeight => write!(f, "pruned_height"),
+            Self::MigrationCheckpoint => write!(f, "migration_checkpoint"),
+        }
+    }
+}
+
+/// The type of the value stored with a [`Key`], see [`Key::value_kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// A block, in its [`WireMessage`] format
+    Block,
+    /// A transaction, in its [`WireMessage`] format
+    Txn,
+    /// A big-endian `u64` block height
+    BlockHeight,
+    /// The version of the store, a big-endian `u32`
+    StoreVersion,
+    /// A [`TxnLocation`][crate::TxnLocation]
+    TxnLocation,
+    /// A [`Checkpoint`][primitives::migration::Checkpoint]
+    MigrationCheckpoint,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct KeyBlock(pub(crate) BlockHeight);
 
@@ -238,7 +319,7 @@ impl StoreKey for KeyBlock {
     }
 
     fn deserialize(bytes: &[u8]) -> Result<Self> {
-        let Ok(u64_bytes) = TryInto::<[u8; 8]>::try_into(&bytes[0..8]) else {
+        let Ok(u64_bytes) = TryInto::<[u8; 8]>::try_into(bytes) else {
             return Err(Error::InvalidKey);
         };
 
@@ -311,7 +392,7 @@ impl StoreKey for KeyNonEmptyBlock {
     }
 
     fn deserialize(bytes: &[u8]) -> Result<Self> {
-        let Ok(u64_bytes) = TryInto::<[u8; 8]>::try_into(&bytes[0..8]) else {
+        let Ok(u64_bytes) = TryInto::<[u8; 8]>::try_into(bytes) else {
             return Err(Error::InvalidKey);
         };
 
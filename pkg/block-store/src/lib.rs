@@ -4,19 +4,19 @@
 #![feature(associated_type_defaults)]
 #![feature(bound_map)]
 
+mod backend;
 mod keys;
 mod list;
 mod migration;
 
-use std::{marker::PhantomData, path::Path};
+use std::{marker::PhantomData, ops::Bound, path::Path};
 
-use keys::{Key, KeyBlock, StoreKey};
-use migration::LATEST_VERSION;
-use primitives::block_height::BlockHeight;
-use rocksdb::DB;
+use keys::{KeyBlock, StoreKey};
+use primitives::{block_height::BlockHeight, migration::Migrate};
 use wire_message::WireMessage;
 
-pub use keys::BlockListOrder;
+pub use backend::{Backend, Direction, Entry, MemoryBackend, RocksDbBackend, WriteBatch};
+pub use keys::{BlockListOrder, Key, ValueKind};
 pub use list::StoreList;
 
 #[derive(Debug, thiserror::Error)]
@@ -24,8 +24,11 @@ pub enum Error {
     #[error("invalid key")]
     InvalidKey,
 
-    #[error("invalid version '{0}'")]
-    InvalidVersion(u32),
+    #[error("invalid value")]
+    InvalidValue,
+
+    #[error("migration error: {0}")]
+    Migration(#[from] primitives::migration::Error),
 
     #[error("rocksdb error: {0}")]
     RocksDB(#[from] rocksdb::Error),
@@ -39,8 +42,12 @@ pub enum Error {
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
-pub struct BlockStore<B> {
-    db: DB,
+/// A store of blocks of type `B`, with indexes for their transactions
+///
+/// Data is stored in a [`Backend`], which is rocksdb by default. Use [`BlockStore::in_memory`] for
+/// a store that isn't persisted (e.g. in tests)
+pub struct BlockStore<B, D = RocksDbBackend> {
+    db: D,
     _marker: PhantomData<B>,
 }
 
@@ -51,10 +58,56 @@ pub trait Block {
     fn block_hash(&self) -> [u8; 32];
 
     fn txns(&self) -> Vec<Self::Txn>;
+
+    /// Drop the data that doesn't need to be kept for old blocks (e.g. proofs), see
+    /// [`BlockStore::prune`]
+    ///
+    /// The height, hash and transaction hashes of the block must not change
+    fn prune(&mut self);
 }
 
 pub trait Transaction {
     fn txn_hash(&self) -> [u8; 32];
+
+    /// The leaves (e.g. commitments and nullifiers) this transaction inserts, which are indexed
+    /// by [`BlockStore::get_txn_location_by_element`]
+    ///
+    /// Padding leaves that are shared by many transactions should not be included
+    fn leaves(&self) -> Vec<[u8; 32]>;
+}
+
+/// The number of blocks [`BlockStore::prune`] rewrites in each write
+const PRUNE_BATCH_SIZE: usize = 1000;
+
+/// Where a transaction is stored, as returned by [`BlockStore::get_txn_location_by_element`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxnLocation {
+    pub block_height: BlockHeight,
+    /// The index of the transaction in [`Block::txns`]
+    pub txn_index: u32,
+    pub txn_hash: [u8; 32],
+}
+
+impl TxnLocation {
+    const SIZE: usize = 8 + 4 + 32;
+
+    fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut out = [0; Self::SIZE];
+        out[0..8].copy_from_slice(&self.block_height.to_be_bytes());
+        out[8..12].copy_from_slice(&self.txn_index.to_be_bytes());
+        out[12..44].copy_from_slice(&self.txn_hash);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes: &[u8; Self::SIZE] = bytes.try_into().map_err(|_| Error::InvalidValue)?;
+
+        Ok(Self {
+            block_height: BlockHeight(u64::from_be_bytes(bytes[0..8].try_into().unwrap())),
+            txn_index: u32::from_be_bytes(bytes[8..12].try_into().unwrap()),
+            txn_hash: bytes[12..44].try_into().unwrap(),
+        })
+    }
 }
 
 impl<B> BlockStore<B>
@@ -62,12 +115,6 @@ where
     B: Block + WireMessage,
     B::Txn: WireMessage,
 {
-    fn db_options(create_if_missing: bool) -> rocksdb::Options {
-        let mut opts = rocksdb::Options::default();
-        opts.create_if_missing(create_if_missing);
-        opts
-    }
-
     pub fn create_or_load(path: &Path) -> Result<Self> {
         if path.exists() && std::fs::read_dir(path)?.next().is_some() {
             Self::load_existing(path)
@@ -77,31 +124,132 @@ where
     }
 
     fn create(path: &Path) -> Result<Self> {
-        let db = DB::open(&Self::db_options(true), path)?;
+        Self::create_with_backend(RocksDbBackend::open(path, true)?)
+    }
 
-        let self_ = Self {
-            db,
+    fn load_existing(path: &Path) -> Result<Self> {
+        Ok(Self {
+            db: RocksDbBackend::open(path, false)?,
             _marker: PhantomData,
-        };
+        })
+    }
 
-        self_.set_store_version(LATEST_VERSION)?;
+    /// Open a read-only store that follows the store at `primary_path`, which is in use by
+    /// another process
+    ///
+    /// `secondary_path` is where rocksdb keeps the secondary instance's own logs, and must be
+    /// different for each secondary instance. Changes made by the primary are only visible after
+    /// calling [`BlockStore::catch_up_with_primary`], and writes to the secondary store fail.
+    /// The primary is responsible for migrations, so the store may be at an older version until
+    /// the primary has migrated it
+    pub fn open_secondary(primary_path: &Path, secondary_path: &Path) -> Result<Self> {
+        Ok(Self {
+            db: RocksDbBackend::open_as_secondary(primary_path, secondary_path)?,
+            _marker: PhantomData,
+        })
+    }
 
-        Ok(self_)
+    /// Open the existing store at `path` read-only, without migrating it
+    ///
+    /// Writes to the store fail, and changes made by other processes after it's opened aren't
+    /// visible
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        Ok(Self {
+            db: RocksDbBackend::open_read_only(path)?,
+            _marker: PhantomData,
+        })
     }
 
-    fn load_existing(path: &Path) -> Result<Self> {
-        let db = DB::open(&Self::db_options(false), path)?;
+    /// Read the blocks stored by the primary since this store was opened with
+    /// [`BlockStore::open_secondary`], or since it last caught up
+    pub fn catch_up_with_primary(&self) -> Result<()> {
+        self.db.catch_up_with_primary()
+    }
 
-        Ok(Self {
+    /// Create a consistent copy of the store at `path`, while it's in use
+    ///
+    /// `path` must not exist yet. Files are hard linked where possible, so a checkpoint on the same
+    /// filesystem is cheap. The checkpoint can be opened with [`BlockStore::create_or_load`]
+    pub fn create_checkpoint(&self, path: &Path) -> Result<()> {
+        self.db.create_checkpoint(path)
+    }
+}
+
+impl<B> BlockStore<B, MemoryBackend>
+where
+    B: Block + WireMessage,
+    B::Txn: WireMessage,
+{
+    /// Create an empty store that is kept in memory, and dropped with it
+    pub fn in_memory() -> Self {
+        Self::create_with_backend(MemoryBackend::new())
+            .expect("writing to a memory backend can't fail")
+    }
+}
+
+impl<B, D> BlockStore<B, D>
+where
+    B: Block + WireMessage,
+    B::Txn: WireMessage,
+    D: Backend,
+{
+    fn create_with_backend(db: D) -> Result<Self> {
+        let self_ = Self {
             db,
             _marker: PhantomData,
-        })
+        };
+
+        self_.set_version(Self::latest_version())?;
+
+        Ok(self_)
     }
 
     pub fn set(&self, block: &B) -> Result<()> {
         // Use a batch to write atomically in case we crash in the middle
-        let mut batch = rocksdb::WriteBatchWithTransaction::<false>::default();
+        let mut batch = WriteBatch::default();
+        self.put_block(&mut batch, block)?;
+        self.db.write(batch)?;
+
+        Ok(())
+    }
 
+    /// Store a block, and mark it as a pending commit in the same atomic write
+    ///
+    /// This is a write-ahead marker for changes that have to be made outside of the block store
+    /// (e.g. to the notes tree). Once they are done, call [`BlockStore::clear_pending_commit`].
+    /// If the process crashes before then, [`BlockStore::get_pending_commit`] returns the height
+    /// of this block on the next startup, so the other changes can be replayed or rolled back
+    pub fn set_pending_commit(&self, block: &B) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        self.put_block(&mut batch, block)?;
+        batch.put(
+            Key::PendingCommit.serialize(),
+            block.block_height().to_be_bytes(),
+        );
+        self.db.write(batch)?;
+
+        Ok(())
+    }
+
+    /// The height of the block stored by [`BlockStore::set_pending_commit`], if it hasn't been
+    /// cleared yet
+    pub fn get_pending_commit(&self) -> Result<Option<BlockHeight>> {
+        if let Some(height) = self.db.get(&Key::PendingCommit.serialize())? {
+            Ok(Some(BlockHeight(u64::from_be_bytes(
+                height.try_into().map_err(|_| Error::InvalidValue)?,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Mark the pending commit as complete
+    pub fn clear_pending_commit(&self) -> Result<()> {
+        self.db.delete(&Key::PendingCommit.serialize())?;
+        Ok(())
+    }
+
+    fn put_block(&self, batch: &mut WriteBatch, block: &B) -> Result<()> {
         let height = block.block_height();
         let block_hash = block.block_hash();
 
@@ -123,12 +271,195 @@ where
             batch.put(k.serialize(), v);
         }
 
+        for (k, v) in Self::element_entries(block) {
+            batch.put(k.serialize(), v);
+        }
+
         if let Some(key) = keys::KeyNonEmptyBlock::from_block(block) {
             batch.put(key.to_key().serialize(), block.to_bytes()?);
         }
 
+        Ok(())
+    }
+
+    /// Remove all blocks above `height`, along with their transactions and indexes, in one atomic
+    /// write
+    ///
+    /// Returns the removed blocks, lowest first, so that changes made from them outside of the block
+    /// store (e.g. to the notes tree) can be rolled back. A pending commit of a removed block is
+    /// cleared
+    pub fn truncate_after(&self, height: BlockHeight) -> Result<Vec<B>> {
+        let removed = self
+            .list(
+                (Bound::Excluded(height), Bound::Unbounded),
+                BlockListOrder::LowestToHighest,
+            )
+            .into_iterator()
+            .map(|r| r.map(|(_, block)| block))
+            .collect::<Result<Vec<_>>>()?;
+
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        let mut batch = WriteBatch::default();
+
+        for block in &removed {
+            self.delete_block(&mut batch, block)?;
+        }
+
+        let max_height = self
+            .list(..=height, BlockListOrder::HighestToLowest)
+            .into_iterator()
+            .next()
+            .transpose()?;
+        match max_height {
+            Some((KeyBlock(max_height), _)) => {
+                batch.put(Key::MaxHeight.serialize(), max_height.to_be_bytes());
+            }
+            None => batch.delete(Key::MaxHeight.serialize()),
+        }
+
+        if self
+            .get_pending_commit()?
+            .map_or(false, |pending| pending > height)
+        {
+            batch.delete(Key::PendingCommit.serialize());
+        }
+
+        // blocks that replace the removed ones haven't been pruned
+        if self
+            .get_pruned_height()?
+            .map_or(false, |pruned_height| pruned_height > height)
+        {
+            batch.put(Key::PrunedHeight.serialize(), height.to_be_bytes());
+        }
+
+        self.db.write(batch)?;
+
+        Ok(removed)
+    }
+
+    /// Prune all blocks at or below `height`, replacing them (and their transactions) with the
+    /// result of [`Block::prune`]
+    ///
+    /// Blocks below the previously pruned height are skipped, so this is cheap to call after every
+    /// new block. Returns the number of blocks that were pruned
+    pub fn prune(&self, height: BlockHeight) -> Result<usize> {
+        let start = match self.get_pruned_height()? {
+            Some(pruned_height) if pruned_height >= height => return Ok(0),
+            Some(pruned_height) => Bound::Excluded(pruned_height),
+            None => Bound::Unbounded,
+        };
+
+        let mut pruned = 0;
+        let mut batch = WriteBatch::default();
+
+        for block in self
+            .list(
+                (start, Bound::Included(height)),
+                BlockListOrder::LowestToHighest,
+            )
+            .into_iterator()
+        {
+            let (_, mut block) = block?;
+
+            let txns = block.txns();
+            block.prune();
+            self.put_pruned_block(&mut batch, &txns, &block)?;
+            pruned += 1;
+
+            if pruned % PRUNE_BATCH_SIZE == 0 {
+                batch.put(
+                    Key::PrunedHeight.serialize(),
+                    block.block_height().to_be_bytes(),
+                );
+                self.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+
+        batch.put(Key::PrunedHeight.serialize(), height.to_be_bytes());
         self.db.write(batch)?;
 
+        Ok(pruned)
+    }
+
+    /// Overwrite a block with its pruned version, given the transactions it had before pruning
+    ///
+    /// Pruning keeps the block's hash, transactions and leaves, so the height and element indexes
+    /// don't change. A transaction can be indexed again by a later block, so its entry is only
+    /// rewritten if it still points to this block
+    fn put_pruned_block(
+        &self,
+        batch: &mut WriteBatch,
+        unpruned_txns: &[B::Txn],
+        block: &B,
+    ) -> Result<()> {
+        let height = block.block_height();
+
+        batch.put(Key::Block(KeyBlock(height)).serialize(), block.to_bytes()?);
+
+        if let Some(key) = keys::KeyNonEmptyBlock::from_block(block) {
+            batch.put(key.to_key().serialize(), block.to_bytes()?);
+        }
+
+        for (unpruned, pruned) in unpruned_txns.iter().zip(block.txns()) {
+            let key = Key::TxnByHash(unpruned.txn_hash()).serialize();
+
+            if self.db.get(&key)?.as_deref() == Some(&unpruned.to_bytes()?[..]) {
+                batch.put(key, pruned.to_bytes()?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The height at or below which all blocks have been pruned, if [`BlockStore::prune`] has been
+    /// called
+    pub fn get_pruned_height(&self) -> Result<Option<BlockHeight>> {
+        if let Some(height) = self.db.get(&Key::PrunedHeight.serialize())? {
+            Ok(Some(BlockHeight(u64::from_be_bytes(
+                height.try_into().map_err(|_| Error::InvalidValue)?,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Delete `block` and its indexes
+    ///
+    /// A transaction or element can be indexed again by a later block, so those index entries are
+    /// only deleted if they still point to this block
+    fn delete_block(&self, batch: &mut WriteBatch, block: &B) -> Result<()> {
+        let height = block.block_height();
+
+        batch.delete(Key::Block(KeyBlock(height)).serialize());
+        batch.delete(Key::BlockHashToHeight(block.block_hash()).serialize());
+        batch.delete(keys::KeyNonEmptyBlock(height).to_key().serialize());
+
+        for e in Self::txn_entries(block) {
+            let (k, v) = e?;
+            let key = k.serialize();
+
+            if self.db.get(&key)?.as_deref() == Some(&v[..]) {
+                batch.delete(key);
+            }
+        }
+
+        for (k, _) in Self::element_entries(block) {
+            let key = k.serialize();
+
+            let location = self
+                .db
+                .get(&key)?
+                .map(|bytes| TxnLocation::from_bytes(&bytes))
+                .transpose()?;
+
+            if location.map_or(false, |location| location.block_height == height) {
+                batch.delete(key);
+            }
+        }
+
         Ok(())
     }
 
@@ -139,19 +470,39 @@ where
             .map(move |tx| Ok((Key::TxnByHash(tx.txn_hash()), tx.to_bytes()?)))
     }
 
+    fn element_entries(block: &B) -> impl Iterator<Item = (Key, [u8; TxnLocation::SIZE])> {
+        let block_height = block.block_height();
+
+        block
+            .txns()
+            .into_iter()
+            .zip(0..)
+            .flat_map(move |(tx, txn_index)| {
+                let location = TxnLocation {
+                    block_height,
+                    txn_index,
+                    txn_hash: tx.txn_hash(),
+                };
+
+                tx.leaves()
+                    .into_iter()
+                    .map(move |element| (Key::ElementToTxn(element), location.to_bytes()))
+            })
+    }
+
     pub fn get(&self, block_number: BlockHeight) -> Result<Option<B>> {
         let key = Key::Block(KeyBlock(block_number)).serialize();
 
-        let block_bytes = self.db.get(key)?;
+        let block_bytes = self.db.get(&key)?;
         let block = block_bytes.map(|bytes| B::from_bytes(&bytes)).transpose()?;
 
         Ok(block)
     }
 
     pub fn get_max_height(&self) -> Result<Option<BlockHeight>> {
-        if let Some(max_block) = self.db.get(Key::MaxHeight.serialize())? {
+        if let Some(max_block) = self.db.get(&Key::MaxHeight.serialize())? {
             Ok(Some(BlockHeight(u64::from_be_bytes(
-                max_block.try_into().unwrap(),
+                max_block.try_into().map_err(|_| Error::InvalidValue)?,
             ))))
         } else {
             Ok(None)
@@ -161,9 +512,9 @@ where
     pub fn get_block_height_by_hash(&self, block_hash: [u8; 32]) -> Result<Option<BlockHeight>> {
         let key_bytes = Key::BlockHashToHeight(block_hash).serialize();
 
-        if let Some(block_height) = self.db.get(key_bytes)? {
+        if let Some(block_height) = self.db.get(&key_bytes)? {
             Ok(Some(BlockHeight(u64::from_be_bytes(
-                block_height.try_into().unwrap(),
+                block_height.try_into().map_err(|_| Error::InvalidValue)?,
             ))))
         } else {
             Ok(None)
@@ -172,7 +523,7 @@ where
 
     pub fn get_pending_block(&self) -> Result<Option<B>> {
         let key = Key::PendingBlock;
-        let bytes = self.db.get(key.serialize())?;
+        let bytes = self.db.get(&key.serialize())?;
         let block = bytes.map(|bytes| B::from_bytes(&bytes)).transpose()?;
 
         Ok(block)
@@ -180,7 +531,7 @@ where
 
     pub fn get_txn_by_hash(&self, txn_hash: [u8; 32]) -> Result<Option<B::Txn>> {
         let key = Key::TxnByHash(txn_hash);
-        let bytes = self.db.get(key.serialize())?;
+        let bytes = self.db.get(&key.serialize())?;
 
         if let Some(bytes) = bytes {
             Ok(Some(B::Txn::from_bytes(&bytes)?))
@@ -189,9 +540,21 @@ where
         }
     }
 
+    /// Find the transaction that inserted `element` (a commitment or nullifier)
+    pub fn get_txn_location_by_element(&self, element: [u8; 32]) -> Result<Option<TxnLocation>> {
+        let key = Key::ElementToTxn(element);
+        let bytes = self.db.get(&key.serialize())?;
+
+        bytes
+            .map(|bytes| TxnLocation::from_bytes(&bytes))
+            .transpose()
+    }
+
     fn store_version(&self) -> Result<u32> {
-        if let Some(version) = self.db.get(Key::StoreVersion.serialize())? {
-            Ok(u32::from_be_bytes(version.try_into().unwrap()))
+        if let Some(version) = self.db.get(&Key::StoreVersion.serialize())? {
+            Ok(u32::from_be_bytes(
+                version.try_into().map_err(|_| Error::InvalidValue)?,
+            ))
         } else {
             Ok(0)
         }
@@ -199,7 +562,7 @@ where
 
     fn set_store_version(&self, version: u32) -> Result<()> {
         self.db
-            .put(Key::StoreVersion.serialize(), version.to_be_bytes())?;
+            .put(&Key::StoreVersion.serialize(), &version.to_be_bytes())?;
         Ok(())
     }
 }
@@ -227,12 +590,53 @@ mod tests {
         fn txns(&self) -> Vec<Self::Txn> {
             self.inner().2.clone()
         }
+
+        // dummy transactions only have a hash, so there is nothing to drop
+        fn prune(&mut self) {}
     }
 
     impl Transaction for DummyTxn {
         fn txn_hash(&self) -> [u8; 32] {
             *self.inner()
         }
+
+        // each dummy transaction has a single leaf, which is its hash
+        fn leaves(&self) -> Vec<[u8; 32]> {
+            vec![*self.inner()]
+        }
+    }
+
+    /// Like [`DummyBlock`], but each transaction also records the height of the block it's in,
+    /// so the same transaction stored by two blocks has different bytes
+    type HeightBlock = wire_message::test_api::DummyMsg<(BlockHeight, [u8; 32], Vec<HeightTxn>)>;
+    type HeightTxn = wire_message::test_api::DummyMsg<([u8; 32], BlockHeight)>;
+
+    impl Block for HeightBlock {
+        type Txn = HeightTxn;
+
+        fn block_height(&self) -> BlockHeight {
+            self.inner().0
+        }
+
+        fn block_hash(&self) -> [u8; 32] {
+            self.inner().1
+        }
+
+        fn txns(&self) -> Vec<Self::Txn> {
+            self.inner().2.clone()
+        }
+
+        fn prune(&mut self) {}
+    }
+
+    impl Transaction for HeightTxn {
+        fn txn_hash(&self) -> [u8; 32] {
+            self.inner().0
+        }
+
+        fn leaves(&self) -> Vec<[u8; 32]> {
+            vec![self.inner().0]
+        }
     }
 
     fn temp_dir() -> TempDir {
@@ -248,8 +652,15 @@ mod tests {
     #[test]
     fn test_set_and_get() {
         let temp_dir = temp_dir();
-        let block_store = BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap();
+        set_and_get(BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_set_and_get_in_memory() {
+        set_and_get(BlockStore::<DummyBlock, MemoryBackend>::in_memory());
+    }
 
+    fn set_and_get<D: Backend>(block_store: BlockStore<DummyBlock, D>) {
         let block_number = BlockHeight(1);
         let txns = vec![DummyTxn::V1([123; 32]), DummyTxn::V1([124; 32])];
         let block_data = DummyBlock::V1((block_number, [0; 32], txns.clone()));
@@ -293,8 +704,15 @@ mod tests {
     #[test]
     fn test_list_blocks() {
         let temp_dir = temp_dir();
-        let block_store = BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap();
+        list_blocks(BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_list_blocks_in_memory() {
+        list_blocks(BlockStore::<DummyBlock, MemoryBackend>::in_memory());
+    }
 
+    fn list_blocks<D: Backend>(block_store: BlockStore<DummyBlock, D>) {
         // Insert some blocks
         for i in 0..10_000 {
             block_store
@@ -362,16 +780,279 @@ mod tests {
         assert_eq!(blocks[1..], before_blocks_except_first);
     }
 
+    #[test]
+    fn txn_location_by_element() {
+        let temp_dir = temp_dir();
+        let block_store = BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap();
+
+        let txns = vec![DummyTxn::V1([1; 32]), DummyTxn::V1([2; 32])];
+        block_store
+            .set(&DummyBlock::V1((BlockHeight(3), [0; 32], txns)))
+            .unwrap();
+
+        assert_eq!(
+            block_store.get_txn_location_by_element([2; 32]).unwrap(),
+            Some(TxnLocation {
+                block_height: BlockHeight(3),
+                txn_index: 1,
+                txn_hash: [2; 32],
+            })
+        );
+        assert_eq!(
+            block_store.get_txn_location_by_element([3; 32]).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn pending_commit() {
+        let temp_dir = temp_dir();
+        let block_store = BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap();
+
+        assert_eq!(block_store.get_pending_commit().unwrap(), None);
+
+        let block = DummyBlock::V1((BlockHeight(5), [1; 32], vec![]));
+        block_store.set_pending_commit(&block).unwrap();
+
+        assert_eq!(block_store.get(BlockHeight(5)).unwrap(), Some(block));
+        assert_eq!(
+            block_store.get_pending_commit().unwrap(),
+            Some(BlockHeight(5))
+        );
+
+        block_store.clear_pending_commit().unwrap();
+        assert_eq!(block_store.get_pending_commit().unwrap(), None);
+    }
+
+    #[test]
+    fn truncate_after() {
+        let block_store = BlockStore::<DummyBlock, MemoryBackend>::in_memory();
+
+        let blocks = (1..=5u8)
+            .map(|i| {
+                let txns = vec![DummyTxn::V1([i; 32])];
+                DummyBlock::V1((BlockHeight(u64::from(i)), [i; 32], txns))
+            })
+            .collect::<Vec<_>>();
+
+        for block in &blocks[..4] {
+            block_store.set(block).unwrap();
+        }
+        block_store.set_pending_commit(&blocks[4]).unwrap();
+
+        let removed = block_store.truncate_after(BlockHeight(2)).unwrap();
+        assert_eq!(removed, blocks[2..]);
+
+        assert_eq!(block_store.get_max_height().unwrap(), Some(BlockHeight(2)));
+        assert_eq!(block_store.get_pending_commit().unwrap(), None);
+        assert_eq!(
+            block_store.get(BlockHeight(2)).unwrap(),
+            Some(blocks[1].clone())
+        );
+
+        for i in 3..=5u8 {
+            assert_eq!(block_store.get(BlockHeight(u64::from(i))).unwrap(), None);
+            assert_eq!(block_store.get_block_height_by_hash([i; 32]).unwrap(), None);
+            assert_eq!(block_store.get_txn_by_hash([i; 32]).unwrap(), None);
+            assert_eq!(
+                block_store.get_txn_location_by_element([i; 32]).unwrap(),
+                None
+            );
+        }
+
+        let non_empty = block_store
+            .list_non_empty(.., BlockListOrder::LowestToHighest)
+            .into_iterator()
+            .count();
+        assert_eq!(non_empty, 2);
+
+        // truncating below every block empties the store
+        assert_eq!(
+            block_store.truncate_after(BlockHeight(0)).unwrap(),
+            blocks[..2]
+        );
+        assert_eq!(block_store.get_max_height().unwrap(), None);
+        assert!(block_store
+            .truncate_after(BlockHeight(0))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn truncate_after_keeps_indexes_of_remaining_blocks() {
+        let block_store = BlockStore::<HeightBlock, MemoryBackend>::in_memory();
+
+        let txn = |height| HeightTxn::V1(([9; 32], BlockHeight(height)));
+        let block_2 = HeightBlock::V1((BlockHeight(2), [2; 32], vec![txn(2)]));
+        let block_3 = HeightBlock::V1((BlockHeight(3), [3; 32], vec![txn(3)]));
+
+        // the transaction is indexed by block 2, which is written last
+        block_store.set(&block_3).unwrap();
+        block_store.set(&block_2).unwrap();
+
+        assert_eq!(
+            block_store.truncate_after(BlockHeight(2)).unwrap(),
+            vec![block_3]
+        );
+
+        assert_eq!(block_store.get_txn_by_hash([9; 32]).unwrap(), Some(txn(2)));
+        assert_eq!(
+            block_store
+                .get_txn_location_by_element([9; 32])
+                .unwrap()
+                .map(|location| location.block_height),
+            Some(BlockHeight(2))
+        );
+    }
+
+    #[test]
+    fn prune_keeps_indexes_of_later_blocks() {
+        let block_store = BlockStore::<HeightBlock, MemoryBackend>::in_memory();
+
+        let txn = |height| HeightTxn::V1(([9; 32], BlockHeight(height)));
+        let block_2 = HeightBlock::V1((BlockHeight(2), [2; 32], vec![txn(2)]));
+        let block_3 = HeightBlock::V1((BlockHeight(3), [3; 32], vec![txn(3)]));
+
+        // the transaction is indexed by block 3, which is written last
+        block_store.set(&block_2).unwrap();
+        block_store.set(&block_3).unwrap();
+
+        assert_eq!(block_store.prune(BlockHeight(2)).unwrap(), 1);
+
+        assert_eq!(block_store.get(BlockHeight(2)).unwrap(), Some(block_2));
+        assert_eq!(block_store.get_txn_by_hash([9; 32]).unwrap(), Some(txn(3)));
+        assert_eq!(
+            block_store
+                .get_txn_location_by_element([9; 32])
+                .unwrap()
+                .map(|location| location.block_height),
+            Some(BlockHeight(3))
+        );
+    }
+
+    #[test]
+    fn prune() {
+        let block_store = BlockStore::<DummyBlock, MemoryBackend>::in_memory();
+
+        for i in 1..=5u8 {
+            let txns = vec![DummyTxn::V1([i; 32])];
+            block_store
+                .set(&DummyBlock::V1((BlockHeight(u64::from(i)), [i; 32], txns)))
+                .unwrap();
+        }
+
+        assert_eq!(block_store.get_pruned_height().unwrap(), None);
+        assert_eq!(block_store.prune(BlockHeight(3)).unwrap(), 3);
+        assert_eq!(
+            block_store.get_pruned_height().unwrap(),
+            Some(BlockHeight(3))
+        );
+
+        // pruned blocks keep their indexes
+        assert_eq!(
+            block_store.get_block_height_by_hash([3; 32]).unwrap(),
+            Some(BlockHeight(3))
+        );
+        assert_eq!(
+            block_store.get_txn_by_hash([3; 32]).unwrap(),
+            Some(DummyTxn::V1([3; 32]))
+        );
+        assert_eq!(
+            block_store
+                .get_txn_location_by_element([3; 32])
+                .unwrap()
+                .map(|location| location.block_height),
+            Some(BlockHeight(3))
+        );
+
+        // already pruned blocks are skipped
+        assert_eq!(block_store.prune(BlockHeight(3)).unwrap(), 0);
+        assert_eq!(block_store.prune(BlockHeight(4)).unwrap(), 1);
+
+        // blocks that are removed and added again need to be pruned again
+        block_store.truncate_after(BlockHeight(2)).unwrap();
+        assert_eq!(
+            block_store.get_pruned_height().unwrap(),
+            Some(BlockHeight(2))
+        );
+    }
+
+    #[test]
+    fn checkpoint() {
+        let temp_dir = temp_dir();
+        let block_store =
+            BlockStore::<DummyBlock>::create_or_load(&temp_dir.path().join("db")).unwrap();
+
+        let block = DummyBlock::V1((BlockHeight(1), [1; 32], vec![DummyTxn::V1([2; 32])]));
+        block_store.set(&block).unwrap();
+
+        let checkpoint_path = temp_dir.path().join("checkpoint");
+        block_store.create_checkpoint(&checkpoint_path).unwrap();
+
+        // later writes aren't included in the checkpoint
+        block_store
+            .set(&DummyBlock::V1((BlockHeight(2), [2; 32], vec![])))
+            .unwrap();
+
+        let checkpoint = BlockStore::<DummyBlock>::create_or_load(&checkpoint_path).unwrap();
+        assert_eq!(checkpoint.get(BlockHeight(1)).unwrap(), Some(block));
+        assert_eq!(checkpoint.get_max_height().unwrap(), Some(BlockHeight(1)));
+        assert_eq!(
+            checkpoint.get_txn_by_hash([2; 32]).unwrap(),
+            Some(DummyTxn::V1([2; 32]))
+        );
+    }
+
+    #[test]
+    fn secondary() {
+        let temp_dir = temp_dir();
+        let primary =
+            BlockStore::<DummyBlock>::create_or_load(&temp_dir.path().join("db")).unwrap();
+
+        let block = DummyBlock::V1((BlockHeight(1), [1; 32], vec![DummyTxn::V1([2; 32])]));
+        primary.set(&block).unwrap();
+
+        let secondary = BlockStore::<DummyBlock>::open_secondary(
+            &temp_dir.path().join("db"),
+            &temp_dir.path().join("secondary"),
+        )
+        .unwrap();
+        assert_eq!(secondary.get(BlockHeight(1)).unwrap(), Some(block));
+
+        primary
+            .set(&DummyBlock::V1((BlockHeight(2), [2; 32], vec![])))
+            .unwrap();
+        assert_eq!(secondary.get_max_height().unwrap(), Some(BlockHeight(1)));
+
+        secondary.catch_up_with_primary().unwrap();
+        assert_eq!(secondary.get_max_height().unwrap(), Some(BlockHeight(2)));
+        assert_eq!(
+            secondary
+                .list(.., BlockListOrder::HighestToLowest)
+                .into_iterator()
+                .count(),
+            2
+        );
+
+        // writes only go to the primary
+        assert!(secondary
+            .set(&DummyBlock::V1((BlockHeight(3), [3; 32], vec![])))
+            .is_err());
+    }
+
     #[test]
     fn successor() {
         let temp_dir = temp_dir();
-        let db = BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap();
+        successor_is_not_listed(BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap());
+        successor_is_not_listed(BlockStore::<DummyBlock, MemoryBackend>::in_memory());
+    }
 
+    fn successor_is_not_listed<D: Backend>(db: BlockStore<DummyBlock, D>) {
         let height = BlockHeight(u64::MAX);
         db.set(&DummyBlock::V1((height, [0; 32], vec![]))).unwrap();
         db.db
             .put(
-                Key::Block(KeyBlock(BlockHeight(u64::MAX))).serialize_immediate_successor(),
+                &Key::Block(KeyBlock(BlockHeight(u64::MAX))).serialize_immediate_successor(),
                 b"test",
             )
             .unwrap();
@@ -383,4 +1064,56 @@ mod tests {
             1
         );
     }
+
+    #[test]
+    fn raw_keys_can_be_decoded() {
+        let db = BlockStore::<DummyBlock, MemoryBackend>::in_memory();
+        db.set(&DummyBlock::V1((
+            BlockHeight(1),
+            [1; 32],
+            vec![DummyTxn::V1([2; 32])],
+        )))
+        .unwrap();
+
+        let keys = db
+            .db
+            .iter_range(vec![], None, Direction::Forward)
+            .map(|entry| {
+                let (key, value) = entry.unwrap();
+                let key = Key::deserialize(&key).unwrap();
+
+                if key.value_kind() == ValueKind::TxnLocation {
+                    assert_eq!(
+                        TxnLocation::from_bytes(&value).unwrap().block_height,
+                        BlockHeight(1)
+                    );
+                }
+
+                key.to_string()
+            })
+            .collect::<Vec<_>>();
+
+        assert!(keys.contains(&"block/1".to_owned()));
+        assert!(keys.contains(&format!("element_to_txn/{}", "02".repeat(32))));
+        assert!(keys.contains(&"store_version".to_owned()));
+    }
+
+    #[test]
+    fn short_keys_are_invalid() {
+        for key in [
+            vec![],
+            vec![0, 1, 2],
+            vec![2; 20],
+            vec![4; 31],
+            vec![6],
+            vec![8; 10],
+            vec![8; 34],
+            vec![255],
+        ] {
+            assert!(
+                matches!(Key::deserialize(&key), Err(Error::InvalidKey)),
+                "{key:?} should be invalid"
+            );
+        }
+    }
 }
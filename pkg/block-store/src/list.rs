@@ -1,12 +1,11 @@
 use std::{marker::PhantomData, ops::RangeBounds};
 
 use primitives::{block_height::BlockHeight, pagination::CursorChoice};
-use rocksdb::DB;
 use wire_message::WireMessage;
 
 use crate::{
     keys::{Key, KeyBlock, KeyNonEmptyBlock, ListableKey, StoreValue},
-    Block, BlockListOrder, BlockStore, Error, Result,
+    Backend, Block, BlockListOrder, BlockStore, Direction, Error, Result,
 };
 
 pub trait StoreList {
@@ -21,7 +20,7 @@ pub trait StoreList {
 }
 
 pub struct List<'db, Stored> {
-    pub(crate) db: &'db DB,
+    pub(crate) db: &'db dyn Backend,
     pub(crate) start_key: Key,
     pub(crate) end_key: Key,
     pub(crate) lower_exclusive: bool,
@@ -48,18 +47,14 @@ where
             false => self.end_key.serialize(),
         };
 
-        let mut read_opts = rocksdb::ReadOptions::default();
-
-        read_opts.set_iterate_lower_bound(lower_bound);
-        read_opts.set_iterate_upper_bound(upper_bound);
-
-        let iter = self.db.iterator_opt(
+        let iter = self.db.iter_range(
+            lower_bound,
+            Some(upper_bound),
             if self.start_to_end {
-                rocksdb::IteratorMode::Start
+                Direction::Forward
             } else {
-                rocksdb::IteratorMode::End
+                Direction::Reverse
             },
-            read_opts,
         );
 
         iter.map(move |r| {
@@ -123,10 +118,11 @@ where
     }
 }
 
-impl<B> BlockStore<B>
+impl<B, D> BlockStore<B, D>
 where
     B: Block + WireMessage,
     B::Txn: WireMessage,
+    D: Backend,
 {
     pub fn list(
         &self,
@@ -205,15 +201,11 @@ where
     }
 
     pub fn list_txns(&self) -> impl Iterator<Item = Result<B::Txn>> + '_ {
-        let mut read_opts = rocksdb::ReadOptions::default();
-
-        read_opts.set_iterate_lower_bound(Key::TxnByHash([0; 32]).serialize());
-        read_opts
-            .set_iterate_upper_bound(Key::TxnByHash([255; 32]).serialize_immediate_successor());
-
-        let iter = self
-            .db
-            .iterator_opt(rocksdb::IteratorMode::Start, read_opts);
+        let iter = self.db.iter_range(
+            Key::TxnByHash([0; 32]).serialize(),
+            Some(Key::TxnByHash([255; 32]).serialize_immediate_successor()),
+            Direction::Forward,
+        );
         iter.map(|r| {
             let (_, value) = r?;
             Ok(B::Txn::from_bytes(&value)?)
@@ -1,67 +1,159 @@
+use primitives::{
+    block_height::BlockHeight,
+    migration::{self, Checkpoint, Migrate, Migration, Step},
+};
 use wire_message::WireMessage;
 
 use super::Result;
 use crate::{
-    keys::{self, BlockListOrder, StoreKey},
-    Block, BlockStore, Error, StoreList,
+    keys::{self, BlockListOrder, Key, StoreKey},
+    Backend, Block, BlockStore, Error, StoreList, WriteBatch,
 };
 
-pub(crate) const LATEST_VERSION: u32 = 1;
+/// The number of blocks each migration step rewrites
+const MIGRATION_BATCH_SIZE: usize = 1000;
 
-impl<B> BlockStore<B>
+impl<B, D> Migrate for BlockStore<B, D>
 where
     B: Block + WireMessage,
     B::Txn: WireMessage,
+    D: Backend,
 {
-    pub fn migrate(&self) -> super::Result<()> {
-        loop {
-            let version = self.store_version()?;
-
-            match version {
-                0 => self.migrate_to_v1()?,
-                1 => break,
-                other => return Err(Error::InvalidVersion(other)),
-            }
-        }
+    type Context = ();
+    type Error = Error;
 
-        Ok(())
+    fn migrations() -> Vec<Migration<Self>> {
+        vec![
+            Migration {
+                id: "txn-index",
+                description: "Index transactions by hash, and blocks that have transactions",
+                step: Self::index_txns,
+            },
+            Migration {
+                id: "element-index",
+                description: "Index transactions by the elements they insert",
+                step: Self::index_elements,
+            },
+        ]
     }
 
-    #[tracing::instrument(skip(self))]
-    fn migrate_to_v1(&self) -> Result<()> {
-        tracing::info!("Migrating block store to version 1");
+    fn version(&self) -> Result<u64> {
+        Ok(self.store_version()?.into())
+    }
 
-        for block in self
-            .list(.., BlockListOrder::LowestToHighest)
-            .into_iterator()
-        {
-            let (_, block) = block?;
+    fn set_version(&self, version: u64) -> Result<()> {
+        self.set_store_version(version as u32)
+    }
+
+    fn checkpoint(&self) -> Result<Option<Checkpoint>> {
+        self.db
+            .get(&Key::MigrationCheckpoint.serialize())?
+            .map(|bytes| Checkpoint::from_bytes(&bytes))
+            .transpose()
+            .map_err(Error::from)
+    }
+
+    fn set_checkpoint(&self, checkpoint: Option<&Checkpoint>) -> Result<()> {
+        let key = Key::MigrationCheckpoint.serialize();
+
+        match checkpoint {
+            Some(checkpoint) => self.db.put(&key, &checkpoint.to_bytes()),
+            None => self.db.delete(&key),
+        }
+    }
+}
 
-            let mut batch = rocksdb::WriteBatchWithTransaction::<false>::default();
+impl<B, D> BlockStore<B, D>
+where
+    B: Block + WireMessage,
+    B::Txn: WireMessage,
+    D: Backend,
+{
+    /// Apply any pending migrations, see [`primitives::migration::migrate`]
+    pub fn migrate(&self) -> Result<()> {
+        migration::migrate(self, &(), false)?;
+        Ok(())
+    }
 
-            let txn_indexes = Self::txn_entries(&block);
-            for e in txn_indexes {
+    fn index_txns(&self, _ctx: &(), cursor: Option<&[u8]>, dry_run: bool) -> Result<Step> {
+        self.migrate_blocks(cursor, dry_run, |batch, block| {
+            for e in Self::txn_entries(block) {
                 let (k, v) = e?;
 
                 batch.put(k.serialize(), v);
             }
 
-            if let Some(key) = keys::KeyNonEmptyBlock::from_block(&block) {
+            if let Some(key) = keys::KeyNonEmptyBlock::from_block(block) {
                 batch.put(key.to_key().serialize(), block.to_bytes()?);
             }
 
-            self.db.write(batch)?;
+            Ok(())
+        })
+    }
+
+    fn index_elements(&self, _ctx: &(), cursor: Option<&[u8]>, dry_run: bool) -> Result<Step> {
+        self.migrate_blocks(cursor, dry_run, |batch, block| {
+            for (k, v) in Self::element_entries(block) {
+                batch.put(k.serialize(), v);
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Write the entries added by `f` for up to [`MIGRATION_BATCH_SIZE`] blocks, starting at the
+    /// height in `cursor`
+    fn migrate_blocks(
+        &self,
+        cursor: Option<&[u8]>,
+        dry_run: bool,
+        f: impl Fn(&mut WriteBatch, &B) -> Result<()>,
+    ) -> Result<Step> {
+        let start = match cursor {
+            Some(cursor) => BlockHeight(u64::from_be_bytes(
+                cursor
+                    .try_into()
+                    .map_err(|_| migration::Error::InvalidCheckpoint)?,
+            )),
+            None => BlockHeight(0),
+        };
+
+        let mut batch = WriteBatch::default();
+        let mut items = 0;
+        let mut last_height = None;
+
+        for block in self
+            .list(start.., BlockListOrder::LowestToHighest)
+            .into_iterator()
+            .take(MIGRATION_BATCH_SIZE)
+        {
+            let (_, block) = block?;
+
+            f(&mut batch, &block)?;
+            items += 1;
+            last_height = Some(block.block_height());
         }
 
-        self.set_store_version(1)?;
+        if !dry_run {
+            self.db.write(batch)?;
+        }
 
-        Ok(())
+        match last_height {
+            Some(height) if items == MIGRATION_BATCH_SIZE as u64 => Ok(Step::Continue {
+                cursor: height.next().to_be_bytes().to_vec(),
+                items,
+            }),
+            _ => Ok(Step::Done { items }),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::DummyBlock;
+    use crate::{
+        tests::{DummyBlock, DummyTxn},
+        MemoryBackend, TxnLocation,
+    };
 
     use super::*;
     use tempdir::TempDir;
@@ -70,6 +162,10 @@ mod tests {
         TempDir::new("block-store").unwrap()
     }
 
+    fn dummy_block(height: u64, element: [u8; 32]) -> DummyBlock {
+        DummyBlock::V1((BlockHeight(height), [0; 32], vec![DummyTxn::V1(element)]))
+    }
+
     #[test]
     fn test_migrate() {
         let temp_dir = temp_dir();
@@ -77,6 +173,93 @@ mod tests {
 
         block_store.migrate().unwrap();
 
-        assert_eq!(block_store.store_version().unwrap(), LATEST_VERSION);
+        assert_eq!(BlockStore::<DummyBlock>::latest_version(), 2);
+        assert_eq!(
+            block_store.version().unwrap(),
+            BlockStore::<DummyBlock>::latest_version()
+        );
+    }
+
+    #[test]
+    fn test_migrate_to_v2_backfills_element_index() {
+        let temp_dir = temp_dir();
+        let block_store = BlockStore::<DummyBlock>::create_or_load(temp_dir.path()).unwrap();
+
+        block_store.set(&dummy_block(1, [1; 32])).unwrap();
+
+        // simulate a store written before the index existed
+        block_store
+            .db
+            .delete(&Key::ElementToTxn([1; 32]).serialize())
+            .unwrap();
+        block_store.set_store_version(1).unwrap();
+        assert_eq!(
+            block_store.get_txn_location_by_element([1; 32]).unwrap(),
+            None
+        );
+
+        block_store.migrate().unwrap();
+
+        assert_eq!(block_store.store_version().unwrap(), 2);
+        assert_eq!(
+            block_store.get_txn_location_by_element([1; 32]).unwrap(),
+            Some(TxnLocation {
+                block_height: BlockHeight(1),
+                txn_index: 0,
+                txn_hash: [1; 32],
+            })
+        );
+    }
+
+    #[test]
+    fn test_migrate_dry_run_and_resume() {
+        let block_store = BlockStore::<DummyBlock, MemoryBackend>::in_memory();
+
+        for height in 1..=3 {
+            block_store
+                .set(&dummy_block(height, [height as u8; 32]))
+                .unwrap();
+            block_store
+                .db
+                .delete(&Key::ElementToTxn([height as u8; 32]).serialize())
+                .unwrap();
+        }
+        block_store.set_store_version(1).unwrap();
+
+        let runs = migration::migrate(&block_store, &(), true).unwrap();
+        assert_eq!(runs[0].items, 3);
+        assert_eq!(block_store.version().unwrap(), 1);
+        assert_eq!(
+            block_store.get_txn_location_by_element([1; 32]).unwrap(),
+            None
+        );
+
+        // simulate a migration that stopped after the first two blocks
+        block_store
+            .set_checkpoint(Some(&Checkpoint {
+                version: 2,
+                cursor: BlockHeight(3).to_be_bytes().to_vec(),
+                items: 2,
+            }))
+            .unwrap();
+        assert_eq!(
+            migration::status(&block_store).unwrap()[1].progress,
+            Some(2)
+        );
+
+        let runs = migration::migrate(&block_store, &(), false).unwrap();
+        assert_eq!(runs[0].items, 3);
+        assert_eq!(block_store.version().unwrap(), 2);
+        assert_eq!(block_store.checkpoint().unwrap(), None);
+
+        // only the blocks after the checkpoint were migrated
+        assert_eq!(
+            block_store.get_txn_location_by_element([1; 32]).unwrap(),
+            None
+        );
+        assert!(block_store
+            .get_txn_location_by_element([3; 32])
+            .unwrap()
+            .is_some());
     }
 }
@@ -5,10 +5,14 @@ use clap::Parser;
 use eyre::Result;
 use futures::Future;
 use node::{
-    config::{cli::CliArgs, Config},
+    config::{
+        cli::{CliArgs, Command},
+        Config,
+    },
     create_rpc_server,
 };
 use node::{Mode, Node, TxnStats};
+use primitives::block_height::BlockHeight;
 use rpc::tracing::setup_tracing;
 
 #[tokio::main]
@@ -36,6 +40,56 @@ async fn main() -> Result<()> {
         config.env_name.clone(),
     )?;
 
+    match args.command {
+        Some(Command::CheckSmirk { repair }) => {
+            if !Node::check_smirk(&config, repair)? {
+                eyre::bail!("smirk is inconsistent, see the logs for details");
+            }
+
+            return Ok(());
+        }
+        Some(Command::Rollback { height }) => {
+            Node::rollback(&config, BlockHeight(height))?;
+            return Ok(());
+        }
+        Some(Command::Migrations { dry_run }) => {
+            for migration in Node::migrations(&config, dry_run)? {
+                println!("{}", serde_json::to_string(&migration)?);
+            }
+
+            return Ok(());
+        }
+        Some(Command::Dump { store }) => {
+            Node::dump_store(&config, store, |entry| println!("{entry}"))?;
+            return Ok(());
+        }
+        Some(Command::Decode {
+            message_type,
+            hex: message,
+        }) => {
+            let bytes = hex::decode(message.trim_start_matches("0x"))?;
+            println!("{}", Node::decode_message(message_type, &bytes)?);
+            return Ok(());
+        }
+        Some(Command::Encode { message_type, json }) => {
+            let bytes = Node::encode_message(message_type, serde_json::from_str(&json)?)?;
+            println!("{}", hex::encode(bytes));
+            return Ok(());
+        }
+        Some(Command::ListBackups) => {
+            for backup in Node::list_backups(&config)? {
+                println!("{}", serde_json::to_string(&backup)?);
+            }
+
+            return Ok(());
+        }
+        Some(Command::RestoreBackup { name }) => {
+            Node::restore_backup(&config, &name)?;
+            return Ok(());
+        }
+        None => {}
+    }
+
     // Listen address of the server
     let rpc_laddr = config.rpc_laddr.clone();
 
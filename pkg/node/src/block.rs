@@ -1,5 +1,5 @@
 use block_store::BlockStore;
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use ethereum_types::U256;
 use primitives::{hash::CryptoHash, peer::PeerIdSigner};
 use serde::{Deserialize, Serialize};
@@ -17,7 +17,16 @@ use crate::{Error, Mode};
 use primitives::sig::Signature;
 
 #[derive(
-    Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+    Debug,
+    Default,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    BorshSchema,
 )]
 pub struct Block {
     pub content: BlockContent,
@@ -25,7 +34,16 @@ pub struct Block {
 }
 
 #[derive(
-    Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+    Debug,
+    Default,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    BorshSchema,
 )]
 pub struct BlockContent {
     pub header: BlockHeader,
@@ -33,7 +51,16 @@ pub struct BlockContent {
 }
 
 #[derive(
-    Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+    Debug,
+    Default,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    BorshSchema,
 )]
 pub struct BlockHeader {
     pub height: BlockHeight,
@@ -44,7 +71,16 @@ pub struct BlockHeader {
 }
 
 #[derive(
-    Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+    Debug,
+    Default,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    BorshSchema,
 )]
 pub struct BlockState {
     pub root_hash: Element,
@@ -1,5 +1,5 @@
-use crate::Mode;
-use clap::Parser;
+use crate::{MessageType, Mode, Store};
+use clap::{Parser, Subcommand};
 use libp2p::multiaddr::Multiaddr;
 use primitives::peer::PeerIdSigner;
 use rpc::tracing::{LogFormat, LogLevel};
@@ -54,6 +54,14 @@ pub struct CliArgs {
     #[arg(long, env = "POLY_SMIRK_PATH")]
     pub smirk_path: Option<PathBuf>,
 
+    /// Backup path
+    #[arg(long, env = "POLY_BACKUP_PATH")]
+    pub backup_path: Option<PathBuf>,
+
+    /// Replica path
+    #[arg(long, env = "POLY_REPLICA_PATH")]
+    pub replica_path: Option<PathBuf>,
+
     /// Ethereum RPC URL
     #[arg(long, env = "POLY_ETH_RPC_URL")]
     pub eth_rpc_url: Option<String>,
@@ -65,4 +73,70 @@ pub struct CliArgs {
     /// Sync chunk size
     #[arg(long, env = "POLY_SYNC_CHUNK_SIZE")]
     pub sync_chunk_size: Option<u64>,
+
+    /// Run a maintenance command instead of starting the node
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Check that the smirk database is consistent with itself and with the latest block
+    CheckSmirk {
+        /// Rebuild smirk's stored hashes if they are inconsistent
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Roll back the chain to a block height, removing all later blocks and their leaves
+    Rollback {
+        /// The last block height to keep
+        height: u64,
+    },
+
+    /// List the migrations of the block store and the prover db, and whether they have been
+    /// applied. Pending migrations are applied when the node starts
+    Migrations {
+        /// Run the pending block store migrations without changing the store, to find how many
+        /// blocks they would migrate. The prover db's migrations need the rollup contract, so
+        /// they are reported as unsupported
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Print every key and value of one of the node's stores as JSON lines, decoding the borsh
+    /// values. The store is opened read-only, so this can be run while the node is running
+    Dump {
+        /// The store to dump
+        #[arg(value_enum)]
+        store: Store,
+    },
+
+    /// Decode a hex encoded message to versioned JSON
+    Decode {
+        /// The type of the message
+        #[arg(value_enum, long = "type")]
+        message_type: MessageType,
+        /// The message, as hex
+        hex: String,
+    },
+
+    /// Encode versioned JSON, as printed by `decode`, to a hex encoded message
+    Encode {
+        /// The type of the message
+        #[arg(value_enum, long = "type")]
+        message_type: MessageType,
+        /// The message, as versioned JSON
+        json: String,
+    },
+
+    /// List the backups in the backup path
+    ListBackups,
+
+    /// Restore a backup into the db and smirk paths, which must not contain any data
+    RestoreBackup {
+        /// The name of the backup to restore
+        name: String,
+    },
 }
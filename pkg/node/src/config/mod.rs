@@ -55,6 +55,33 @@ pub struct Config {
     /// Path to Smirk
     pub smirk_path: PathBuf,
 
+    /// Path to store backups of the database and Smirk
+    pub backup_path: PathBuf,
+
+    /// In [`Mode::Replica`], the path to the replica's own files. The database and Smirk are read
+    /// from `db_path` and `smirk_path`, which belong to the primary node
+    pub replica_path: PathBuf,
+
+    /// In [`Mode::Replica`], how often to read new blocks from the primary node
+    pub replica_catch_up_interval_ms: u64,
+
+    /// Number of backups to keep, older backups are removed when a new one is created (0 keeps
+    /// all backups)
+    pub backup_retention: usize,
+
+    /// Bearer token required by the admin RPC endpoints, which are disabled if this isn't set
+    pub admin_token: Option<String>,
+
+    /// Whether to keep the proofs of old blocks
+    pub storage_mode: StorageMode,
+
+    /// In [`StorageMode::Full`], the proofs of blocks more than this many blocks behind the latest
+    /// block are pruned
+    pub prune_depth: u64,
+
+    /// Maximum number of bytes used to cache each smirk tree's hashes
+    pub smirk_cache_memory_budget: usize,
+
     pub eth_rpc_url: String,
 
     pub rollup_contract_addr: String,
@@ -76,6 +103,18 @@ pub struct Config {
     pub safe_eth_height_offset: u64,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageMode {
+    /// Keep every block in full
+    #[default]
+    Archive,
+
+    /// Keep the headers, root hashes and transaction hashes of every block, but prune the proofs
+    /// of old blocks
+    Full,
+}
+
 impl Config {
     /// The text of the default config string
     pub const DEFAULT_STR: &str = include_str!("./default_config.toml");
@@ -141,6 +180,26 @@ impl Config {
                 .join(config.smirk_path.strip_prefix("~").unwrap());
         }
 
+        if let Some(backup_path) = args.backup_path {
+            config.backup_path = backup_path;
+        }
+
+        if config.backup_path.starts_with("~") {
+            config.backup_path = home_dir()
+                .unwrap()
+                .join(config.backup_path.strip_prefix("~").unwrap());
+        }
+
+        if let Some(replica_path) = args.replica_path {
+            config.replica_path = replica_path;
+        }
+
+        if config.replica_path.starts_with("~") {
+            config.replica_path = home_dir()
+                .unwrap()
+                .join(config.replica_path.strip_prefix("~").unwrap());
+        }
+
         if let Some(eth_rpc_url) = args.eth_rpc_url {
             config.eth_rpc_url = eth_rpc_url;
         }
@@ -157,6 +216,21 @@ impl Config {
     }
 }
 
+#[cfg(test)]
+impl Config {
+    /// The default config, with the db, smirk, backups and replica files in `dir`
+    pub(crate) fn for_tests(dir: &std::path::Path) -> Self {
+        use clap::Parser;
+
+        let mut config = Config::from_env(CliArgs::try_parse_from(["node"]).unwrap()).unwrap();
+        config.db_path = dir.join("db");
+        config.smirk_path = dir.join("smirk");
+        config.backup_path = dir.join("backups");
+        config.replica_path = dir.join("replica");
+        config
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use clap::Parser;
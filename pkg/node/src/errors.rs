@@ -1,4 +1,4 @@
-use std::num::ParseIntError;
+use std::{num::ParseIntError, path::PathBuf};
 
 use libp2p::PeerId;
 use primitives::{block_height::BlockHeight, hash::CryptoHash};
@@ -53,6 +53,9 @@ pub enum Error {
     #[error("element is not in the tree")]
     ElementNotInTree { element: Element },
 
+    #[error("tree at block height {height} is no longer available")]
+    HeightNotInTreeHistory { height: BlockHeight },
+
     #[error("element is not in any transaction of block {block_height}")]
     ElementNotInTxn {
         element: Element,
@@ -62,6 +65,9 @@ pub enum Error {
     #[error("block height {block} not found")]
     BlockNotFound { block: BlockHeight },
 
+    #[error("block {height} has been pruned")]
+    Pruned { height: BlockHeight },
+
     #[error("block hash {block} not found")]
     BlockHashNotFound { block: CryptoHash },
 
@@ -86,6 +92,15 @@ pub enum Error {
     #[error("invalid block root, got: {got}, expected: {expected}")]
     InvalidBlockRoot { got: Element, expected: Element },
 
+    #[error("backup '{name}' not found")]
+    BackupNotFound { name: String },
+
+    #[error("can't restore a backup into {path:?}, it already contains data")]
+    RestoreTargetNotEmpty { path: PathBuf },
+
+    #[error("this node is a read-only replica")]
+    ReadOnlyReplica,
+
     #[error("failed to find transaction {txn}")]
     TxnNotFound { txn: CryptoHash },
 
@@ -121,6 +136,15 @@ pub enum Error {
     #[error("smirk error: {0}")]
     Smirk(#[from] smirk::storage::Error),
 
+    #[error("prover db error: {0}")]
+    ProverDb(#[from] crate::prover::db::Error),
+
+    #[error("rocksdb error: {0}")]
+    RocksDB(#[from] rocksdb::Error),
+
+    #[error("wire message error: {0}")]
+    WireMessage(#[from] wire_message::Error),
+
     #[error("contracts error: {0}")]
     Contracts(#[from] contracts::Error),
 
@@ -85,6 +85,11 @@ impl SnapshotChunk {
     }
 }
 
+// Every event is understood by all supported protocol versions. Events added in the future should
+// return the protocol version they were introduced in, so they are only sent to peers that have
+// been upgraded.
+impl p2p2::VersionedEvent for NetworkEvent {}
+
 fn fmt_vec<T>(vec: &[T], fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
     write!(fmt, "Vec(len = {})", vec.len())
 }
@@ -5,6 +5,7 @@ use libp2p::PeerId;
 use p2p2::Network;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
+use tracing::Instrument;
 
 pub fn network_handler(
     network: Arc<Network<NetworkEvent>>,
@@ -12,10 +13,19 @@ pub fn network_handler(
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
         loop {
-            let Some((network_peer_id, event)) = network.next().await else { continue };
+            let Some((network_peer_id, event, trace)) = network.next().await else { continue };
             tracing::debug!(network_peer_id = ?network_peer_id, event = ?event, "network event");
 
-            if let Err(e) = handle_event(&node, network_peer_id, event).await {
+            // Continue the sender's trace, so spans for this event show up under it
+            let span = tracing::info_span!("network_event", network_peer_id = ?network_peer_id);
+            if let Some(trace) = trace {
+                trace.set_as_parent(&span);
+            }
+
+            if let Err(e) = handle_event(&node, network_peer_id, event)
+                .instrument(span)
+                .await
+            {
                 tracing::error!(error = ?e, "network error");
             }
         }
@@ -13,12 +13,12 @@ use crate::node::load::LoadedData;
 use crate::types::BlockHeight;
 use crate::utxo::UtxoProof;
 use crate::{sync, util};
-use block_store::{BlockListOrder, BlockStore, StoreList};
+use block_store::{BlockListOrder, BlockStore, StoreList, TxnLocation};
 use contracts::RollupContract;
 use doomslug::{Approval, ApprovalContent, ApprovalStake, ApprovalValidated, Doomslug};
 use futures::Stream;
 use libp2p::PeerId;
-use p2p2::Network;
+use p2p2::{IpFilter, IpNet, Network, NetworkInfo};
 use parking_lot::{Mutex, RwLock};
 use primitives::hash::CryptoHash;
 use primitives::pagination::CursorChoice;
@@ -26,8 +26,8 @@ use primitives::peer::{self, Address, PeerIdSigner};
 use primitives::tick_worker::TickWorker;
 use prover::smirk_metadata::SmirkMetadata;
 use serde::{Deserialize, Serialize};
+use smirk::hash_cache::LruHashCache;
 use std::collections::HashSet;
-use std::net::IpAddr;
 use std::ops::RangeBounds;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -38,20 +38,33 @@ use tokio_stream::StreamExt;
 use tracing::{debug, error, info, instrument};
 use zk_primitives::Element;
 
+pub use self::backup::Backup;
 pub use self::block_format::BlockFormat;
+pub use self::inspect::{MessageType, Store};
+pub use self::migrations::{DryRun, StoreMigration};
 pub use self::txn_format::TxnFormat;
 pub use self::txn_format::TxnMetadata;
 
+mod backup;
 mod block;
 mod block_format;
+pub(crate) mod cache_metrics;
+mod history;
+mod inspect;
+mod integrity;
 mod load;
+mod migrations;
 mod proposal;
+pub(crate) mod prune;
+mod replica;
+mod rollback;
 mod snapshot;
 mod tick_worker;
 mod transaction;
 mod txn_format;
 
-pub type PersistentMerkleTree = smirk::storage::Persistent<MERKLE_TREE_DEPTH, SmirkMetadata>;
+pub type PersistentMerkleTree =
+    smirk::storage::Persistent<MERKLE_TREE_DEPTH, SmirkMetadata, LruHashCache>;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
 // This rename is for the config parser,
@@ -68,6 +81,10 @@ pub enum Mode {
     Prover,
 
     MockProver,
+
+    /// Node serves the RPC API from the data of a primary node on the same machine, without
+    /// taking part in p2p, consensus or the mempool
+    Replica,
 }
 
 impl Mode {
@@ -103,8 +120,8 @@ pub struct NodeShared {
     /// Store for Solid conesnsus blocks
     block_store: Arc<BlockStore<BlockFormat>>,
 
-    /// Network
-    network: Arc<Network<NetworkEvent>>,
+    /// Network, which replicas don't connect to
+    network: Option<Arc<Network<NetworkEvent>>>,
 
     /// Smirk tree containing notes
     notes_tree: Arc<RwLock<PersistentMerkleTree>>,
@@ -119,13 +136,13 @@ pub struct NodeShared {
     // Ticker
     pub(crate) ticker: TickWorker<NodeSharedArc>,
 
-    /// Explicitly whitelisted IP addresses
+    /// Explicitly whitelisted IP addresses or CIDR ranges
     ///
-    /// Any IP address that isn't in this set will be banned (connections will be immediately
-    /// rejected)
+    /// Any IP address that isn't in one of these ranges will be banned (connections will be
+    /// immediately rejected)
     ///
     /// If empty, whitelisting is disabled (i.e. all IPs are allowed)
-    pub whitelisted_ips: HashSet<IpAddr>,
+    pub whitelisted_ips: HashSet<IpNet>,
 }
 
 pub struct NodeSharedArc(Arc<NodeShared>);
@@ -152,13 +169,29 @@ impl Node {
             local_peer.address().to_hex()
         );
 
+        let is_replica = config.mode == Mode::Replica;
+
         let LoadedData {
             block_store,
-            persistent_tree,
+            mut persistent_tree,
             block: initial_block,
-        } = Self::load_db_and_smirk(&config)?;
+        } = if is_replica {
+            // the primary migrates and prunes the stores
+            Self::load_replica(&config)?
+        } else {
+            let data = Self::load_db_and_smirk(&config)?;
+            data.block_store.migrate()?;
+            prune::prune_blocks(&config, &data.block_store)?;
+            data
+        };
+
+        cache_metrics::export("node", persistent_tree.tree().cache().clone());
 
-        block_store.migrate()?;
+        Self::load_tree_history(
+            &block_store,
+            &mut persistent_tree,
+            initial_block.content.header.height,
+        )?;
 
         let block_store = Arc::new(block_store);
         let notes_tree = Arc::new(RwLock::new(persistent_tree));
@@ -179,13 +212,18 @@ impl Node {
             doomslug::DoomslugThresholdMode::TwoThirds,
         )));
 
-        let (keypair, _) = util::generate_p2p_key();
-        let network = Network::new(
-            &keypair,
-            vec![config.p2p.laddr.clone()].into_iter(),
-            config.p2p.dial.clone().into_iter(),
-            config.p2p.whitelisted_ips.clone(),
-        )?;
+        let network = if is_replica {
+            None
+        } else {
+            let (keypair, _) = util::generate_p2p_key();
+            Some(Arc::new(Network::new(
+                &keypair,
+                vec![config.p2p.laddr.clone()].into_iter(),
+                config.p2p.dial.clone().into_iter(),
+                config.p2p.ip_filter(),
+                config.p2p.protocol_versions.clone(),
+            )?))
+        };
 
         let (sync_worker_sender, sync_worker_receiver) = mpsc::unbounded_channel();
 
@@ -197,7 +235,7 @@ impl Node {
             block_cache,
             doomslug,
             notes_tree,
-            network: Arc::new(network),
+            network,
             config: config.clone(),
             ticker: TickWorker::new(),
             state: Mutex::new(NodeSharedState {
@@ -226,13 +264,18 @@ impl Node {
     }
 
     pub async fn run(self) {
-        let _network_event_handler =
-            network_handler(self.shared.network.clone(), self.shared.clone());
+        let Some(network) = self.shared.network.clone() else {
+            // replicas only follow the primary's stores
+            self.shared.follow_primary().await;
+            return;
+        };
+
+        let _network_event_handler = network_handler(network.clone(), self.shared.clone());
 
         // Dial peers
         for peer in self.shared.config.p2p.dial.iter() {
             debug!("Dialing peer {peer}...");
-            match self.shared.network.dial(peer.clone()).await {
+            match network.dial(peer.clone()).await {
                 Ok(_) => {
                     info!("Connected to peer {}", peer)
                 }
@@ -278,6 +321,10 @@ impl NodeShared {
         self.notes_tree.read().tree().root_hash()
     }
 
+    pub(crate) fn admin_token(&self) -> Option<&str> {
+        self.config.admin_token.as_deref()
+    }
+
     pub(crate) fn notes_tree(&self) -> &Arc<RwLock<PersistentMerkleTree>> {
         &self.notes_tree
     }
@@ -297,29 +344,82 @@ impl NodeShared {
     }
 
     pub(crate) fn get_merkle_paths(&self, elements: &[Element]) -> Result<Vec<Vec<Element>>> {
+        std::iter::zip(elements, self.find_merkle_paths(elements))
+            .map(|(e, path)| path.ok_or(Error::ElementNotInTree { element: *e }))
+            .collect::<Result<Vec<Vec<Element>>>>()
+    }
+
+    /// Merkle paths for `elements`, in the same order, with `None` for elements that aren't in
+    /// the tree
+    ///
+    /// The paths are generated in a single batch, so the read lock on the tree is only held once
+    pub(crate) fn find_merkle_paths(&self, elements: &[Element]) -> Vec<Option<Vec<Element>>> {
         let notes_tree = self.notes_tree.read();
         let tree = notes_tree.tree();
 
-        elements
-            .iter()
-            .map(|e| {
-                // Check the element is in the tree
-                if !tree.contains_element(e) {
+        std::iter::zip(elements, tree.paths_for(elements))
+            .map(|(e, path)| {
+                tree.contains_element(e)
+                    .then(|| path.siblings_deepest_first().to_vec())
+            })
+            .collect()
+    }
+
+    /// Merkle paths against the tree as it was after the block at `height` was committed
+    ///
+    /// Only the last [`RECENT_ROOT_COUNT`][crate::constants::RECENT_ROOT_COUNT] heights are
+    /// available
+    pub(crate) fn get_merkle_paths_at(
+        &self,
+        elements: &[Element],
+        height: BlockHeight,
+    ) -> Result<(Element, Vec<Vec<Element>>)> {
+        let notes_tree = self.notes_tree.read();
+        let snapshot = notes_tree
+            .snapshot_at(height.0)
+            .ok_or(Error::HeightNotInTreeHistory { height })?;
+
+        let paths = std::iter::zip(elements, snapshot.paths_for(elements))
+            .map(|(e, path)| {
+                if !snapshot.contains_element(*e) {
                     return Err(Error::ElementNotInTree { element: *e });
                 }
 
-                // Return the path
-                Ok(tree.path_for(*e).siblings_deepest_first().to_vec())
+                Ok(path.siblings_deepest_first().to_vec())
             })
-            .collect::<Result<Vec<Vec<Element>>>>()
+            .collect::<Result<Vec<Vec<Element>>>>()?;
+
+        Ok((snapshot.root_hash(), paths))
     }
 
     pub(crate) async fn send_all(&self, event: NetworkEvent) {
-        self.network.send_all(event).await
+        if let Some(network) = &self.network {
+            network.send_all(event).await
+        }
     }
 
     pub(crate) async fn send(&self, peer: PeerId, request: NetworkEvent) {
-        self.network.send(&peer, request).await
+        if let Some(network) = &self.network {
+            network.send(&peer, request).await
+        }
+    }
+
+    /// Connected peers, recent dial failures and bans
+    pub(crate) async fn network_info(&self) -> Result<NetworkInfo> {
+        let Some(network) = &self.network else {
+            return Err(Error::ReadOnlyReplica);
+        };
+
+        Ok(network.info().await?)
+    }
+
+    /// Replace the IP whitelist and banlist, closing connections that are no longer allowed
+    pub(crate) async fn set_ip_filter(&self, ip_filter: IpFilter) -> Result<()> {
+        let Some(network) = &self.network else {
+            return Err(Error::ReadOnlyReplica);
+        };
+
+        Ok(network.set_ip_filter(ip_filter).await?)
     }
 
     /// My peer address
@@ -518,6 +618,15 @@ impl NodeShared {
         Ok(txn.map(|TxnFormat::V1(txn, metadata)| (txn, metadata)))
     }
 
+    pub(crate) fn get_txn_location_by_element(
+        &self,
+        element: Element,
+    ) -> Result<Option<TxnLocation>> {
+        Ok(self
+            .block_store
+            .get_txn_location_by_element(element.to_be_bytes())?)
+    }
+
     pub(crate) fn last_commit_time(&self) -> Option<Instant> {
         self.state.lock().last_commit
     }
@@ -0,0 +1,316 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use block_store::BlockStore;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{config::Config, types::BlockHeight, BlockFormat, Error, Node, NodeShared, Result};
+
+/// The file in each backup directory that describes the backup
+const METADATA_FILE: &str = "backup.json";
+
+/// Backups are written to a directory with this suffix, and renamed once they are complete
+const INCOMPLETE_SUFFIX: &str = ".incomplete";
+
+/// A backup of the block store and the notes tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    /// The name of the backup's directory in the backup path
+    pub name: String,
+    /// The height of the latest block in the backup, if there are any blocks
+    pub height: Option<BlockHeight>,
+    /// When the backup was created, in seconds since the unix epoch
+    pub created_at: u64,
+}
+
+impl NodeShared {
+    /// Create a backup of the block store and the notes tree while the node is running, then
+    /// remove the oldest backups past the configured retention
+    pub(crate) fn create_backup(&self) -> Result<Backup> {
+        let backup_path = &self.config.backup_path;
+        fs::create_dir_all(backup_path)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let created_at = now.as_secs();
+        let incomplete_path = backup_path.join(format!("{}{INCOMPLETE_SUFFIX}", now.as_nanos()));
+        fs::create_dir(&incomplete_path)?;
+
+        {
+            // Blocks are only applied to the notes tree while holding its write lock, so holding
+            // the read lock keeps the tree checkpoint at or behind the block store checkpoint.
+            // If the block store is ahead, the newer block is a pending commit, which is applied
+            // to the tree when the backup is restored and loaded
+            let notes_tree = self.notes_tree.read();
+            notes_tree.create_checkpoint(incomplete_path.join("smirk"))?;
+            self.block_store
+                .create_checkpoint(&incomplete_path.join("db"))?;
+        }
+
+        let height = BlockStore::<BlockFormat>::open_read_only(&incomplete_path.join("db"))?
+            .get_max_height()?;
+
+        let name = format!("{created_at}-{}", height.map_or(0, |height| height.0));
+        let backup = Backup {
+            name: name.clone(),
+            height,
+            created_at,
+        };
+
+        fs::write(
+            incomplete_path.join(METADATA_FILE),
+            serde_json::to_vec_pretty(&backup).unwrap(),
+        )?;
+        fs::rename(&incomplete_path, backup_path.join(&name))?;
+
+        info!(?backup, "Created backup");
+
+        apply_backup_retention(backup_path, self.config.backup_retention)?;
+
+        Ok(backup)
+    }
+
+    pub(crate) fn list_backups(&self) -> Result<Vec<Backup>> {
+        list_backups(&self.config.backup_path)
+    }
+}
+
+impl Node {
+    /// List the backups in the configured backup path, oldest first
+    pub fn list_backups(config: &Config) -> Result<Vec<Backup>> {
+        list_backups(&config.backup_path)
+    }
+
+    /// Restore a backup into the configured db and smirk paths
+    ///
+    /// This must be run while the node is stopped, and the node's data must not exist yet (e.g.
+    /// use a fresh `--db-path` and `--smirk-path`). The backup itself is left unchanged
+    pub fn restore_backup(config: &Config, name: &str) -> Result<Backup> {
+        let Some(backup) = Self::list_backups(config)?
+            .into_iter()
+            .find(|backup| backup.name == name)
+        else {
+            return Err(Error::BackupNotFound {
+                name: name.to_owned(),
+            });
+        };
+
+        let backup_path = config.backup_path.join(&backup.name);
+        let db_path = config.db_path.join("latest");
+        let smirk_path = config.smirk_path.join("latest");
+
+        for path in [&db_path, &smirk_path] {
+            if path.exists() && fs::read_dir(path)?.next().is_some() {
+                return Err(Error::RestoreTargetNotEmpty {
+                    path: path.to_owned(),
+                });
+            }
+        }
+
+        copy_dir(&backup_path.join("db"), &db_path)?;
+        copy_dir(&backup_path.join("smirk"), &smirk_path)?;
+
+        info!(?backup, ?db_path, ?smirk_path, "Restored backup");
+
+        Ok(backup)
+    }
+}
+
+fn list_backups(backup_path: &Path) -> Result<Vec<Backup>> {
+    if !backup_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+
+    for entry in fs::read_dir(backup_path)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(INCOMPLETE_SUFFIX) {
+            continue;
+        }
+
+        let Ok(metadata) = fs::read(path.join(METADATA_FILE)) else {
+            continue;
+        };
+
+        match serde_json::from_slice::<Backup>(&metadata) {
+            Ok(backup) => backups.push(backup),
+            Err(err) => warn!(?err, ?path, "Ignoring backup with invalid metadata"),
+        }
+    }
+
+    backups.sort_by(|a, b| (a.created_at, &a.name).cmp(&(b.created_at, &b.name)));
+
+    Ok(backups)
+}
+
+/// Remove the oldest backups in `backup_path`, so that only `retention` are left (0 keeps all
+/// backups)
+fn apply_backup_retention(backup_path: &Path, retention: usize) -> Result<()> {
+    if retention == 0 {
+        return Ok(());
+    }
+
+    let backups = list_backups(backup_path)?;
+    let expired = backups.len().saturating_sub(retention);
+
+    for backup in &backups[..expired] {
+        fs::remove_dir_all(backup_path.join(&backup.name))?;
+        info!(?backup, "Removed expired backup");
+    }
+
+    Ok(())
+}
+
+/// Copy a checkpoint, whose files may be hard links shared with the original database
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let to = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else {
+            fs::copy(entry.path(), to)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use prover::smirk_metadata::SmirkMetadata;
+    use smirk::Element;
+
+    use super::*;
+    use crate::Block;
+
+    fn write_metadata(backup_path: &Path, backup: &Backup) {
+        let path = backup_path.join(&backup.name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(
+            path.join(METADATA_FILE),
+            serde_json::to_vec(backup).unwrap(),
+        )
+        .unwrap();
+    }
+
+    fn backup(created_at: u64) -> Backup {
+        Backup {
+            name: format!("{created_at}-0"),
+            height: None,
+            created_at,
+        }
+    }
+
+    fn names(backups: &[Backup]) -> Vec<&str> {
+        backups.iter().map(|backup| backup.name.as_str()).collect()
+    }
+
+    #[test]
+    fn list_backups_skips_incomplete_and_invalid_backups() {
+        let temp_dir = tempdir::TempDir::new("backups").unwrap();
+        let backup_path = temp_dir.path();
+
+        assert!(list_backups(&backup_path.join("missing"))
+            .unwrap()
+            .is_empty());
+
+        write_metadata(backup_path, &backup(3));
+        write_metadata(backup_path, &backup(1));
+        write_metadata(backup_path, &backup(2));
+
+        fs::create_dir(backup_path.join(format!("4{INCOMPLETE_SUFFIX}"))).unwrap();
+        fs::create_dir(backup_path.join("no-metadata")).unwrap();
+        fs::create_dir(backup_path.join("invalid-metadata")).unwrap();
+        fs::write(
+            backup_path.join("invalid-metadata").join(METADATA_FILE),
+            "{",
+        )
+        .unwrap();
+
+        assert_eq!(
+            names(&list_backups(backup_path).unwrap()),
+            ["1-0", "2-0", "3-0"]
+        );
+    }
+
+    #[test]
+    fn retention_removes_the_oldest_backups() {
+        let temp_dir = tempdir::TempDir::new("backups").unwrap();
+        let backup_path = temp_dir.path();
+
+        for created_at in 1..=4 {
+            write_metadata(backup_path, &backup(created_at));
+        }
+
+        apply_backup_retention(backup_path, 0).unwrap();
+        assert_eq!(list_backups(backup_path).unwrap().len(), 4);
+
+        apply_backup_retention(backup_path, 2).unwrap();
+        assert_eq!(names(&list_backups(backup_path).unwrap()), ["3-0", "4-0"]);
+        assert!(!backup_path.join("1-0").exists());
+
+        apply_backup_retention(backup_path, 5).unwrap();
+        assert_eq!(list_backups(backup_path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn restore_copies_the_backup_into_empty_paths() {
+        let temp_dir = tempdir::TempDir::new("backups").unwrap();
+        let config = Config::for_tests(temp_dir.path());
+
+        // make a backup from checkpoints, like `create_backup` does
+        let source = temp_dir.path().join("source");
+        let block_store = BlockStore::<BlockFormat>::create_or_load(&source.join("db")).unwrap();
+        block_store.set(&BlockFormat::V1(Block::default())).unwrap();
+        let mut notes_tree = Node::load_notes_tree(&config, &source.join("smirk")).unwrap();
+        notes_tree
+            .insert(Element::new(1), SmirkMetadata::inserted_in(0))
+            .unwrap();
+
+        let backup = Backup {
+            name: "1-0".to_owned(),
+            height: Some(BlockHeight(0)),
+            created_at: 1,
+        };
+        let backup_dir = config.backup_path.join(&backup.name);
+        fs::create_dir_all(&backup_dir).unwrap();
+        block_store
+            .create_checkpoint(&backup_dir.join("db"))
+            .unwrap();
+        notes_tree
+            .create_checkpoint(backup_dir.join("smirk"))
+            .unwrap();
+        write_metadata(&config.backup_path, &backup);
+        drop(block_store);
+        drop(notes_tree);
+
+        assert!(matches!(
+            Node::restore_backup(&config, "missing"),
+            Err(Error::BackupNotFound { .. })
+        ));
+
+        assert_eq!(Node::restore_backup(&config, "1-0").unwrap().name, "1-0");
+
+        let block_store =
+            BlockStore::<BlockFormat>::create_or_load(&config.db_path.join("latest")).unwrap();
+        assert_eq!(block_store.get_max_height().unwrap(), Some(BlockHeight(0)));
+        let notes_tree = Node::load_notes_tree(&config, &config.smirk_path.join("latest")).unwrap();
+        assert!(notes_tree.tree().contains_element(&Element::new(1)));
+        drop(block_store);
+        drop(notes_tree);
+
+        // the node's data is never overwritten
+        assert!(matches!(
+            Node::restore_backup(&config, "1-0"),
+            Err(Error::RestoreTargetNotEmpty { .. })
+        ));
+    }
+}
@@ -70,6 +70,7 @@ impl NodeShared {
         let batch = Batch::from_entries(leaves_with_height_maybe_ignoring_collisions)?;
 
         notes_tree.insert_batch(batch)?;
+        notes_tree.commit_version(current_height.0);
         Ok(())
     }
 }
@@ -1,18 +1,19 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use primitives::block_height::BlockHeight;
+use serde::{Deserialize, Serialize};
 use wire_message::WireMessage;
 
 use crate::{block::Block, TxnFormat};
 
 use super::txn_format::TxnMetadata;
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, BorshSchema, Serialize, Deserialize)]
 pub struct BlockMetadata {
     pub timestamp_unix_s: Option<u64>,
 }
 
-#[derive(Debug, Clone)]
-#[wire_message::wire_message]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[wire_message::wire_message(schema)]
 pub enum BlockFormat {
     V1(Block),
     V2(Block, BlockMetadata),
@@ -59,6 +60,17 @@ impl WireMessage for BlockFormat {
             Self::V2(_, _) => Err(Self::max_version_error()),
         }
     }
+
+    fn downgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, wire_message::Error> {
+        match self {
+            Self::V1(_) => Err(Self::min_version_error()),
+            // V1 blocks have no timestamp
+            Self::V2(block, BlockMetadata { timestamp_unix_s }) => match timestamp_unix_s {
+                None => Ok(Self::V1(block)),
+                Some(_) => Err(Self::unrepresentable_error(2, 1)),
+            },
+        }
+    }
 }
 
 impl block_store::Block for BlockFormat {
@@ -90,6 +102,13 @@ impl block_store::Block for BlockFormat {
             }
         }
     }
+
+    fn prune(&mut self) {
+        match self {
+            Self::V1(block) => block.prune(),
+            Self::V2(block, _) => block.prune(),
+        }
+    }
 }
 
 impl block_store::Block for Block {
@@ -122,4 +141,49 @@ impl block_store::Block for Block {
             })
             .collect()
     }
+
+    /// Proofs are only needed to validate new blocks, so they are dropped. The hashes of the block
+    /// and its transactions don't include the proofs
+    fn prune(&mut self) {
+        for txn in &mut self.content.state.txns {
+            txn.proof = Vec::new();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_snapshot() {
+        wire_message::test_api::assert_schema_snapshot::<BlockFormat>(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/node/snapshots/block_format.schema"
+        ));
+    }
+
+    #[test]
+    fn downgrade_to_v1() {
+        let block = BlockFormat::V2(
+            Block::genesis(),
+            BlockMetadata {
+                timestamp_unix_s: None,
+            },
+        );
+        let bytes = block.to_bytes_at_version(1, &mut ()).unwrap();
+        let block = BlockFormat::from_bytes(&bytes).unwrap();
+        assert_eq!(block.version(), 1);
+        assert_eq!(block.into_block(), Block::genesis());
+
+        // the timestamp can't be represented in V1, so it isn't silently dropped
+        let block = BlockFormat::V2(
+            Block::genesis(),
+            BlockMetadata {
+                timestamp_unix_s: Some(1),
+            },
+        );
+        let err = block.to_bytes_at_version(1, &mut ()).unwrap_err();
+        assert!(err.is_downgrade());
+    }
 }
@@ -0,0 +1,56 @@
+use opentelemetry::{metrics::Unit, KeyValue};
+use smirk::hash_cache::LruHashCache;
+use tracing::warn;
+
+/// Export the metrics of a smirk hash cache through the OpenTelemetry meter
+///
+/// `tree` is added as an attribute, to tell apart the caches of different trees (e.g. the node's
+/// tree and the prover's tree). If no meter provider is configured, this does nothing
+pub(crate) fn export(tree: &'static str, cache: LruHashCache) {
+    let meter = rpc::tracing::meter("smirk");
+
+    let hashes = meter
+        .u64_observable_counter("smirk.hash_cache.hashes")
+        .with_description("Number of hashes requested from the cache")
+        .init();
+    let hits = meter
+        .u64_observable_counter("smirk.hash_cache.hits")
+        .with_description("Number of hashes found in the cache")
+        .init();
+    let misses = meter
+        .u64_observable_counter("smirk.hash_cache.misses")
+        .with_description("Number of hashes that had to be computed")
+        .init();
+    let evictions = meter
+        .u64_observable_counter("smirk.hash_cache.evictions")
+        .with_description("Number of hashes evicted to stay within the memory budget")
+        .init();
+    let memory_usage = meter
+        .u64_observable_gauge("smirk.hash_cache.memory_usage")
+        .with_description("Approximate memory used by the cached hashes")
+        .with_unit(Unit::new("By"))
+        .init();
+
+    let instruments = [
+        hashes.as_any(),
+        hits.as_any(),
+        misses.as_any(),
+        evictions.as_any(),
+        memory_usage.as_any(),
+    ];
+
+    let result = meter.register_callback(&instruments, move |observer| {
+        let attributes = [KeyValue::new("tree", tree)];
+        let metrics = cache.metrics();
+
+        observer.observe_u64(&hashes, metrics.hashes() as u64, &attributes);
+        observer.observe_u64(&hits, metrics.cache_hits() as u64, &attributes);
+        observer.observe_u64(&misses, metrics.cache_misses() as u64, &attributes);
+        observer.observe_u64(&evictions, metrics.evictions() as u64, &attributes);
+        observer.observe_u64(&memory_usage, cache.memory_usage() as u64, &attributes);
+    });
+
+    if let Err(err) = result {
+        warn!(?err, tree, "Failed to register smirk hash cache metrics");
+    }
+}
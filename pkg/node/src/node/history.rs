@@ -0,0 +1,170 @@
+use block_store::{BlockListOrder, BlockStore, StoreList};
+use tracing::{info, warn};
+use zk_primitives::Element;
+
+use crate::{
+    constants::RECENT_ROOT_COUNT, types::BlockHeight, BlockFormat, Node, PersistentMerkleTree,
+    Result,
+};
+
+impl Node {
+    /// Keep the notes tree at the last [`RECENT_ROOT_COUNT`] heights, so paths can be generated
+    /// against any recent root, rebuilding the heights before `height` from the block store
+    pub(super) fn load_tree_history(
+        block_store: &BlockStore<BlockFormat>,
+        notes_tree: &mut PersistentMerkleTree,
+        height: BlockHeight,
+    ) -> Result<()> {
+        notes_tree.set_history_len(RECENT_ROOT_COUNT as usize);
+        notes_tree.commit_version(height.0);
+
+        let from = BlockHeight(height.0.saturating_sub(RECENT_ROOT_COUNT - 1));
+        rebuild_tree_history(block_store, notes_tree, from, height)?;
+
+        info!(
+            ?height,
+            oldest = ?notes_tree.versions().next(),
+            "Loaded notes tree history"
+        );
+
+        Ok(())
+    }
+}
+
+/// Add the heights in `from..=to` to the history of the notes tree, which must be at height `to`
+///
+/// The history is only kept in memory, so heights the tree wasn't committed at (before it was
+/// loaded, or skipped by a replica catching up) are rebuilt from the block store, by removing the
+/// leaves each block inserted, newest first. Rebuilding stops at the first block whose root hash
+/// doesn't match, or that isn't in the block store (e.g. it was pruned)
+pub(super) fn rebuild_tree_history(
+    block_store: &BlockStore<BlockFormat>,
+    notes_tree: &mut PersistentMerkleTree,
+    from: BlockHeight,
+    to: BlockHeight,
+) -> Result<()> {
+    let mut snapshot = notes_tree.tree().snapshot();
+    let mut expected_height = to;
+
+    for res in block_store
+        .list(from..=to, BlockListOrder::HighestToLowest)
+        .into_iterator()
+    {
+        let (_, block) = res?;
+        let block = block.into_block();
+        let height = block.content.header.height;
+
+        if height != expected_height {
+            warn!(
+                ?height,
+                ?expected_height,
+                "Missing block, stopping tree history rebuild"
+            );
+            break;
+        }
+
+        if snapshot.root_hash() != block.content.state.root_hash {
+            warn!(
+                ?height,
+                root_hash = ?snapshot.root_hash(),
+                block_root_hash = ?block.content.state.root_hash,
+                "Rebuilt tree doesn't match the block, stopping tree history rebuild"
+            );
+            break;
+        }
+
+        notes_tree.insert_version(height.0, snapshot.clone());
+
+        if height == from {
+            break;
+        }
+
+        // leaves that collided with earlier blocks were inserted at those heights, so they stay
+        let inserted = block
+            .content
+            .state
+            .leaves()
+            .filter(|leaf| {
+                notes_tree
+                    .tree()
+                    .get(*leaf)
+                    .map_or(false, |metadata| metadata.inserted_in == height.0)
+            })
+            .collect::<Vec<Element>>();
+        snapshot = snapshot.without(&inserted, notes_tree.tree().cache());
+        expected_height = BlockHeight(height.0 - 1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, utxo::UtxoProof, Block, NodeShared};
+
+    #[test]
+    fn merkle_paths_at_past_heights_after_restart() {
+        let temp_dir = tempdir::TempDir::new("history").unwrap();
+        let config = Config::for_tests(temp_dir.path());
+        let db_path = config.db_path.join("latest");
+        let smirk_path = config.smirk_path.join("latest");
+
+        let block_store = BlockStore::<BlockFormat>::create_or_load(&db_path).unwrap();
+        let mut notes_tree = Node::load_notes_tree(&config, &smirk_path).unwrap();
+
+        let mut root_hashes = Vec::new();
+        for height in 1..=3 {
+            let mut block = Block::default();
+            block.content.header.height = BlockHeight(height);
+            block.content.state.txns = vec![UtxoProof {
+                output_leaves: [Element::new(height), Element::NULL_HASH],
+                ..UtxoProof::default()
+            }];
+
+            NodeShared::apply_block_to_tree(
+                &mut notes_tree,
+                &block.content.state,
+                BlockHeight(height),
+                false,
+            )
+            .unwrap();
+            block.content.state.root_hash = notes_tree.tree().root_hash();
+            root_hashes.push(block.content.state.root_hash);
+
+            block_store.set(&BlockFormat::V1(block)).unwrap();
+        }
+
+        drop(block_store);
+        drop(notes_tree);
+
+        // restart, which loads the tree history the same way `Node::new` does
+        let mut data = Node::load_db_and_smirk(&config).unwrap();
+        assert_eq!(data.persistent_tree.versions().count(), 0);
+        Node::load_tree_history(
+            &data.block_store,
+            &mut data.persistent_tree,
+            data.block.content.header.height,
+        )
+        .unwrap();
+
+        assert_eq!(
+            data.persistent_tree.versions().collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        for (height, root_hash) in (1..=3).zip(&root_hashes) {
+            assert_eq!(data.persistent_tree.root_hash_at(height), Some(*root_hash));
+
+            let path = data
+                .persistent_tree
+                .path_for_at(height, Element::new(1))
+                .unwrap();
+            assert_eq!(path.actual_root_hash(), *root_hash);
+            assert!(path.proves(Element::new(1)));
+
+            let snapshot = data.persistent_tree.snapshot_at(height).unwrap();
+            assert!(snapshot.contains_element(Element::new(height)));
+            assert!(!snapshot.contains_element(Element::new(height + 1)));
+        }
+    }
+}
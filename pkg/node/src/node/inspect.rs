@@ -0,0 +1,297 @@
+use std::path::PathBuf;
+
+use block_store::{TxnLocation, ValueKind};
+use primitives::migration::Checkpoint;
+use prover::smirk_metadata::SmirkMetadata;
+use serde_json::{json, Value};
+use smirk::storage::{KeyFormat, ValueFormat};
+use wire_message::json::Codec;
+
+use crate::{config::Config, prover::db, BlockFormat, Node, Result, TxnFormat};
+
+/// A [`WireMessage`][wire_message::WireMessage] type that can be decoded to JSON and encoded back
+/// with [`Node::decode_message`] and [`Node::encode_message`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageType {
+    /// A block in the block store
+    BlockFormat,
+    /// A transaction in the block store
+    TxnFormat,
+    /// A key in a smirk database
+    SmirkKey,
+    /// A value in a smirk database
+    SmirkValue,
+    /// A value in the prover db
+    ProverValue,
+}
+
+impl MessageType {
+    pub fn codec(self) -> Codec {
+        match self {
+            Self::BlockFormat => Codec::new::<BlockFormat>("BlockFormat"),
+            Self::TxnFormat => Codec::new::<TxnFormat>("TxnFormat"),
+            Self::SmirkKey => Codec::new::<KeyFormat>("smirk::KeyFormat"),
+            Self::SmirkValue => Codec::new::<ValueFormat<SmirkMetadata>>("smirk::ValueFormat"),
+            Self::ProverValue => Codec::new::<db::Value>("prover::db::Value"),
+        }
+    }
+}
+
+/// One of the node's rocksdb databases, as dumped by [`Node::dump_store`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Store {
+    /// The block store, in `<db_path>/latest`
+    Blocks,
+    /// The prover db, in `<db_path>/prover`
+    Prover,
+    /// The smirk tree, in `<smirk_path>/latest`
+    Smirk,
+    /// The prover's smirk tree, in `<smirk_path>/prover`
+    ProverSmirk,
+}
+
+impl Store {
+    fn path(self, config: &Config) -> PathBuf {
+        match self {
+            Self::Blocks => config.db_path.join("latest"),
+            Self::Prover => config.db_path.join("prover"),
+            Self::Smirk => config.smirk_path.join("latest"),
+            Self::ProverSmirk => config.smirk_path.join("prover"),
+        }
+    }
+
+    /// Decode a raw key and value of this store to JSON
+    fn decode_entry(self, key: &[u8], value: &[u8]) -> Result<(Value, Value)> {
+        match self {
+            Self::Blocks => {
+                let key = block_store::Key::deserialize(key)?;
+
+                let value = match key.value_kind() {
+                    ValueKind::Block => MessageType::BlockFormat.codec().decode(value)?,
+                    ValueKind::Txn => MessageType::TxnFormat.codec().decode(value)?,
+                    ValueKind::BlockHeight => json!(u64::from_be_bytes(
+                        value
+                            .try_into()
+                            .map_err(|_| block_store::Error::InvalidValue)?
+                    )),
+                    ValueKind::StoreVersion => json!(u32::from_be_bytes(
+                        value
+                            .try_into()
+                            .map_err(|_| block_store::Error::InvalidValue)?
+                    )),
+                    ValueKind::TxnLocation => {
+                        let location = TxnLocation::from_bytes(value)?;
+                        json!({
+                            "block_height": location.block_height,
+                            "txn_index": location.txn_index,
+                            "txn_hash": hex::encode(location.txn_hash),
+                        })
+                    }
+                    ValueKind::MigrationCheckpoint => {
+                        let checkpoint =
+                            Checkpoint::from_bytes(value).map_err(block_store::Error::from)?;
+                        json!({
+                            "version": checkpoint.version,
+                            "cursor": hex::encode(checkpoint.cursor),
+                            "items": checkpoint.items,
+                        })
+                    }
+                };
+
+                Ok((json!(key.to_string()), value))
+            }
+            Self::Prover => {
+                let key = db::Key::deserialize(key)?;
+                let value = MessageType::ProverValue.codec().decode(value)?;
+
+                Ok((json!(format!("{key:?}")), value))
+            }
+            Self::Smirk | Self::ProverSmirk => Ok((
+                MessageType::SmirkKey.codec().decode(key)?,
+                MessageType::SmirkValue.codec().decode(value)?,
+            )),
+        }
+    }
+}
+
+impl Node {
+    /// Decode the bytes of a message of type `message_type` to versioned JSON
+    pub fn decode_message(message_type: MessageType, bytes: &[u8]) -> Result<Value> {
+        Ok(message_type.codec().decode(bytes)?)
+    }
+
+    /// Encode versioned JSON, as returned by [`Node::decode_message`], to the bytes of a message
+    pub fn encode_message(message_type: MessageType, json: Value) -> Result<Vec<u8>> {
+        Ok(message_type.codec().encode(json)?)
+    }
+
+    /// Decode every key and value in one of the node's stores, in key order, and call `f` with
+    /// each of them as JSON
+    ///
+    /// Each entry has the raw key in `key_hex`, and the decoded `key` and `value`. Entries that
+    /// can't be decoded have the raw value in `value_hex` and an `error` instead, so a corrupt
+    /// entry doesn't stop the dump. The store is opened read-only, so this can be run while the
+    /// node is running
+    pub fn dump_store(config: &Config, store: Store, mut f: impl FnMut(Value)) -> Result<()> {
+        let db = rocksdb::DB::open_for_read_only(
+            &rocksdb::Options::default(),
+            store.path(config),
+            false,
+        )?;
+
+        for entry in db.iterator(rocksdb::IteratorMode::Start) {
+            let (key, value) = entry?;
+
+            f(match store.decode_entry(&key, &value) {
+                Ok((decoded_key, decoded_value)) => json!({
+                    "key_hex": hex::encode(&key),
+                    "key": decoded_key,
+                    "value": decoded_value,
+                }),
+                Err(err) => json!({
+                    "key_hex": hex::encode(&key),
+                    "value_hex": hex::encode(&value),
+                    "error": err.to_string(),
+                }),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use block_store::BlockStore;
+    use smirk::Element;
+
+    use super::*;
+    use crate::{types::BlockHeight, Block};
+
+    #[test]
+    fn block_format_roundtrips_through_json() {
+        let block = BlockFormat::V1(Block::default());
+        let bytes = wire_message::WireMessage::to_bytes(&block).unwrap();
+
+        let json = Node::decode_message(MessageType::BlockFormat, &bytes).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(
+            Node::encode_message(MessageType::BlockFormat, json).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn block_store_entries_are_decoded() {
+        let temp_dir = tempdir::TempDir::new("dump_store").unwrap();
+        let db_path = temp_dir.path().join("latest");
+
+        let block_store = BlockStore::<BlockFormat>::create_or_load(&db_path).unwrap();
+        block_store.set(&BlockFormat::V1(Block::default())).unwrap();
+        drop(block_store);
+
+        let db =
+            rocksdb::DB::open_for_read_only(&rocksdb::Options::default(), &db_path, false).unwrap();
+        let entries = db
+            .iterator(rocksdb::IteratorMode::Start)
+            .map(|entry| {
+                let (key, value) = entry.unwrap();
+                Store::Blocks.decode_entry(&key, &value).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        assert!(entries
+            .iter()
+            .any(|(key, value)| key == "block/0" && value["version"] == 1));
+        assert!(entries
+            .iter()
+            .any(|(key, value)| key == "max_height" && value == 0));
+    }
+
+    fn dump(config: &Config, store: Store) -> Vec<Value> {
+        let mut entries = Vec::new();
+        Node::dump_store(config, store, |entry| entries.push(entry)).unwrap();
+        entries
+    }
+
+    #[test]
+    fn smirk_entries_are_decoded() {
+        let temp_dir = tempdir::TempDir::new("dump_store").unwrap();
+        let config = Config::for_tests(temp_dir.path());
+
+        let mut notes_tree =
+            Node::load_notes_tree(&config, &config.smirk_path.join("latest")).unwrap();
+        notes_tree
+            .insert(Element::new(1), SmirkMetadata::inserted_in(5))
+            .unwrap();
+        drop(notes_tree);
+
+        let entries = dump(&config, Store::Smirk);
+
+        assert!(entries.iter().all(|entry| entry.get("error").is_none()));
+        assert!(entries.iter().any(|entry| {
+            entry["key"]["message"]["V2"]["Element"] == json!(Element::new(1))
+                && entry["value"]["message"]["V2"]["Metadata"]["inserted_in"] == 5
+        }));
+    }
+
+    #[test]
+    fn prover_entries_are_decoded() {
+        let temp_dir = tempdir::TempDir::new("dump_store").unwrap();
+        let config = Config::for_tests(temp_dir.path());
+
+        let prover_db = db::ProverDb::create_or_load(&config.db_path.join("prover")).unwrap();
+        prover_db
+            .set_last_seen_block(db::LastSeenBlock {
+                height: BlockHeight(5),
+                root_hash: Element::new(1),
+            })
+            .unwrap();
+        drop(prover_db);
+
+        let entries = dump(&config, Store::Prover);
+
+        assert!(entries.iter().all(|entry| entry.get("error").is_none()));
+        assert!(entries.iter().any(|entry| {
+            entry["key"] == "LastSeenBlock"
+                && entry["value"]["message"]["V1"]["LastSeenBlock"]["height"] == 5
+        }));
+    }
+
+    #[test]
+    fn corrupt_entries_dont_stop_the_dump() {
+        let temp_dir = tempdir::TempDir::new("dump_store").unwrap();
+        let config = Config::for_tests(temp_dir.path());
+        let db_path = config.db_path.join("latest");
+
+        let block_store = BlockStore::<BlockFormat>::create_or_load(&db_path).unwrap();
+        block_store.set(&BlockFormat::V1(Block::default())).unwrap();
+        drop(block_store);
+
+        // block 0 can't be decoded anymore, and there is a key of an unknown kind
+        let db = rocksdb::DB::open_default(&db_path).unwrap();
+        db.put([0; 9], b"corrupt").unwrap();
+        db.put([255], b"unknown").unwrap();
+        drop(db);
+
+        let entries = dump(&config, Store::Blocks);
+
+        let corrupt = entries
+            .iter()
+            .filter(|entry| entry.get("error").is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(corrupt.len(), 2);
+        assert!(corrupt
+            .iter()
+            .any(|entry| entry["value_hex"] == hex::encode(b"corrupt")));
+
+        let unknown = corrupt
+            .iter()
+            .find(|entry| entry["key_hex"] == "ff")
+            .unwrap();
+        assert_eq!(unknown["value_hex"], hex::encode(b"unknown"));
+
+        // the other entries are still decoded
+        assert!(entries.iter().any(|entry| entry["key"] == "max_height"));
+    }
+}
@@ -0,0 +1,83 @@
+use block_store::BlockStore;
+use prover::smirk_metadata::SmirkMetadata;
+use tracing::{info, warn};
+
+use crate::{config::Config, constants::MERKLE_TREE_DEPTH, BlockFormat, Error, Node, Result};
+
+use super::load::{empty_tree_hash, previous_root_hash};
+
+impl Node {
+    /// Check the smirk database against the block store, and optionally repair it
+    ///
+    /// This must be run while the node is stopped, since it opens the databases directly. Returns
+    /// `true` if smirk is consistent with itself and with the latest block (after repairing, if
+    /// `repair` is set)
+    pub fn check_smirk(config: &Config, repair: bool) -> Result<bool> {
+        let db_path = config.db_path.join("latest");
+        let smirk_path = config.smirk_path.join("latest");
+
+        let block_store = BlockStore::<BlockFormat>::open_read_only(&db_path)?;
+        let (height, expected_root_hash) = match block_store.get_max_height()? {
+            Some(height) => {
+                let block = block_store
+                    .get(height)?
+                    .ok_or(Error::BlockNotFound { block: height })?
+                    .into_block();
+                (Some(height), block.content.state.root_hash)
+            }
+            None => (None, empty_tree_hash()),
+        };
+
+        // If a commit was interrupted before it was applied to smirk, the tree is still at the
+        // previous block's root, and the block is replayed when the node starts
+        let pending_root_hash = block_store
+            .get_pending_commit()?
+            .map(|height| previous_root_hash(&block_store, height))
+            .transpose()?;
+
+        info!(smirk_path = ?smirk_path, "Checking smirk integrity, this may take a while");
+
+        let report =
+            smirk::storage::check_integrity::<MERKLE_TREE_DEPTH, SmirkMetadata, _>(&smirk_path)?;
+
+        info!(
+            root_hash = ?report.root_hash,
+            ?expected_root_hash,
+            ?height,
+            ?pending_root_hash,
+            elements = report.elements,
+            incorrect_hashes = report.incorrect_hashes.len(),
+            stale_hashes = report.stale_hashes.len(),
+            missing_hashes = report.missing_hashes.len(),
+            unreadable_entries = report.unreadable_entries,
+            "Checked smirk integrity"
+        );
+
+        // repairing only fixes the stored hashes, the elements themselves are left alone
+        let root_matches =
+            report.root_hash == expected_root_hash || Some(report.root_hash) == pending_root_hash;
+        if report.root_hash != expected_root_hash && root_matches {
+            info!("Smirk is at the root before the pending commit, the node will replay the block when it starts");
+        }
+
+        if !root_matches {
+            warn!(
+                "The elements in smirk don't match the latest block. Restore a backup with `restore-backup`, or use `rollback` to roll back to a block before the mismatch. Otherwise, the node will move the db and smirk aside and sync from genesis when it starts"
+            );
+        }
+
+        if report.is_consistent() {
+            return Ok(root_matches);
+        }
+
+        if !repair {
+            warn!("Smirk's stored hashes are inconsistent, run with --repair to rebuild them");
+            return Ok(false);
+        }
+
+        smirk::storage::repair::<MERKLE_TREE_DEPTH, SmirkMetadata, _>(&smirk_path)?;
+        info!("Rebuilt smirk's stored hashes");
+
+        Ok(root_matches)
+    }
+}
@@ -1,12 +1,12 @@
 use std::{path::Path, sync::OnceLock};
 
 use block_store::BlockStore;
-use smirk::Element;
-use tracing::info;
+use smirk::{hash_cache::LruHashCache, Element};
+use tracing::{info, warn};
 
 use crate::{
     block::Block, config::Config, constants::MERKLE_TREE_DEPTH, types::BlockHeight, BlockFormat,
-    Node, NodeShared, PersistentMerkleTree, Result,
+    Error, Node, NodeShared, PersistentMerkleTree, Result,
 };
 
 pub(super) struct LoadedData {
@@ -15,11 +15,27 @@ pub(super) struct LoadedData {
     pub block: Block,
 }
 
-fn empty_tree_hash() -> Element {
+pub(super) fn empty_tree_hash() -> Element {
     static EMPTY_TREE_HASH: OnceLock<Element> = OnceLock::new();
     *EMPTY_TREE_HASH.get_or_init(|| smirk::Tree::<MERKLE_TREE_DEPTH, ()>::default().root_hash())
 }
 
+/// The root hash of the notes tree before the block at `height` was applied
+pub(super) fn previous_root_hash(
+    block_store: &BlockStore<BlockFormat>,
+    height: BlockHeight,
+) -> Result<Element> {
+    let Some(previous_height) = height.0.checked_sub(1) else {
+        return Ok(empty_tree_hash());
+    };
+
+    Ok(block_store
+        .get(BlockHeight(previous_height))?
+        .map_or_else(empty_tree_hash, |block| {
+            block.into_block().content.state.root_hash
+        }))
+}
+
 impl Node {
     pub(super) fn load_db_and_smirk(config: &Config) -> Result<LoadedData> {
         let db_path = &config.db_path.join("latest");
@@ -29,7 +45,12 @@ impl Node {
         info!("Loading Smirk from: {}", &smirk_path.to_str().unwrap());
 
         let block_store = BlockStore::create_or_load(db_path)?;
-        let mut persistent_tree = smirk::storage::Persistent::load(&smirk_path)?;
+        let mut persistent_tree = Self::load_notes_tree(config, &smirk_path)?;
+
+        let pending_commit = block_store.get_pending_commit()?;
+        if let Some(height) = pending_commit {
+            Self::recover_pending_commit(config, &block_store, &mut persistent_tree, height)?;
+        }
 
         let Some(max_height) = block_store.get_max_height()? else {
             info!(
@@ -40,8 +61,7 @@ impl Node {
             drop(persistent_tree);
 
             Self::reset_db_and_smirk(None, Some(&config.smirk_path))?;
-            let persistent_tree =
-                smirk::storage::Persistent::load(smirk_path)?;
+            let persistent_tree = Self::load_notes_tree(config, &smirk_path)?;
 
             debug_assert_eq!(persistent_tree.tree().root_hash(), empty_tree_hash());
 
@@ -54,7 +74,10 @@ impl Node {
             return Ok(data);
         };
 
-        let block = block_store.get(max_height)?.unwrap().into_block();
+        let block = block_store
+            .get(max_height)?
+            .ok_or(Error::BlockNotFound { block: max_height })?
+            .into_block();
 
         if persistent_tree.tree().root_hash() == block.content.state.root_hash {
             let data = LoadedData {
@@ -66,41 +89,38 @@ impl Node {
             return Ok(data);
         }
 
-        let previous_block = block_store
-            .get(BlockHeight(max_height.0 - 1))?
-            .unwrap()
-            .into_block();
-
-        if persistent_tree.tree().root_hash() == previous_block.content.state.root_hash {
-            info!(
-                local_tree_root_hash = ?persistent_tree.tree().root_hash(),
-                block_root_hash = ?block.content.state.root_hash,
-                previous_block_root_hash = ?previous_block.content.state.root_hash,
-                "The node crashed after committing to block store, but before committing to notes tree. We will recover by applying the block to the tree."
-            );
-
-            NodeShared::apply_block_to_tree(
+        // Data written by a version without the pending commit marker (or a crash that left no
+        // marker) can still be at the previous block's root, in which case the last block is
+        // replayed. Marked commits were already handled by `recover_pending_commit` above.
+        if pending_commit.is_none() {
+            let replayed = Self::replay_block_onto_previous_root(
+                config,
+                &block_store,
                 &mut persistent_tree,
-                &block.content.state,
+                &block,
                 max_height,
-                config.bad_blocks.contains(&max_height),
             )?;
-            assert!(persistent_tree.tree().root_hash() == block.content.state.root_hash);
 
-            let data = LoadedData {
-                block_store,
-                persistent_tree,
-                block,
-            };
+            if replayed {
+                info!(
+                    height = ?max_height,
+                    "The node crashed after committing to block store, but before committing to notes tree. Recovered by applying the block to the tree."
+                );
 
-            return Ok(data);
+                let data = LoadedData {
+                    block_store,
+                    persistent_tree,
+                    block,
+                };
+
+                return Ok(data);
+            }
         }
 
         info!(
             local_tree_root_hash = ?persistent_tree.tree().root_hash(),
             block_root_hash = ?block.content.state.root_hash,
-            previous_block_root_hash = ?previous_block.content.state.root_hash,
-            "Block store and tree are too far out of sync to recover. Resetting and starting from genesis"
+            "Block store and tree are out of sync. Resetting and starting from genesis"
         );
         drop(block_store);
         drop(persistent_tree);
@@ -108,7 +128,7 @@ impl Node {
         Self::reset_db_and_smirk(Some(&config.db_path), Some(&config.smirk_path))?;
 
         let block_store = BlockStore::create_or_load(db_path)?;
-        let persistent_tree = smirk::storage::Persistent::load(&smirk_path)?;
+        let persistent_tree = Self::load_notes_tree(config, &smirk_path)?;
 
         debug_assert!(block_store.get_max_height()?.is_none());
         debug_assert_eq!(persistent_tree.tree().root_hash(), empty_tree_hash(),);
@@ -122,6 +142,111 @@ impl Node {
         Ok(data)
     }
 
+    /// Finish a commit that was interrupted after the block was stored, but before it was applied
+    /// to the notes tree
+    ///
+    /// If the tree is still at the previous block's root, the block is replayed onto the tree. If
+    /// the tree is at neither root, the marker is left in place, and the stores are reset by
+    /// [`Node::load_db_and_smirk`]
+    fn recover_pending_commit(
+        config: &Config,
+        block_store: &BlockStore<BlockFormat>,
+        persistent_tree: &mut PersistentMerkleTree,
+        height: BlockHeight,
+    ) -> Result<()> {
+        let Some(block) = block_store.get(height)? else {
+            // The block is stored in the same write as the marker, so this shouldn't happen
+            warn!(
+                ?height,
+                "Found a pending commit, but its block is missing, clearing the marker"
+            );
+
+            block_store.clear_pending_commit()?;
+            return Ok(());
+        };
+        let block = block.into_block();
+
+        let tree_root_hash = persistent_tree.tree().root_hash();
+        let block_root_hash = block.content.state.root_hash;
+
+        if tree_root_hash == block_root_hash {
+            info!(
+                ?height,
+                "Found a pending commit that was already applied to the notes tree"
+            );
+
+            block_store.clear_pending_commit()?;
+            return Ok(());
+        }
+
+        let replayed = Self::replay_block_onto_previous_root(
+            config,
+            block_store,
+            persistent_tree,
+            &block,
+            height,
+        )?;
+
+        if !replayed {
+            warn!(
+                ?height,
+                ?tree_root_hash,
+                ?block_root_hash,
+                "Found a pending commit, but the notes tree is not at the previous block's root, so the block can't be replayed"
+            );
+
+            return Ok(());
+        }
+
+        info!(
+            ?height,
+            "Found a pending commit that wasn't applied to the notes tree, replayed the block"
+        );
+
+        block_store.clear_pending_commit()?;
+
+        Ok(())
+    }
+
+    /// Apply `block` to the notes tree, if the tree is at the root of the block before it
+    ///
+    /// Returns `false` (and leaves the tree unchanged) if the tree is at any other root
+    fn replay_block_onto_previous_root(
+        config: &Config,
+        block_store: &BlockStore<BlockFormat>,
+        persistent_tree: &mut PersistentMerkleTree,
+        block: &Block,
+        height: BlockHeight,
+    ) -> Result<bool> {
+        if persistent_tree.tree().root_hash() != previous_root_hash(block_store, height)? {
+            return Ok(false);
+        }
+
+        NodeShared::apply_block_to_tree(
+            persistent_tree,
+            &block.content.state,
+            height,
+            config.bad_blocks.contains(&height),
+        )?;
+
+        let root_hash = persistent_tree.tree().root_hash();
+        let block_root_hash = block.content.state.root_hash;
+        if root_hash != block_root_hash {
+            return Err(Error::InvalidBlockRoot {
+                got: root_hash,
+                expected: block_root_hash,
+            });
+        }
+
+        Ok(true)
+    }
+
+    /// Load the smirk tree at `path`, with a hash cache limited to the configured memory budget
+    pub(crate) fn load_notes_tree(config: &Config, path: &Path) -> Result<PersistentMerkleTree> {
+        let cache = LruHashCache::with_memory_budget(config.smirk_cache_memory_budget);
+        Ok(smirk::storage::Persistent::load_with_cache(path, cache)?)
+    }
+
     /// Moves current db and smirk to old-{unix-timestamp-millis}-{random}
     fn reset_db_and_smirk(db_path: Option<&Path>, smirk_path: Option<&Path>) -> Result<()> {
         let timestamp = chrono::Utc::now().timestamp_millis();
@@ -143,3 +268,89 @@ impl Node {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utxo::UtxoProof;
+
+    /// Store blocks 1 and 2, but only apply block 1 to the notes tree, as if the node crashed in
+    /// the middle of committing block 2
+    fn interrupted_commit(config: &Config, set_marker: bool) -> Element {
+        let block_store =
+            BlockStore::<BlockFormat>::create_or_load(&config.db_path.join("latest")).unwrap();
+        let mut notes_tree =
+            Node::load_notes_tree(config, &config.smirk_path.join("latest")).unwrap();
+
+        let mut blocks = Vec::new();
+        for height in 1..=2 {
+            let mut block = Block::default();
+            block.content.header.height = BlockHeight(height);
+            block.content.state.txns = vec![UtxoProof {
+                output_leaves: [Element::new(height), Element::NULL_HASH],
+                ..UtxoProof::default()
+            }];
+
+            NodeShared::apply_block_to_tree(
+                &mut notes_tree,
+                &block.content.state,
+                BlockHeight(height),
+                false,
+            )
+            .unwrap();
+            block.content.state.root_hash = notes_tree.tree().root_hash();
+            blocks.push(block);
+        }
+        let expected_root_hash = blocks[1].content.state.root_hash;
+
+        notes_tree.remove_batch([Element::new(2)]).unwrap();
+        assert_eq!(
+            notes_tree.tree().root_hash(),
+            blocks[0].content.state.root_hash
+        );
+
+        block_store
+            .set(&BlockFormat::V1(blocks[0].clone()))
+            .unwrap();
+        if set_marker {
+            block_store
+                .set_pending_commit(&BlockFormat::V1(blocks[1].clone()))
+                .unwrap();
+        } else {
+            block_store
+                .set(&BlockFormat::V1(blocks[1].clone()))
+                .unwrap();
+        }
+
+        expected_root_hash
+    }
+
+    #[test]
+    fn replays_pending_commit() {
+        let temp_dir = tempdir::TempDir::new("load").unwrap();
+        let config = Config::for_tests(temp_dir.path());
+        let expected_root_hash = interrupted_commit(&config, true);
+
+        let data = Node::load_db_and_smirk(&config).unwrap();
+
+        assert_eq!(data.block.content.header.height, BlockHeight(2));
+        assert_eq!(data.persistent_tree.tree().root_hash(), expected_root_hash);
+        assert_eq!(data.block_store.get_pending_commit().unwrap(), None);
+    }
+
+    #[test]
+    fn replays_last_block_without_marker() {
+        let temp_dir = tempdir::TempDir::new("load").unwrap();
+        let config = Config::for_tests(temp_dir.path());
+        let expected_root_hash = interrupted_commit(&config, false);
+
+        let data = Node::load_db_and_smirk(&config).unwrap();
+
+        assert_eq!(data.block.content.header.height, BlockHeight(2));
+        assert_eq!(data.persistent_tree.tree().root_hash(), expected_root_hash);
+        assert_eq!(
+            data.block_store.get_max_height().unwrap(),
+            Some(BlockHeight(2))
+        );
+    }
+}
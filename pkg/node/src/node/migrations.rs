@@ -0,0 +1,76 @@
+use block_store::BlockStore;
+use primitives::migration::{self, MigrationStatus};
+use serde::Serialize;
+
+use crate::{config::Config, prover::db::ProverDb, BlockFormat, Node, Result};
+
+/// A migration of one of the node's stores, as returned by [`Node::migrations`]
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreMigration {
+    /// The store the migration is for, `block_store` or `prover`
+    pub store: &'static str,
+    #[serde(flatten)]
+    pub status: MigrationStatus,
+    /// The result of dry running the migration, if it's pending and a dry run was asked for
+    pub dry_run: Option<DryRun>,
+}
+
+/// The result of dry running a pending migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DryRun {
+    /// The number of items the migration would migrate
+    Items(u64),
+    /// The store's migrations can't be dry run offline
+    Unsupported,
+}
+
+impl Node {
+    /// The migrations of the block store and the prover db, and whether they have been applied
+    ///
+    /// Pending migrations are applied when the node starts. If `dry_run` is set, the pending
+    /// block store migrations are run without changing the store, to find how many blocks they
+    /// would migrate. The prover db's migrations start from the rollup contract's height, so
+    /// they are reported as [`DryRun::Unsupported`]. Stores that don't exist yet are skipped.
+    /// The stores are opened read-only, so nothing is written, even when dry running
+    pub fn migrations(config: &Config, dry_run: bool) -> Result<Vec<StoreMigration>> {
+        let db_path = config.db_path.join("latest");
+        let prover_db_path = config.db_path.join("prover");
+
+        let mut migrations = Vec::new();
+
+        if db_path.exists() {
+            let block_store = BlockStore::<BlockFormat>::open_read_only(&db_path)?;
+
+            let runs = match dry_run {
+                true => migration::migrate(&block_store, &(), true)?,
+                false => Vec::new(),
+            };
+
+            migrations.extend(migration::status(&block_store)?.into_iter().map(|status| {
+                StoreMigration {
+                    store: "block_store",
+                    dry_run: runs
+                        .iter()
+                        .find(|run| run.id == status.id)
+                        .map(|run| DryRun::Items(run.items)),
+                    status,
+                }
+            }));
+        }
+
+        if prover_db_path.exists() {
+            let prover_db = ProverDb::open_read_only(&prover_db_path)?;
+
+            migrations.extend(migration::status(&prover_db)?.into_iter().map(|status| {
+                StoreMigration {
+                    store: "prover",
+                    dry_run: (dry_run && !status.applied).then_some(DryRun::Unsupported),
+                    status,
+                }
+            }));
+        }
+
+        Ok(migrations)
+    }
+}
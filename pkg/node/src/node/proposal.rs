@@ -61,10 +61,11 @@ impl NodeShared {
         }
 
         // The order of these operations is important.
-        // If we exit after commiting to block store,
-        // but before commiting to notes tree, we
-        // can detect it by checking the previous block's root hash.
-        self.block_store.set(
+        // The block is stored along with a pending commit marker,
+        // which is only cleared once the block has been applied to the notes tree.
+        // If we exit in between, `load_db_and_smirk` finds the marker
+        // and applies the block to the tree on startup.
+        self.block_store.set_pending_commit(
             &BlockFormat::V2(block.clone(), BlockMetadata {
                 timestamp_unix_s: Some(commit_time.timestamp() as u64)
             }),
@@ -72,6 +73,12 @@ impl NodeShared {
 
         Self::apply_block_to_tree(&mut self.notes_tree.write(), state, height, skip_validation)?;
 
+        self.block_store.clear_pending_commit()?;
+
+        if let Err(err) = self.prune_blocks() {
+            warn!(?err, "Failed to prune old blocks");
+        }
+
         let block = Arc::new(block);
 
         // Commit changes in mempool (releasing unused txns and removing used ones). This will
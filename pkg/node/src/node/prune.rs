@@ -0,0 +1,56 @@
+use block_store::BlockStore;
+use tracing::info;
+
+use crate::{
+    config::{Config, StorageMode},
+    types::BlockHeight,
+    BlockFormat, Error, NodeShared, Result,
+};
+
+/// Prune the proofs of blocks that are more than `prune_depth` blocks behind the latest block, if
+/// the node is in [`StorageMode::Full`]
+pub(super) fn prune_blocks(config: &Config, block_store: &BlockStore<BlockFormat>) -> Result<()> {
+    if config.storage_mode != StorageMode::Full {
+        return Ok(());
+    }
+
+    let Some(max_height) = block_store.get_max_height()? else {
+        return Ok(());
+    };
+
+    let Some(height) = max_height.0.checked_sub(config.prune_depth) else {
+        return Ok(());
+    };
+
+    let pruned = block_store.prune(BlockHeight(height))?;
+    if pruned > 0 {
+        info!(pruned, ?height, "Pruned old blocks");
+    }
+
+    Ok(())
+}
+
+impl NodeShared {
+    pub(super) fn prune_blocks(&self) -> Result<()> {
+        prune_blocks(&self.config, &self.block_store)
+    }
+
+    /// The height at or below which the proofs of blocks have been pruned, if any
+    pub(crate) fn pruned_height(&self) -> Result<Option<BlockHeight>> {
+        Ok(self.block_store.get_pruned_height()?)
+    }
+
+    /// Return [`Error::Pruned`] if the proofs of the block at `height` have been pruned
+    pub(crate) fn check_not_pruned(&self, height: BlockHeight) -> Result<()> {
+        if is_pruned(height, self.pruned_height()?) {
+            return Err(Error::Pruned { height });
+        }
+
+        Ok(())
+    }
+}
+
+/// Have the proofs of the block at `height` been pruned, given the [`NodeShared::pruned_height`]
+pub(crate) fn is_pruned(height: BlockHeight, pruned_height: Option<BlockHeight>) -> bool {
+    matches!(pruned_height, Some(pruned_height) if height <= pruned_height)
+}
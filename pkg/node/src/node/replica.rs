@@ -0,0 +1,168 @@
+use std::{
+    fs,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use block_store::{BlockListOrder, BlockStore, StoreList};
+use smirk::hash_cache::LruHashCache;
+use tracing::{info, warn};
+
+use crate::{
+    block::Block, config::Config, constants::RECENT_ROOT_COUNT, BlockFormat, Node, NodeShared,
+    PersistentMerkleTree, Result,
+};
+
+use super::{
+    history::rebuild_tree_history,
+    load::{empty_tree_hash, LoadedData},
+};
+
+impl Node {
+    /// Open the block store and notes tree of the primary node as read-only secondary instances
+    pub(super) fn load_replica(config: &Config) -> Result<LoadedData> {
+        let db_path = config.db_path.join("latest");
+        let smirk_path = config.smirk_path.join("latest");
+        let replica_db_path = config.replica_path.join("db");
+        let replica_smirk_path = config.replica_path.join("smirk");
+
+        info!(
+            ?db_path,
+            ?smirk_path,
+            replica_path = ?config.replica_path,
+            "Opening the primary's data as a replica"
+        );
+
+        fs::create_dir_all(&replica_db_path)?;
+        fs::create_dir_all(&replica_smirk_path)?;
+
+        // The tree is opened first, so the block store has every block that was applied to it
+        let cache = LruHashCache::with_memory_budget(config.smirk_cache_memory_budget);
+        let persistent_tree =
+            smirk::storage::Persistent::open_secondary(&smirk_path, &replica_smirk_path, cache)?;
+        let block_store = BlockStore::open_secondary(&db_path, &replica_db_path)?;
+
+        let block = match find_tree_block(&block_store, &persistent_tree)? {
+            Some(block) => block,
+            None => {
+                if persistent_tree.tree().root_hash() != empty_tree_hash() {
+                    warn!(
+                        root_hash = ?persistent_tree.tree().root_hash(),
+                        "The primary's notes tree doesn't match any recent block, starting from genesis until it does"
+                    );
+                }
+
+                Block::genesis()
+            }
+        };
+
+        Ok(LoadedData {
+            block_store,
+            persistent_tree,
+            block,
+        })
+    }
+}
+
+impl NodeShared {
+    /// Keep a replica caught up with the primary node, until the node is stopped
+    pub(super) async fn follow_primary(&self) {
+        let interval = Duration::from_millis(self.config.replica_catch_up_interval_ms);
+
+        loop {
+            if let Err(err) = self.catch_up_with_primary() {
+                warn!(?err, "Failed to catch up with the primary");
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Read the blocks committed by the primary since the last catch up, and notify commit
+    /// listeners of them
+    fn catch_up_with_primary(&self) -> Result<()> {
+        let height = self.height();
+
+        // Like when loading, the tree is caught up before the block store. The changes are read
+        // while RPC requests can still read the tree, so the write lock is only held to apply them
+        let changes = self.notes_tree.read().primary_changes()?;
+        if let Some(changes) = changes {
+            self.notes_tree.write().apply_primary_changes(changes)?;
+        }
+        self.block_store.catch_up_with_primary()?;
+
+        let Some(block) = find_tree_block(&self.block_store, &self.notes_tree.read())? else {
+            // the primary is in the middle of a commit, try again next time
+            return Ok(());
+        };
+
+        let new_height = block.content.header.height;
+        if new_height <= height {
+            return Ok(());
+        }
+
+        // The tree is only known at the height it caught up to, so the heights in between are
+        // rebuilt from the block store
+        {
+            let mut notes_tree = self.notes_tree.write();
+            notes_tree.commit_version(new_height.0);
+            rebuild_tree_history(
+                &self.block_store,
+                &mut notes_tree,
+                height.next(),
+                new_height,
+            )?;
+        }
+
+        {
+            let mut block_cache = self.block_cache.lock();
+            block_cache.insert(block);
+            block_cache.confirm(new_height);
+        }
+
+        let blocks = self
+            .block_store
+            .list(height.next()..=new_height, BlockListOrder::LowestToHighest)
+            .into_iterator()
+            .map(|r| r.map(|(_, block)| Arc::new(block.into_block())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut state = self.state.lock();
+        state.last_commit = Some(Instant::now());
+        for block in blocks {
+            state
+                .listeners
+                .retain(|tx| tx.send(Arc::clone(&block)).is_ok());
+        }
+
+        info!(?height, ?new_height, "Caught up with the primary");
+
+        Ok(())
+    }
+}
+
+/// The latest block whose root matches the notes tree
+///
+/// The primary stores each block before applying it to the tree, so the tree can be behind the
+/// block store. Only recent blocks are checked, since the tree is never far behind
+fn find_tree_block(
+    block_store: &BlockStore<BlockFormat>,
+    notes_tree: &PersistentMerkleTree,
+) -> Result<Option<Block>> {
+    let root_hash = notes_tree.tree().root_hash();
+
+    for block in block_store
+        .list(.., BlockListOrder::HighestToLowest)
+        .into_iterator()
+        .take(RECENT_ROOT_COUNT as usize)
+    {
+        let (_, block) = block?;
+        let block = block.into_block();
+
+        if block.content.state.root_hash == root_hash {
+            return Ok(Some(block));
+        }
+    }
+
+    Ok(None)
+}
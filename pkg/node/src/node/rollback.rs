@@ -0,0 +1,142 @@
+use block_store::{BlockListOrder, BlockStore, StoreList};
+use tracing::info;
+
+use crate::{config::Config, types::BlockHeight, BlockFormat, Error, Node, Result};
+
+use super::load::empty_tree_hash;
+
+impl Node {
+    /// Roll back the block store and the notes tree to `height`, removing all later blocks and
+    /// the leaves they inserted
+    ///
+    /// This must be run while the node is stopped. When it starts again, the removed blocks are
+    /// synced from peers, so a bad block should also be added to `bad_blocks`
+    pub fn rollback(config: &Config, height: BlockHeight) -> Result<()> {
+        let db_path = config.db_path.join("latest");
+        let smirk_path = config.smirk_path.join("latest");
+
+        let block_store = BlockStore::<BlockFormat>::create_or_load(&db_path)?;
+        let mut notes_tree = Self::load_notes_tree(config, &smirk_path)?;
+
+        let expected_root_hash = match height {
+            BlockHeight(0) => empty_tree_hash(),
+            height => {
+                let Some(block) = block_store.get(height)? else {
+                    return Err(Error::BlockNotFound { block: height });
+                };
+
+                block.into_block().content.state.root_hash
+            }
+        };
+
+        // The tree is rolled back first, so if we exit before the block store is truncated,
+        // running the rollback again finishes it
+        let mut leaves = Vec::new();
+        for block in block_store
+            .list(height.next().., BlockListOrder::LowestToHighest)
+            .into_iterator()
+        {
+            let (_, block) = block?;
+
+            leaves.extend(block.into_block().content.state.leaves_non_null());
+        }
+
+        // Only remove the leaves that were inserted after `height`. If collisions were ignored
+        // when applying a block, its leaves may have been inserted by an earlier block
+        let leaves = leaves
+            .into_iter()
+            .filter(|leaf| {
+                notes_tree
+                    .tree()
+                    .get(*leaf)
+                    .map_or(false, |meta| meta.inserted_in > height.0)
+            })
+            .collect::<Vec<_>>();
+
+        info!(
+            ?height,
+            leaves = leaves.len(),
+            "Removing leaves from the notes tree"
+        );
+        notes_tree.remove_batch(leaves)?;
+
+        let root_hash = notes_tree.tree().root_hash();
+        if root_hash != expected_root_hash {
+            return Err(Error::InvalidBlockRoot {
+                got: root_hash,
+                expected: expected_root_hash,
+            });
+        }
+
+        let removed = block_store.truncate_after(height)?;
+
+        info!(?height, blocks = removed.len(), "Rolled back the chain");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smirk::Element;
+
+    use super::*;
+    use crate::{utxo::UtxoProof, Block, NodeShared};
+
+    #[test]
+    fn rollback_removes_later_blocks_and_their_leaves() {
+        let temp_dir = tempdir::TempDir::new("rollback").unwrap();
+        let config = Config::for_tests(temp_dir.path());
+        let db_path = config.db_path.join("latest");
+        let smirk_path = config.smirk_path.join("latest");
+
+        let block_store = BlockStore::<BlockFormat>::create_or_load(&db_path).unwrap();
+        let mut notes_tree = Node::load_notes_tree(&config, &smirk_path).unwrap();
+
+        let mut root_hashes = Vec::new();
+        for height in 1..=3 {
+            let mut block = Block::default();
+            block.content.header.height = BlockHeight(height);
+            block.content.state.txns = vec![UtxoProof {
+                output_leaves: [Element::new(height), Element::NULL_HASH],
+                ..UtxoProof::default()
+            }];
+
+            NodeShared::apply_block_to_tree(
+                &mut notes_tree,
+                &block.content.state,
+                BlockHeight(height),
+                false,
+            )
+            .unwrap();
+            block.content.state.root_hash = notes_tree.tree().root_hash();
+            root_hashes.push(block.content.state.root_hash);
+
+            block_store.set(&BlockFormat::V1(block)).unwrap();
+        }
+
+        drop(block_store);
+        drop(notes_tree);
+
+        Node::rollback(&config, BlockHeight(1)).unwrap();
+
+        let block_store = BlockStore::<BlockFormat>::create_or_load(&db_path).unwrap();
+        assert_eq!(block_store.get_max_height().unwrap(), Some(BlockHeight(1)));
+        assert!(block_store.get(BlockHeight(2)).unwrap().is_none());
+        assert!(block_store
+            .get_txn_location_by_element(Element::new(2).to_be_bytes())
+            .unwrap()
+            .is_none());
+
+        let notes_tree = Node::load_notes_tree(&config, &smirk_path).unwrap();
+        assert_eq!(notes_tree.tree().root_hash(), root_hashes[0]);
+        assert!(notes_tree.tree().contains_element(&Element::new(1)));
+        assert!(!notes_tree.tree().contains_element(&Element::new(2)));
+        assert!(!notes_tree.tree().contains_element(&Element::new(3)));
+
+        // rolling back to the current height does nothing
+        drop(block_store);
+        drop(notes_tree);
+        Node::rollback(&config, BlockHeight(1)).unwrap();
+    }
+}
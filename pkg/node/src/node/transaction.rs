@@ -7,11 +7,15 @@ use tracing::{error, info, instrument};
 use crate::{
     network::NetworkEvent,
     utxo::{validate_txn, UtxoProof},
-    Block, Error, NodeShared, Result,
+    Block, Error, Mode, NodeShared, Result,
 };
 
 impl NodeShared {
     pub async fn submit_transaction_and_wait(&self, utxo: UtxoProof) -> Result<Arc<Block>> {
+        if self.config.mode == Mode::Replica {
+            return Err(Error::ReadOnlyReplica);
+        }
+
         let mut started_waiting_at_eth_block = None;
         loop {
             match self.validate_transaction(&utxo).await {
@@ -1,11 +1,12 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use primitives::block_height::BlockHeight;
 use serde::{Deserialize, Serialize};
 use wire_message::WireMessage;
+use zk_primitives::Element;
 
 use crate::utxo::UtxoProof;
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, BorshSchema, Serialize, Deserialize)]
 pub struct TxnMetadata {
     pub block_height: BlockHeight,
     pub block_time: Option<u64>,
@@ -13,8 +14,8 @@ pub struct TxnMetadata {
     pub block_txn_index: u32,
 }
 
-#[derive(Debug, Clone)]
-#[wire_message::wire_message]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[wire_message::wire_message(schema)]
 pub enum TxnFormat {
     V1(UtxoProof, TxnMetadata),
     // TODO next version:
@@ -44,4 +45,28 @@ impl block_store::Transaction for TxnFormat {
             Self::V1(txn, _) => txn.hash().into_inner(),
         }
     }
+
+    fn leaves(&self) -> Vec<[u8; 32]> {
+        match self {
+            Self::V1(txn, _) => txn
+                .leaves()
+                .into_iter()
+                .filter(|leaf| *leaf != Element::NULL_HASH)
+                .map(Element::to_be_bytes)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_snapshot() {
+        wire_message::test_api::assert_schema_snapshot::<TxnFormat>(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/node/snapshots/txn_format.schema"
+        ));
+    }
 }
@@ -1,7 +1,9 @@
-use std::{ops::Range, path::Path};
+use std::{ops::Range, path::Path, sync::Arc};
 
 use borsh::BorshDeserialize;
+use primitives::migration::{self, Checkpoint, Migrate, Migration, Step};
 use prover::RollupInput;
+use serde::{Deserialize, Serialize};
 use wire_message::WireMessage;
 use zk_primitives::Element;
 
@@ -23,6 +25,12 @@ pub enum Error {
 
     #[error("io error")]
     Io(#[from] std::io::Error),
+
+    #[error("migration error: {0}")]
+    Migration(#[from] migration::Error),
+
+    #[error("tokio-postgres error")]
+    TokioPostgres(#[from] tokio_postgres::Error),
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -31,10 +39,12 @@ pub(crate) struct ProverDb {
     db: rocksdb::DB,
 }
 
+#[derive(Debug)]
 pub(crate) enum Key {
     LastSeenBlock,
     Rollup { height: BlockHeight },
     ProverVersion,
+    MigrationCheckpoint,
 }
 
 impl Key {
@@ -43,6 +53,7 @@ impl Key {
             Self::LastSeenBlock => 0,
             Self::Rollup { .. } => 1,
             Self::ProverVersion => 2,
+            Self::MigrationCheckpoint => 3,
         }
     }
 
@@ -55,12 +66,13 @@ impl Key {
                 out.extend_from_slice(&height.to_be_bytes());
             }
             Self::ProverVersion => {}
+            Self::MigrationCheckpoint => {}
         }
 
         out
     }
 
-    fn deserialize(bytes: &[u8]) -> Result<Self> {
+    pub(crate) fn deserialize(bytes: &[u8]) -> Result<Self> {
         if bytes.is_empty() {
             return Err(Error::InvalidKey);
         }
@@ -73,26 +85,29 @@ impl Key {
                 Ok(Self::Rollup { height })
             }
             2 => Ok(Self::ProverVersion),
+            3 => Ok(Self::MigrationCheckpoint),
             _ => Err(Error::InvalidKey),
         }
     }
 }
 
-#[derive(Debug, borsh::BorshSerialize, borsh::BorshDeserialize)]
+#[derive(Debug, borsh::BorshSerialize, borsh::BorshDeserialize, Serialize, Deserialize)]
 pub(crate) struct LastSeenBlock {
     pub(crate) height: BlockHeight,
     pub(crate) root_hash: Element,
 }
 
-#[derive(Debug, borsh::BorshSerialize, borsh::BorshDeserialize)]
-enum ValueV1 {
+#[derive(Debug, borsh::BorshSerialize, borsh::BorshDeserialize, Serialize, Deserialize)]
+pub(crate) enum ValueV1 {
     LastSeenBlock(LastSeenBlock),
     Rollup(RollupInput),
     ProverVersion(u64),
+    MigrationCheckpoint(Vec<u8>),
 }
 
+#[derive(Serialize, Deserialize)]
 #[wire_message::wire_message]
-enum Value {
+pub(crate) enum Value {
     V1(ValueV1),
 }
 
@@ -113,7 +128,17 @@ impl WireMessage for Value {
     }
 }
 
-pub(crate) const LATEST_VERSION: u64 = 1;
+/// The number of rollups each step of the `postgres-rollups` migration copies
+const MIGRATION_BATCH_SIZE: usize = 100;
+
+/// What the [`ProverDb`] migrations need, other than the db
+pub(crate) struct MigrationContext {
+    /// Rollups below this height have already been submitted to the contract
+    pub(crate) contract_height: u64,
+    pub(crate) postgres: Option<Arc<tokio_postgres::Client>>,
+    /// Migrations are synchronous, so they use this to run postgres queries
+    pub(crate) runtime: tokio::runtime::Handle,
+}
 
 impl ProverDb {
     pub(crate) fn create_or_load(path: &Path) -> Result<Self> {
@@ -121,11 +146,17 @@ impl ProverDb {
         let db = rocksdb::DB::open_default(path)?;
         let db = Self { db };
         if new_db {
-            db.set_version(LATEST_VERSION)?;
+            db.set_version(Self::latest_version())?;
         }
         Ok(db)
     }
 
+    /// Open the existing db at `path` read-only, without creating or migrating it
+    pub(crate) fn open_read_only(path: &Path) -> Result<Self> {
+        let db = rocksdb::DB::open_for_read_only(&rocksdb::Options::default(), path, false)?;
+        Ok(Self { db })
+    }
+
     fn get(&self, key: Key) -> Result<Option<Vec<u8>>> {
         let bytes = self.db.get(key.serialize())?;
         Ok(bytes)
@@ -203,25 +234,107 @@ impl ProverDb {
         })
     }
 
-    pub(crate) fn get_version(&self) -> Result<Option<u64>> {
+    /// Copy the rollups that haven't been submitted yet to postgres, where the rollup worker
+    /// reads them from
+    fn copy_rollups_to_postgres(
+        &self,
+        ctx: &MigrationContext,
+        cursor: Option<&[u8]>,
+        dry_run: bool,
+    ) -> Result<Step> {
+        let start = match cursor {
+            Some(cursor) => u64::from_be_bytes(
+                cursor
+                    .try_into()
+                    .map_err(|_| migration::Error::InvalidCheckpoint)?,
+            ),
+            None => ctx.contract_height,
+        };
+
+        let rollups = self
+            .list_rollups(BlockHeight(start)..BlockHeight(u64::MAX))
+            .take(MIGRATION_BATCH_SIZE)
+            .collect::<Result<Vec<_>>>()?;
+
+        if let (Some(client), false) = (&ctx.postgres, dry_run) {
+            for (height, rollup_input) in &rollups {
+                #[allow(clippy::disallowed_methods)]
+                ctx.runtime.block_on(client.execute(
+                    "INSERT INTO rollup_proofs (height, proof) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                    &[&(height.0 as i64), &borsh::to_vec(rollup_input)?],
+                ))?;
+            }
+        }
+
+        let items = rollups.len() as u64;
+        match rollups.last() {
+            Some((height, _)) if rollups.len() == MIGRATION_BATCH_SIZE => Ok(Step::Continue {
+                cursor: height.next().to_be_bytes().to_vec(),
+                items,
+            }),
+            _ => Ok(Step::Done { items }),
+        }
+    }
+}
+
+impl Migrate for ProverDb {
+    type Context = MigrationContext;
+    type Error = Error;
+
+    fn migrations() -> Vec<Migration<Self>> {
+        vec![Migration {
+            id: "postgres-rollups",
+            description: "Copy rollups that haven't been submitted yet to postgres",
+            step: Self::copy_rollups_to_postgres,
+        }]
+    }
+
+    fn version(&self) -> Result<u64> {
         let Some(bytes) = self.get(Key::ProverVersion)? else {
-            return Ok(None);
+            return Ok(0);
         };
 
         let value = Value::deserialize(&mut &*bytes)?;
 
         match value {
-            Value::V1(ValueV1::ProverVersion(value)) => Ok(Some(value)),
+            Value::V1(ValueV1::ProverVersion(value)) => Ok(value),
             Value::V1(_) => Err(Error::InvalidValue),
         }
     }
 
-    pub(crate) fn set_version(&self, version: u64) -> Result<()> {
+    fn set_version(&self, version: u64) -> Result<()> {
         self.set(
             Key::ProverVersion,
             Value::V1(ValueV1::ProverVersion(version)),
-        )?;
-        Ok(())
+        )
+    }
+
+    fn checkpoint(&self) -> Result<Option<Checkpoint>> {
+        let Some(bytes) = self.get(Key::MigrationCheckpoint)? else {
+            return Ok(None);
+        };
+
+        let value = Value::deserialize(&mut &*bytes)?;
+
+        match value {
+            Value::V1(ValueV1::MigrationCheckpoint(bytes)) => {
+                Ok(Some(Checkpoint::from_bytes(&bytes)?))
+            }
+            Value::V1(_) => Err(Error::InvalidValue),
+        }
+    }
+
+    fn set_checkpoint(&self, checkpoint: Option<&Checkpoint>) -> Result<()> {
+        match checkpoint {
+            Some(checkpoint) => self.set(
+                Key::MigrationCheckpoint,
+                Value::V1(ValueV1::MigrationCheckpoint(checkpoint.to_bytes())),
+            ),
+            None => {
+                self.db.delete(Key::MigrationCheckpoint.serialize())?;
+                Ok(())
+            }
+        }
     }
 }
 
@@ -247,4 +360,21 @@ mod tests {
         assert_eq!(rollups.len(), 1);
         assert_eq!(rollups[0].as_ref().unwrap().0, BlockHeight(2));
     }
+
+    #[test]
+    fn open_read_only() {
+        let tmpdir = tempdir::TempDir::new("open_read_only").unwrap();
+        let path = tmpdir.path().join("prover");
+
+        assert!(ProverDb::open_read_only(&path).is_err());
+        assert!(!path.exists());
+
+        let db = ProverDb::create_or_load(&path).unwrap();
+        db.set_rollup(1.into(), RollupInput::default()).unwrap();
+        drop(db);
+
+        let db = ProverDb::open_read_only(&path).unwrap();
+        assert_eq!(db.list_rollups(BlockHeight(1)..BlockHeight(2)).count(), 1);
+        assert!(db.set_rollup(2.into(), RollupInput::default()).is_err());
+    }
 }
@@ -20,9 +20,6 @@ pub enum Error {
     #[error("root {got} does not match expected root {expected}")]
     RootMismatch { got: Element, expected: Element },
 
-    #[error("invalid prover version '{0}'")]
-    InvalidProverVersion(u64),
-
     #[error("failed to get nonce")]
     FailedToGetNonce(#[source] web3::Error),
 
@@ -58,6 +55,9 @@ pub enum Error {
 
     #[error("tokio-postgres error")]
     TokioPostgresError(#[from] tokio_postgres::Error),
+
+    #[error("tokio join error")]
+    TokioJoinError(#[from] tokio::task::JoinError),
 }
 
 pub(super) type Result<T, E = Error> = std::result::Result<T, E>;
@@ -7,17 +7,19 @@ use std::time::Duration;
 use super::{Error, Result};
 use crate::config::Config;
 use crate::constants::MERKLE_TREE_DEPTH;
-use crate::prover::db::{LastSeenBlock, ProverDb};
+use crate::node::cache_metrics;
+use crate::prover::db::{LastSeenBlock, MigrationContext, ProverDb};
 use crate::types::BlockHeight;
 use crate::{Mode, NodeShared, PersistentMerkleTree};
 use contracts::RollupContract;
 use either::Either;
 use futures::StreamExt;
+use primitives::migration::{self, Migrate};
 use prover::smirk_metadata::SmirkMetadata;
 use prover::{Prover, Transaction};
 use prover::{RollupInput, MAXIMUM_TXNS};
 use scopeguard::ScopeGuard;
-use smirk::{empty_tree_hash, Element, Tree};
+use smirk::{empty_tree_hash, hash_cache::LruHashCache, Element, Tree};
 use tokio::sync::{mpsc, Mutex, Notify};
 use tracing::{error, info};
 use zk_circuits::data::Utxo;
@@ -56,7 +58,10 @@ pub async fn run_prover(config: &Config, node: Arc<NodeShared>) -> Result<()> {
     let prover = Arc::new(Prover::new(contract.clone()));
 
     let smirk_path = config.smirk_path.join("prover");
-    let notes_tree = Arc::new(Mutex::new(Some(PersistentMerkleTree::load(&smirk_path)?)));
+    let cache = LruHashCache::with_memory_budget(config.smirk_cache_memory_budget);
+    let notes_tree = PersistentMerkleTree::load_with_cache(&smirk_path, cache)?;
+    cache_metrics::export("prover", notes_tree.tree().cache().clone());
+    let notes_tree = Arc::new(Mutex::new(Some(notes_tree)));
     let delete_smirk = || {
         let notes_tree = Arc::clone(&notes_tree);
         let smirk_path = smirk_path.clone();
@@ -70,29 +75,18 @@ pub async fn run_prover(config: &Config, node: Arc<NodeShared>) -> Result<()> {
 
     let proof_notifier = Arc::new(Notify::new());
 
-    match prover_state_db.get_version()? {
-        None => {
-            let contract_height = contract.block_height().await?;
-            let proofs =
-                prover_state_db.list_rollups(BlockHeight(contract_height)..BlockHeight(u64::MAX));
-            for proof in proofs {
-                let (height, rollup_input) = proof?;
-                if let Some(client) = client.as_ref() {
-                    #[allow(clippy::disallowed_methods)]
-                    client
-                        .execute(
-                            "INSERT INTO rollup_proofs (height, proof) VALUES ($1, $2) ON CONFLICT DO NOTHING",
-                            &[&(height.0 as i64), &borsh::to_vec(&rollup_input)?],
-                        )
-                        .await?;
-                }
-            }
+    if prover_state_db.version()? != ProverDb::latest_version() {
+        let ctx = MigrationContext {
+            contract_height: contract.block_height().await?,
+            postgres: client.clone(),
+            runtime: tokio::runtime::Handle::current(),
+        };
 
-            prover_state_db.set_version(1)?;
-            info!("Migrated to version 1");
-        }
-        Some(1) => {}
-        Some(n) => return Err(Error::InvalidProverVersion(n)),
+        tokio::task::spawn_blocking({
+            let prover_state_db = Arc::clone(&prover_state_db);
+            move || migration::migrate(&*prover_state_db, &ctx, false)
+        })
+        .await??;
     }
 
     tokio::try_join!(
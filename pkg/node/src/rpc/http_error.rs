@@ -1,6 +1,6 @@
 use super::routes;
 use crate::errors;
-use primitives::hash::CryptoHash;
+use primitives::{block_height::BlockHeight, hash::CryptoHash};
 use rpc::{code::ErrorCode, error::HTTPError};
 use serde::Serialize;
 use zk_primitives::Element;
@@ -46,6 +46,11 @@ pub struct HashData {
     pub hash: CryptoHash,
 }
 
+#[derive(Debug, Serialize)]
+pub struct HeightData {
+    pub height: BlockHeight,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ElementStringData {
     pub element: String,
@@ -98,6 +103,18 @@ impl From<errors::Error> for HTTPError {
                     element: commitment,
                 }),
             ),
+            errors::Error::HeightNotInTreeHistory { height } => HTTPError::new(
+                ErrorCode::NotFound,
+                "height-not-in-tree-history",
+                Some(err.into()),
+                Some(HeightData { height }),
+            ),
+            errors::Error::Pruned { height } => HTTPError::new(
+                ErrorCode::NotFound,
+                "pruned",
+                Some(err.into()),
+                Some(HeightData { height }),
+            ),
             errors::Error::MintIsNotInTheContract { key } => HTTPError::new(
                 ErrorCode::NotFound,
                 "mint-not-in-contract",
@@ -128,6 +145,12 @@ impl From<errors::Error> for HTTPError {
                 Some(err.into()),
                 Some(HashData { hash: txn }),
             ),
+            errors::Error::ReadOnlyReplica => HTTPError::new(
+                ErrorCode::FailedPrecondition,
+                "read-only-replica",
+                Some(err.into()),
+                None::<()>,
+            ),
             errors::Error::FailedToParseElement { element, source } => HTTPError::new(
                 ErrorCode::BadRequest,
                 "failed-to-parse-element",
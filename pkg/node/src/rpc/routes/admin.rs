@@ -0,0 +1,92 @@
+use super::State;
+use crate::Backup;
+use actix_web::{http::header, web, HttpRequest};
+use p2p2::{IpFilter, IpNet};
+use rpc::error::{HTTPError, HttpResult};
+use serde::Deserialize;
+use std::{collections::HashSet, sync::Arc};
+
+/// Admin endpoints require the configured admin token as a bearer token, and are disabled if no
+/// token is configured
+pub(super) fn authorize(state: &State, req: &HttpRequest) -> HttpResult<()> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match (state.node.admin_token(), token) {
+        (Some(expected), Some(token))
+            if constant_time_eq(expected.as_bytes(), token.as_bytes()) =>
+        {
+            Ok(())
+        }
+        _ => Err(HTTPError::permission_denied()),
+    }
+}
+
+/// Compare tokens without returning early, so the response time doesn't reveal how much of a
+/// token was correct
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// POST /admin/backups - create a backup of the db and smirk while the node is running
+#[tracing::instrument(err, skip_all)]
+pub async fn create_backup(
+    state: web::Data<State>,
+    req: HttpRequest,
+) -> HttpResult<web::Json<Backup>> {
+    authorize(&state, &req)?;
+
+    let node = Arc::clone(&state.node);
+    let backup = tokio::task::spawn_blocking(move || node.create_backup())
+        .await
+        .map_err(|e| HTTPError::internal(e.into()))??;
+
+    Ok(web::Json(backup))
+}
+
+/// GET /admin/backups - list backups, oldest first
+#[tracing::instrument(err, skip_all)]
+pub async fn list_backups(
+    state: web::Data<State>,
+    req: HttpRequest,
+) -> HttpResult<web::Json<Vec<Backup>>> {
+    authorize(&state, &req)?;
+
+    Ok(web::Json(state.node.list_backups()?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetIpFilterReq {
+    #[serde(default)]
+    whitelisted_ips: HashSet<IpNet>,
+    #[serde(default)]
+    banned_ips: HashSet<IpNet>,
+}
+
+/// PUT /admin/ip-filter - replace the IP whitelist and banlist without restarting the node
+///
+/// Connections that are no longer allowed are closed. The change isn't persisted, so the
+/// configured lists apply again after a restart
+#[tracing::instrument(err, skip_all)]
+pub async fn set_ip_filter(
+    state: web::Data<State>,
+    req: HttpRequest,
+    web::Json(body): web::Json<SetIpFilterReq>,
+) -> HttpResult<web::Json<()>> {
+    authorize(&state, &req)?;
+
+    let mut ip_filter = IpFilter::default();
+    ip_filter.whitelisted_ips = body.whitelisted_ips;
+    ip_filter.banned_ips = body.banned_ips;
+
+    state.node.set_ip_filter(ip_filter).await?;
+
+    Ok(web::Json(()))
+}
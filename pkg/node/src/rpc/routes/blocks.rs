@@ -1,5 +1,5 @@
 use super::{txn::TxnWithInfo, State};
-use crate::node;
+use crate::node::{self, prune::is_pruned};
 use actix_web::web;
 use either::Either;
 use primitives::{
@@ -22,7 +22,7 @@ pub struct Block {
 }
 
 impl Block {
-    fn from_node_block(block: crate::block::Block, time: u64) -> Self {
+    fn from_node_block(block: crate::block::Block, time: u64, proofs_pruned: bool) -> Self {
         let crate::block::Block { content, signature } = block;
         let crate::block::BlockContent { header, state } = content;
         let crate::block::BlockHeader {
@@ -54,6 +54,7 @@ impl Block {
                             index_in_block: index_in_block as u64,
                             block_height: height,
                             time,
+                            proof_pruned: proofs_pruned,
                         })
                         .collect(),
                 },
@@ -133,6 +134,10 @@ pub async fn get_block(
         node::BlockFormat::V2(block, metadata) => (block, metadata),
     };
 
+    // Looking up a single block asks for its proofs, so pruned blocks are an error. Listings
+    // return them with `proofs_pruned` set instead.
+    state.node.check_not_pruned(block.content.header.height)?;
+
     let max_height = state.node.max_height();
 
     let time = metadata
@@ -144,7 +149,8 @@ pub async fn get_block(
     Ok(web::Json(BlockResponse {
         time,
         hash: block.hash(),
-        block: Block::from_node_block(block, time),
+        proofs_pruned: false,
+        block: Block::from_node_block(block, time, false),
     }))
 }
 
@@ -153,6 +159,8 @@ pub struct BlockWithInfo {
     block: Block,
     hash: CryptoHash,
     time: u64,
+    /// The proofs of the block's transactions have been pruned, so they are empty
+    proofs_pruned: bool,
 }
 
 #[derive(Serialize)]
@@ -224,6 +232,7 @@ pub async fn list_blocks(
     };
 
     let max_height = state.node.max_height();
+    let pruned_height = state.node.pruned_height()?;
 
     let (cursor, blocks) = Paginator::new(
         blocks.map(|r| {
@@ -238,10 +247,13 @@ pub async fn list_blocks(
                     block.content.header.height,
                     max_height,
                 ));
+            let proofs_pruned = is_pruned(block.content.header.height, pruned_height);
+
             Ok::<_, node::Error>(BlockWithInfo {
                 time,
                 hash: block.hash(),
-                block: Block::from_node_block(block, time),
+                proofs_pruned,
+                block: Block::from_node_block(block, time, proofs_pruned),
             })
         }),
         |r| {
@@ -1,4 +1,7 @@
-use super::{blocks, element, health, height, merkle, payment_links, prove, stats, txn, State};
+use super::{
+    admin, blocks, element, health, height, merkle, network, payment_links, prove, stats, txn,
+    State,
+};
 use actix_web::web;
 
 pub fn configure_routes(state: State) -> Box<dyn FnOnce(&mut web::ServiceConfig)> {
@@ -19,6 +22,13 @@ pub fn configure_routes(state: State) -> Box<dyn FnOnce(&mut web::ServiceConfig)
                     .post(txn::submit_txn),
             )
             .service(web::resource("/stats").get(stats::get_stats))
+            .service(web::resource("/network").get(network::get_network))
+            .service(
+                web::resource("/admin/backups")
+                    .get(admin::list_backups)
+                    .post(admin::create_backup),
+            )
+            .service(web::resource("/admin/ip-filter").put(admin::set_ip_filter))
             // Proving endpoints for mobile wallet
             .service(web::resource("/prove/transfer").post(prove::prove_transfer))
             .service(web::resource("/prove/derive-address").post(prove::derive_address))
@@ -1,6 +1,5 @@
 use super::{error, State};
 use actix_web::web;
-use block_store::Block;
 use primitives::hash::CryptoHash;
 use rpc::error::HttpResult;
 use serde::{Deserialize, Serialize};
@@ -76,27 +75,28 @@ fn get_element_response(
         .get(element)
         .ok_or(crate::Error::ElementNotInTree { element })?;
 
-    let Some(block) = state.node.get_block(meta.inserted_in.into())? else {
-        return Err(crate::Error::BlockNotFound { block: meta.inserted_in.into() });
+    let Some(location) = state.node.get_txn_location_by_element(element)? else {
+        // This should never happen in practice
+        return Err(crate::Error::ElementNotInTxn { element, block_height: meta.inserted_in.into() });
     };
 
-    let block = block.into_block();
-    let root_hash = block.content.state.root_hash;
-    let txn = block
-        .content
-        .state
-        .txns
-        .iter()
-        .find(|txn| txn.leaves().contains(&element));
-    let Some(txn) = txn else {
-        // This should never happen in practice
-        return Err(crate::Error::ElementNotInTxn { element, block_height: block.block_height() });
+    // Recent roots are kept in the tree's history, so the block only has to be loaded for older
+    // heights
+    let root_hash = match notes_tree.root_hash_at(location.block_height.0) {
+        Some(root_hash) => root_hash,
+        None => {
+            let Some(block) = state.node.get_block(location.block_height)? else {
+                return Err(crate::Error::BlockNotFound { block: location.block_height });
+            };
+
+            block.into_block().content.state.root_hash
+        }
     };
-    let txn_hash = txn.hash();
+    let txn_hash = CryptoHash::new(location.txn_hash);
 
     Ok(ElementResponse {
         element,
-        height: meta.inserted_in,
+        height: location.block_height.0,
         root_hash,
         txn_hash,
     })
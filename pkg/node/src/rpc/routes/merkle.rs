@@ -1,18 +1,57 @@
 use super::{error, State};
+use crate::constants::MERKLE_TREE_DEPTH;
 use actix_web::web;
+use primitives::block_height::BlockHeight;
 use rpc::error::HttpResult;
 use serde::{Deserialize, Serialize};
+use smirk::CompressedPath;
 use std::str::FromStr;
 use zk_primitives::Element;
 
 #[derive(Debug, Deserialize)]
 pub struct MerklePathRequestQuery {
     commitments: String,
+    /// Generate paths against the tree at this (recent) block height, rather than the latest tree
+    height: Option<u64>,
+    /// How to encode the returned paths
+    #[serde(default)]
+    encoding: PathEncoding,
+}
+
+/// How merkle paths are encoded in responses
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathEncoding {
+    /// Every sibling of the path, deepest first
+    #[default]
+    Full,
+    /// Only the siblings that aren't empty subtree hashes, see [`CompressedPath`]
+    Compressed,
+}
+
+/// A merkle path, encoded as requested by [`PathEncoding`]
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum EncodedPath {
+    Full(Vec<Element>),
+    Compressed(CompressedPath<MERKLE_TREE_DEPTH>),
+}
+
+impl PathEncoding {
+    fn encode(self, siblings: Vec<Element>) -> EncodedPath {
+        match self {
+            Self::Full => EncodedPath::Full(siblings),
+            Self::Compressed => EncodedPath::Compressed(CompressedPath::compress(&siblings)),
+        }
+    }
 }
 
 #[derive(Serialize)]
 pub struct MerklePathResponse {
-    paths: Vec<Vec<Element>>,
+    paths: Vec<EncodedPath>,
+    /// The root hash the paths prove against, only set when a height was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    root_hash: Option<Element>,
 }
 
 #[tracing::instrument(err, skip_all)]
@@ -33,6 +72,68 @@ pub async fn get_merkle_paths(
         })
         .collect::<HttpResult<Vec<Element>>>()?;
 
-    let paths = state.node.get_merkle_paths(&commitments)?;
-    Ok(web::Json(MerklePathResponse { paths }))
+    let (paths, root_hash) = match query.0.height {
+        Some(height) => {
+            let (root_hash, paths) = state
+                .node
+                .get_merkle_paths_at(&commitments, BlockHeight(height))?;
+            (paths, Some(root_hash))
+        }
+        None => (state.node.get_merkle_paths(&commitments)?, None),
+    };
+
+    let paths = paths
+        .into_iter()
+        .map(|path| query.0.encoding.encode(path))
+        .collect();
+
+    Ok(web::Json(MerklePathResponse { paths, root_hash }))
+}
+
+#[cfg(test)]
+mod tests {
+    use smirk::{smirk, Tree};
+
+    use super::*;
+
+    #[test]
+    fn query_parses_encoding() {
+        let query = web::Query::<MerklePathRequestQuery>::from_query("commitments=1").unwrap();
+        assert_eq!(query.encoding, PathEncoding::Full);
+
+        let query =
+            web::Query::<MerklePathRequestQuery>::from_query("commitments=1&encoding=compressed")
+                .unwrap();
+        assert_eq!(query.encoding, PathEncoding::Compressed);
+
+        assert!(
+            web::Query::<MerklePathRequestQuery>::from_query("commitments=1&encoding=zip").is_err()
+        );
+    }
+
+    #[test]
+    fn compressed_paths_decompress_to_full_paths() {
+        let tree: Tree<MERKLE_TREE_DEPTH, ()> = smirk! { 1, 2, 4 };
+        let siblings = tree
+            .path_for(Element::ONE)
+            .siblings_deepest_first()
+            .to_vec();
+
+        let EncodedPath::Full(full) = PathEncoding::Full.encode(siblings.clone()) else {
+            panic!("expected a full path");
+        };
+        assert_eq!(full, siblings);
+
+        let EncodedPath::Compressed(compressed) = PathEncoding::Compressed.encode(siblings.clone())
+        else {
+            panic!("expected a compressed path");
+        };
+        assert_eq!(compressed.siblings.len(), 2);
+        assert_eq!(compressed.decompress().unwrap(), siblings);
+
+        // the bitmap is hex encoded, and the siblings are the non-empty ones
+        let json = serde_json::to_value(EncodedPath::Compressed(compressed.clone())).unwrap();
+        assert_eq!(json["bitmap"], hex::encode(&compressed.bitmap));
+        assert_eq!(json["siblings"].as_array().unwrap().len(), 2);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod admin;
 pub mod blocks;
 pub mod configure;
 pub mod element;
@@ -5,6 +6,7 @@ pub mod error;
 pub mod health;
 pub mod height;
 pub mod merkle;
+pub mod network;
 pub mod payment_links;
 pub mod prove;
 pub mod state;
@@ -0,0 +1,173 @@
+use super::{admin, State};
+use actix_web::{web, HttpRequest};
+use chrono::{DateTime, Utc};
+use rpc::error::HttpResult;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct PeerResp {
+    peer_id: String,
+    /// Only included for admin requests
+    address: Option<String>,
+    connected_secs: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    request_latency_ms: Option<u64>,
+    protocol_version: String,
+    reputation: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DialFailureResp {
+    peer_id: Option<String>,
+    error: String,
+    at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BanResp {
+    address: String,
+    reason: String,
+    at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NetworkResp {
+    peer_id: String,
+    peers: Vec<PeerResp>,
+    dial_failures: Vec<DialFailureResp>,
+    bans: Vec<BanResp>,
+}
+
+/// GET /network - returns connected peers (with address, uptime, traffic, latency and protocol
+/// version), recent dial failures and recently rejected connections
+///
+/// Peer addresses, dial failures and bans are only returned to requests with the admin token
+#[tracing::instrument(err, skip_all)]
+pub async fn get_network(
+    state: web::Data<State>,
+    req: HttpRequest,
+) -> HttpResult<web::Json<NetworkResp>> {
+    let info = state.node.network_info().await?;
+    let is_admin = admin::authorize(&state, &req).is_ok();
+
+    Ok(web::Json(network_response(info, is_admin)))
+}
+
+fn network_response(mut info: p2p2::NetworkInfo, is_admin: bool) -> NetworkResp {
+    if !is_admin {
+        info.dial_failures.clear();
+        info.bans.clear();
+    }
+
+    let peers = info
+        .peers
+        .into_iter()
+        .map(|peer| PeerResp {
+            peer_id: peer.peer_id.to_string(),
+            address: is_admin.then(|| peer.address.to_string()),
+            connected_secs: peer.connected_for.as_secs(),
+            bytes_in: peer.bytes_in,
+            bytes_out: peer.bytes_out,
+            request_latency_ms: peer
+                .request_latency
+                .map(|latency| latency.as_millis() as u64),
+            protocol_version: peer.protocol_version.protocol_name().to_string(),
+            reputation: peer.reputation,
+        })
+        .collect();
+
+    let dial_failures = info
+        .dial_failures
+        .into_iter()
+        .map(|failure| DialFailureResp {
+            peer_id: failure.peer_id.map(|peer_id| peer_id.to_string()),
+            error: failure.error,
+            at: failure.at.into(),
+        })
+        .collect();
+
+    let bans = info
+        .bans
+        .into_iter()
+        .map(|ban| BanResp {
+            address: ban.address.to_string(),
+            reason: ban.reason,
+            at: ban.at.into(),
+        })
+        .collect();
+
+    NetworkResp {
+        peer_id: info.local_peer_id.to_string(),
+        peers,
+        dial_failures,
+        bans,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use libp2p::PeerId;
+    use p2p2::{Ban, DialFailure, NetworkInfo, PeerInfo, ProtocolVersion};
+
+    use super::*;
+
+    fn info() -> NetworkInfo {
+        let address = "/ip4/1.2.3.4/tcp/5000".parse().unwrap();
+
+        NetworkInfo {
+            local_peer_id: PeerId::random(),
+            peers: vec![PeerInfo {
+                peer_id: PeerId::random(),
+                address,
+                connected_for: Duration::from_secs(5),
+                bytes_in: 10,
+                bytes_out: 20,
+                request_latency: Some(Duration::from_millis(15)),
+                protocol_version: ProtocolVersion::LATEST,
+                reputation: None,
+            }],
+            dial_failures: vec![DialFailure {
+                peer_id: None,
+                error: "connection refused".to_string(),
+                at: SystemTime::now(),
+            }],
+            bans: vec![Ban {
+                address: "/ip4/5.6.7.8/tcp/5000".parse().unwrap(),
+                reason: "not allowed".to_string(),
+                at: SystemTime::now(),
+            }],
+        }
+    }
+
+    #[test]
+    fn admin_gets_everything() {
+        let resp = network_response(info(), true);
+
+        assert_eq!(resp.peers.len(), 1);
+        assert_eq!(
+            resp.peers[0].address.as_deref(),
+            Some("/ip4/1.2.3.4/tcp/5000")
+        );
+        assert_eq!(resp.peers[0].connected_secs, 5);
+        assert_eq!(resp.peers[0].bytes_in, 10);
+        assert_eq!(resp.peers[0].bytes_out, 20);
+        assert_eq!(resp.peers[0].request_latency_ms, Some(15));
+        assert_eq!(resp.dial_failures.len(), 1);
+        assert_eq!(resp.bans.len(), 1);
+        assert_eq!(resp.bans[0].address, "/ip4/5.6.7.8/tcp/5000");
+    }
+
+    #[test]
+    fn non_admin_is_redacted() {
+        let resp = network_response(info(), false);
+
+        assert_eq!(resp.peers.len(), 1);
+        assert_eq!(resp.peers[0].address, None);
+        assert_eq!(resp.peers[0].bytes_in, 10);
+        assert!(resp.dial_failures.is_empty());
+        assert!(resp.bans.is_empty());
+    }
+}
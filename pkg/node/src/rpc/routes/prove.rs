@@ -1,7 +1,8 @@
-use super::State;
+use super::{merkle::PathEncoding, State};
 use actix_web::web;
 use rpc::error::{HTTPError, HttpResult};
 use serde::{Deserialize, Serialize};
+use smirk::CompressedPath;
 use zk_circuits::{
     constants::MERKLE_TREE_DEPTH,
     data::{InputNote, MerklePath, Note, SnarkWitness, Utxo, UtxoKind},
@@ -362,6 +363,9 @@ pub async fn faucet(
 pub struct MerklePathsRequest {
     /// List of note commitments (hex strings)
     pub commitments: Vec<String>,
+    /// How to encode the returned paths
+    #[serde(default)]
+    pub encoding: PathEncoding,
 }
 
 #[derive(Debug, Serialize)]
@@ -375,10 +379,28 @@ pub struct MerklePathsResponse {
 #[derive(Debug, Serialize)]
 pub struct MerklePathInfo {
     pub commitment: String,
-    pub path: Vec<String>,
+    pub path: MerklePathEncoding,
     pub found: bool,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum MerklePathEncoding {
+    /// Every sibling (hex strings)
+    Full(Vec<String>),
+    /// Only the siblings that aren't empty subtree hashes
+    Compressed(CompressedPath<MERKLE_TREE_DEPTH>),
+}
+
+impl MerklePathEncoding {
+    fn new(encoding: PathEncoding, siblings: Vec<Element>) -> Self {
+        match encoding {
+            PathEncoding::Full => Self::Full(siblings.into_iter().map(element_to_hex).collect()),
+            PathEncoding::Compressed => Self::Compressed(CompressedPath::compress(&siblings)),
+        }
+    }
+}
+
 #[tracing::instrument(err, skip_all)]
 pub async fn get_merkle_paths_for_notes(
     state: web::Data<State>,
@@ -388,38 +410,88 @@ pub async fn get_merkle_paths_for_notes(
 
     // Get current root
     let root = state.node.root_hash();
-    
-    let mut paths = Vec::new();
-    
-    for commitment_hex in &req.commitments {
-        let commitment = hex_to_element(commitment_hex)?;
-        
-        // Try to get merkle path for this commitment
-        match state.node.get_merkle_paths(&[commitment]) {
-            Ok(merkle_paths) if !merkle_paths.is_empty() && !merkle_paths[0].is_empty() => {
-                let path: Vec<String> = merkle_paths[0]
-                    .iter()
-                    .map(|e| element_to_hex(*e))
-                    .collect();
-                
-                paths.push(MerklePathInfo {
-                    commitment: commitment_hex.clone(),
-                    path,
-                    found: true,
-                });
-            }
-            _ => {
-                paths.push(MerklePathInfo {
-                    commitment: commitment_hex.clone(),
-                    path: vec![],
-                    found: false,
-                });
-            }
-        }
-    }
+
+    let commitments = req
+        .commitments
+        .iter()
+        .map(|commitment_hex| hex_to_element(commitment_hex))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Get all the merkle paths in one batch
+    let paths = state
+        .node
+        .find_merkle_paths(&commitments)
+        .into_iter()
+        .zip(&req.commitments)
+        .map(|(path, commitment_hex)| match path {
+            Some(path) => MerklePathInfo {
+                commitment: commitment_hex.clone(),
+                path: MerklePathEncoding::new(req.encoding, path),
+                found: true,
+            },
+            None => MerklePathInfo {
+                commitment: commitment_hex.clone(),
+                path: MerklePathEncoding::Full(vec![]),
+                found: false,
+            },
+        })
+        .collect();
 
     Ok(web::Json(MerklePathsResponse {
         root: element_to_hex(root),
         paths,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use smirk::{smirk, Tree};
+
+    use super::*;
+
+    fn siblings() -> Vec<Element> {
+        let tree: Tree<MERKLE_TREE_DEPTH, ()> = smirk! { 1, 2, 4 };
+        tree.path_for(Element::ONE)
+            .siblings_deepest_first()
+            .to_vec()
+    }
+
+    #[test]
+    fn request_defaults_to_full_encoding() {
+        let req: MerklePathsRequest = serde_json::from_str(r#"{ "commitments": [] }"#).unwrap();
+        assert_eq!(req.encoding, PathEncoding::Full);
+
+        let req: MerklePathsRequest =
+            serde_json::from_str(r#"{ "commitments": [], "encoding": "compressed" }"#).unwrap();
+        assert_eq!(req.encoding, PathEncoding::Compressed);
+    }
+
+    #[test]
+    fn encodes_full_and_compressed_paths() {
+        let siblings = siblings();
+
+        let MerklePathEncoding::Full(full) =
+            MerklePathEncoding::new(PathEncoding::Full, siblings.clone())
+        else {
+            panic!("expected a full path");
+        };
+        assert_eq!(
+            full,
+            siblings
+                .iter()
+                .copied()
+                .map(element_to_hex)
+                .collect::<Vec<_>>()
+        );
+
+        let MerklePathEncoding::Compressed(compressed) =
+            MerklePathEncoding::new(PathEncoding::Compressed, siblings.clone())
+        else {
+            panic!("expected a compressed path");
+        };
+        assert_eq!(compressed.siblings.len(), 2);
+
+        let path = MerklePath::<MERKLE_TREE_DEPTH>::try_from(&compressed).unwrap();
+        assert_eq!(path.siblings, siblings);
+    }
+}
@@ -1,7 +1,11 @@
 use std::{str::FromStr, sync::Arc};
 
 use super::State;
-use crate::{node, utxo::UtxoProof, BlockFormat};
+use crate::{
+    node::{self, prune::is_pruned},
+    utxo::UtxoProof,
+    BlockFormat,
+};
 use actix_web::web;
 use base64::Engine;
 use block_store::BlockListOrder;
@@ -67,6 +71,8 @@ pub(crate) struct TxnWithInfo {
     pub(crate) hash: CryptoHash,
     pub(crate) block_height: BlockHeight,
     pub(crate) time: u64,
+    /// The proof has been pruned, so it is empty
+    pub(crate) proof_pruned: bool,
 }
 
 #[derive(Serialize)]
@@ -126,8 +132,9 @@ pub async fn list_txns(
         };
 
     let max_height = state.node.max_height();
+    let pruned_height = state.node.pruned_height()?;
 
-    let (cursor, transactions) = list_txns_inner(block_fetcher, &query, max_height)?;
+    let (cursor, transactions) = list_txns_inner(block_fetcher, &query, max_height, pruned_height)?;
 
     let (cursor, transactions) = if transactions.is_empty() && query.poll {
         let towards_newer_height = match (&query.order, query.cursor.as_deref()) {
@@ -166,6 +173,7 @@ pub async fn list_txns(
                             block_fetcher,
                             &query,
                             max_height,
+                            pruned_height,
                         )?
                     }
                 }
@@ -190,6 +198,7 @@ fn list_txns_inner<I: Iterator<Item = Result<BlockFormat, node::Error>>>(
     ) -> Result<I, node::Error>,
     query: &ListTxnsQuery,
     max_height: BlockHeight,
+    pruned_height: Option<BlockHeight>,
 ) -> Result<(Cursor<ListTxnsPosition>, Vec<TxnWithInfo>), HTTPError> {
     let txn_limit = query.limit.unwrap_or(10).min(100);
 
@@ -210,6 +219,7 @@ fn list_txns_inner<I: Iterator<Item = Result<BlockFormat, node::Error>>>(
                     node::BlockFormat::V1(_) => unreachable!("already upgraded"),
                     node::BlockFormat::V2(block, metadata) => (block, metadata),
                 };
+                let proof_pruned = is_pruned(block.content.header.height, pruned_height);
 
                 block
                     .content
@@ -228,6 +238,7 @@ fn list_txns_inner<I: Iterator<Item = Result<BlockFormat, node::Error>>>(
                                 max_height,
                             ),
                         ),
+                        proof_pruned,
                     })
             })
         })
@@ -300,6 +311,8 @@ pub async fn get_txn(
         .get_txn(txn_hash.into_inner())?
         .ok_or(crate::Error::TxnNotFound { txn: txn_hash })?;
 
+    state.node.check_not_pruned(metadata.block_height)?;
+
     let time = metadata.block_time.unwrap_or_else(|| {
         node::NodeShared::estimate_block_time(metadata.block_height, state.node.max_height())
     });
@@ -311,6 +324,7 @@ pub async fn get_txn(
             hash: txn_hash,
             block_height: metadata.block_height,
             time,
+            proof_pruned: false,
         },
     }))
 }
@@ -325,9 +339,7 @@ mod tests {
 
     #[test]
     fn list_txns_pagination() {
-        let tempdir = tempdir::TempDir::new("list_txns").unwrap();
-
-        let store = block_store::BlockStore::<BlockFormat>::create_or_load(tempdir.path()).unwrap();
+        let store = block_store::BlockStore::<BlockFormat, block_store::MemoryBackend>::in_memory();
 
         let new_block = |height: u64, txns: Vec<UtxoProof>| {
             let mut block = Block::default();
@@ -373,6 +385,7 @@ mod tests {
                 poll: false,
             },
             max_height,
+            None,
         )
         .unwrap();
         assert_eq!(txns.len(), 4);
@@ -388,6 +401,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 1);
@@ -402,6 +416,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 1);
@@ -417,6 +432,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 1);
@@ -432,6 +448,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 1);
@@ -447,6 +464,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 0);
@@ -465,6 +483,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 1);
@@ -480,6 +499,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 1);
@@ -495,6 +515,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 1);
@@ -510,6 +531,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 1);
@@ -525,6 +547,7 @@ mod tests {
                     poll: false,
                 },
                 max_height,
+                None,
             )
             .unwrap();
             assert_eq!(txns.len(), 0);
@@ -21,6 +21,7 @@ use crate::{
         NetworkEvent, SnapshotAccept, SnapshotChunk, SnapshotChunkFast, SnapshotChunkSlow,
         SnapshotKind, SnapshotRequest,
     },
+    node::prune::is_pruned,
     types::{BlockHeight, SnapshotId},
     NodeShared,
 };
@@ -438,13 +439,24 @@ pub(crate) async fn handle_snapshot_request(
     snapshot_id: SnapshotId,
     from_height: BlockHeight,
     _to_height: BlockHeight,
-    _kind: SnapshotKind,
+    kind: SnapshotKind,
 ) -> Result<(), Error> {
     if node.is_out_of_sync() || from_height > node.height() {
         info!("Ignoring snapshot request, we're too far behind");
         return Ok(());
     }
 
+    // Slow snapshots send blocks, which the peer can't validate once they're pruned, so another
+    // peer has to offer them
+    let pruned_height = node.pruned_height().map_err(Box::new)?;
+    if matches!(kind, SnapshotKind::Slow) && is_pruned(from_height, pruned_height) {
+        info!(
+            ?from_height,
+            "Ignoring slow snapshot request, the blocks are pruned"
+        );
+        return Ok(());
+    }
+
     info!(?snapshot_id, "Sending snapshot offer");
 
     let offer = crate::network::SnapshotOffer { snapshot_id };
@@ -493,6 +505,9 @@ async fn send_snapshot_chunk_slow(
 ) -> Result<(), Error> {
     let to_height = std::cmp::min(to_height, node.height() + BlockHeight(1));
 
+    // Pruned blocks can't be validated by the peer
+    node.check_not_pruned(from_height).map_err(Box::new)?;
+
     let mut blocks = node
         .fetch_blocks(from_height..to_height, BlockListOrder::LowestToHighest)
         .into_iterator()
@@ -1,16 +1,18 @@
-use super::protocol::PolyProtocol;
+use super::protocol::{PolyProtocol, VersionedEvent};
 use borsh::{BorshDeserialize, BorshSerialize};
 use libp2p::{
-    request_response,
-    swarm::{behaviour::toggle::Toggle, keep_alive, NetworkBehaviour},
+    identify, request_response,
+    swarm::{keep_alive, NetworkBehaviour},
 };
 
 #[derive(NetworkBehaviour)]
 pub struct Behaviour<NetworkEvent>
 where
-    NetworkEvent: Clone + Sync + Send + BorshSerialize + BorshDeserialize + 'static,
+    NetworkEvent:
+        VersionedEvent + Clone + Sync + Send + BorshSerialize + BorshDeserialize + 'static,
 {
     pub rr: request_response::Behaviour<PolyProtocol<NetworkEvent>>,
     pub keep_alive: keep_alive::Behaviour,
-    pub whitelist: Toggle<whitelist_ips::Behaviour>,
+    pub identify: identify::Behaviour,
+    pub whitelist: whitelist_ips::Behaviour,
 }
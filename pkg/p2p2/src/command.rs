@@ -2,6 +2,8 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use libp2p::{Multiaddr, PeerId};
 use tokio::sync::oneshot;
 
+use crate::{info::NetworkInfo, trace::TraceContext};
+
 /// A command that can be sent to a running P2P node
 #[derive(Debug)]
 pub enum Command<NetworkEvent>
@@ -16,5 +18,16 @@ where
 
     /// Send a message to another peer, Sender will respond when response
     /// received
-    Send(PeerId, NetworkEvent, oneshot::Sender<()>),
+    Send(
+        PeerId,
+        NetworkEvent,
+        Option<TraceContext>,
+        oneshot::Sender<()>,
+    ),
+
+    /// Replace the IP whitelist and banlist, closing connections that are no longer allowed
+    SetIpFilter(whitelist_ips::Config, oneshot::Sender<()>),
+
+    /// Get a snapshot of connected peers, recent dial failures and bans
+    Info(oneshot::Sender<NetworkInfo>),
 }
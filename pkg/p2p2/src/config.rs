@@ -1,4 +1,4 @@
-use std::{path::Path, sync::OnceLock, collections::HashSet, net::IpAddr};
+use std::{path::Path, sync::OnceLock, collections::HashSet};
 
 use figment::{
     providers::{Env, Format, Toml},
@@ -6,6 +6,9 @@ use figment::{
 };
 use libp2p::Multiaddr;
 use serde::{Deserialize, Deserializer};
+use whitelist_ips::IpNet;
+
+use crate::ProtocolVersion;
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[non_exhaustive]
@@ -13,9 +16,15 @@ use serde::{Deserialize, Deserializer};
 /// Config to configure the P2P node
 pub struct Config {
     /// The multiaddr to listen on
+    ///
+    /// Both TCP (`/ip4/0.0.0.0/tcp/5000`) and QUIC (`/ip4/0.0.0.0/udp/5000/quic-v1`) addresses
+    /// are supported. When listening on QUIC, the node also listens on TCP with the same port, so
+    /// peers that can't use QUIC can fall back to it
     pub laddr: Multiaddr,
 
     /// A list of other multiaddrs to dial when calling `spawn`,
+    ///
+    /// If dialing a QUIC address fails, the equivalent TCP address is dialed instead
     #[serde(deserialize_with = "deserialize_multiaddr")]
     pub dial: Vec<Multiaddr>,
 
@@ -24,13 +33,28 @@ pub struct Config {
     /// 0 means "no timeout" (i.e. `u64::MAX` seconds)
     pub idle_timeout_secs: u64,
 
-    /// Explicitly whitelisted IP addresses
+    /// Explicitly whitelisted IP addresses or CIDR ranges (e.g. `10.0.0.0/8`)
     ///
-    /// Any IP address that isn't in this set will be banned (connections will be immediately
-    /// rejected)
+    /// Any IP address that isn't in one of these ranges will be banned (connections will be
+    /// immediately rejected)
     ///
     /// If empty, whitelisting is disabled (i.e. all IPs are allowed)
-    pub whitelisted_ips: HashSet<IpAddr>,
+    pub whitelisted_ips: HashSet<IpNet>,
+
+    /// Banned IP addresses or CIDR ranges
+    ///
+    /// Connections from these ranges are rejected, even if they are also whitelisted
+    #[serde(default)]
+    pub banned_ips: HashSet<IpNet>,
+
+    /// The protocol versions to advertise to peers
+    ///
+    /// Peers negotiate the highest version they both advertise, so during a rolling upgrade nodes
+    /// should keep advertising the previous version until every node has been upgraded
+    ///
+    /// Defaults to all supported versions
+    #[serde(default = "default_protocol_versions")]
+    pub protocol_versions: Vec<ProtocolVersion>,
 }
 
 impl Default for Config {
@@ -66,6 +90,18 @@ impl Config {
             .join(Toml::string(Self::DEFAULT_STR))
             .extract()
     }
+
+    /// The IP filter described by this config
+    pub fn ip_filter(&self) -> whitelist_ips::Config {
+        let mut ip_filter = whitelist_ips::Config::default();
+        ip_filter.whitelisted_ips = self.whitelisted_ips.clone();
+        ip_filter.banned_ips = self.banned_ips.clone();
+        ip_filter
+    }
+}
+
+fn default_protocol_versions() -> Vec<ProtocolVersion> {
+    ProtocolVersion::ALL.to_vec()
 }
 
 fn deserialize_multiaddr<'de, D>(deserializer: D) -> Result<Vec<Multiaddr>, D::Error>
@@ -8,6 +8,9 @@ pub enum Error {
     #[error("Tansport error: {0}")]
     Transport(#[from] libp2p::TransportError<std::io::Error>),
 
+    #[error("No protocol versions configured")]
+    NoProtocolVersions,
+
     #[error("Channel error")]
     ChannelError(String),
 }
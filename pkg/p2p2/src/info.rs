@@ -0,0 +1,243 @@
+use libp2p::{Multiaddr, PeerId};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::ProtocolVersion;
+
+/// The number of dial failures and bans to remember
+const MAX_RECENT_EVENTS: usize = 100;
+
+/// A snapshot of the state of the network, returned by [`Network::info`][crate::Network::info]
+#[derive(Debug, Clone)]
+pub struct NetworkInfo {
+    pub local_peer_id: PeerId,
+    pub peers: Vec<PeerInfo>,
+    /// The most recent failed dials, oldest first
+    pub dial_failures: Vec<DialFailure>,
+    /// The most recent rejected connections, oldest first
+    pub bans: Vec<Ban>,
+}
+
+/// A connected peer
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    pub peer_id: PeerId,
+    /// The remote address of the most recently established connection to this peer
+    pub address: Multiaddr,
+    /// How long we have been connected to this peer
+    pub connected_for: Duration,
+    /// The number of event bytes received from this peer
+    pub bytes_in: u64,
+    /// The number of event bytes sent to this peer
+    pub bytes_out: u64,
+    /// The mean time between sending a request to this peer and receiving its response
+    ///
+    /// `None` if no responses have been received yet
+    pub request_latency: Option<Duration>,
+    /// The protocol version negotiated with this peer
+    pub protocol_version: ProtocolVersion,
+    /// The reputation of this peer
+    ///
+    /// Peers aren't scored yet, so this is always `None`
+    pub reputation: Option<i64>,
+}
+
+/// An outgoing connection that couldn't be established
+#[derive(Debug, Clone)]
+pub struct DialFailure {
+    pub peer_id: Option<PeerId>,
+    pub error: String,
+    pub at: SystemTime,
+}
+
+/// An incoming connection that was rejected
+#[derive(Debug, Clone)]
+pub struct Ban {
+    pub address: Multiaddr,
+    pub reason: String,
+    pub at: SystemTime,
+}
+
+/// Tracks per-peer statistics inside the network event loop
+#[derive(Debug, Default)]
+pub(crate) struct NetworkStats {
+    peers: HashMap<PeerId, PeerStats>,
+    dial_failures: VecDeque<DialFailure>,
+    bans: VecDeque<Ban>,
+}
+
+#[derive(Debug)]
+struct PeerStats {
+    address: Multiaddr,
+    connected_at: Instant,
+    bytes_in: u64,
+    bytes_out: u64,
+    total_latency: Duration,
+    responses: u32,
+}
+
+impl NetworkStats {
+    pub(crate) fn connection_established(&mut self, peer_id: PeerId, address: Multiaddr) {
+        self.peers
+            .entry(peer_id)
+            .and_modify(|stats| stats.address = address.clone())
+            .or_insert_with(|| PeerStats {
+                address,
+                connected_at: Instant::now(),
+                bytes_in: 0,
+                bytes_out: 0,
+                total_latency: Duration::ZERO,
+                responses: 0,
+            });
+    }
+
+    pub(crate) fn connection_closed(&mut self, peer_id: &PeerId, num_established: u32) {
+        if num_established == 0 {
+            self.peers.remove(peer_id);
+        }
+    }
+
+    pub(crate) fn bytes_in(&mut self, peer_id: &PeerId, bytes: usize) {
+        if let Some(stats) = self.peers.get_mut(peer_id) {
+            stats.bytes_in += bytes as u64;
+        }
+    }
+
+    pub(crate) fn bytes_out(&mut self, peer_id: &PeerId, bytes: usize) {
+        if let Some(stats) = self.peers.get_mut(peer_id) {
+            stats.bytes_out += bytes as u64;
+        }
+    }
+
+    pub(crate) fn response_received(&mut self, peer_id: &PeerId, latency: Duration) {
+        if let Some(stats) = self.peers.get_mut(peer_id) {
+            stats.total_latency += latency;
+            stats.responses += 1;
+        }
+    }
+
+    pub(crate) fn dial_failed(&mut self, peer_id: Option<PeerId>, error: String) {
+        push_bounded(
+            &mut self.dial_failures,
+            DialFailure {
+                peer_id,
+                error,
+                at: SystemTime::now(),
+            },
+        );
+    }
+
+    pub(crate) fn banned(&mut self, address: Multiaddr, reason: String) {
+        push_bounded(
+            &mut self.bans,
+            Ban {
+                address,
+                reason,
+                at: SystemTime::now(),
+            },
+        );
+    }
+
+    pub(crate) fn info(
+        &self,
+        local_peer_id: PeerId,
+        protocol_version: impl Fn(&PeerId) -> ProtocolVersion,
+    ) -> NetworkInfo {
+        let peers = self
+            .peers
+            .iter()
+            .map(|(peer_id, stats)| PeerInfo {
+                peer_id: *peer_id,
+                address: stats.address.clone(),
+                connected_for: stats.connected_at.elapsed(),
+                bytes_in: stats.bytes_in,
+                bytes_out: stats.bytes_out,
+                request_latency: (stats.responses > 0)
+                    .then(|| stats.total_latency / stats.responses),
+                protocol_version: protocol_version(peer_id),
+                reputation: None,
+            })
+            .collect();
+
+        NetworkInfo {
+            local_peer_id,
+            peers,
+            dial_failures: self.dial_failures.iter().cloned().collect(),
+            bans: self.bans.iter().cloned().collect(),
+        }
+    }
+}
+
+fn push_bounded<T>(queue: &mut VecDeque<T>, item: T) {
+    if queue.len() == MAX_RECENT_EVENTS {
+        queue.pop_front();
+    }
+
+    queue.push_back(item);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr() -> Multiaddr {
+        "/ip4/1.2.3.4/tcp/5000".parse().unwrap()
+    }
+
+    #[test]
+    fn counts_bytes_and_latency() {
+        let mut stats = NetworkStats::default();
+        let peer = PeerId::random();
+        let unknown_peer = PeerId::random();
+
+        stats.connection_established(peer, addr());
+        stats.bytes_in(&peer, 10);
+        stats.bytes_in(&peer, 5);
+        stats.bytes_out(&peer, 7);
+        stats.bytes_out(&unknown_peer, 100);
+
+        let info = stats.info(PeerId::random(), |_| ProtocolVersion::LATEST);
+        assert_eq!(info.peers.len(), 1);
+        assert_eq!(info.peers[0].bytes_in, 15);
+        assert_eq!(info.peers[0].bytes_out, 7);
+        assert_eq!(info.peers[0].request_latency, None);
+        assert_eq!(info.peers[0].protocol_version, ProtocolVersion::LATEST);
+
+        stats.response_received(&peer, Duration::from_millis(10));
+        stats.response_received(&peer, Duration::from_millis(30));
+
+        let info = stats.info(PeerId::random(), |_| ProtocolVersion::LATEST);
+        assert_eq!(
+            info.peers[0].request_latency,
+            Some(Duration::from_millis(20))
+        );
+
+        // stats are kept until the last connection to the peer is closed
+        stats.connection_closed(&peer, 1);
+        assert_eq!(stats.peers.len(), 1);
+        stats.connection_closed(&peer, 0);
+        assert!(stats.peers.is_empty());
+    }
+
+    #[test]
+    fn keeps_most_recent_events() {
+        let mut stats = NetworkStats::default();
+
+        for i in 0..MAX_RECENT_EVENTS + 10 {
+            stats.dial_failed(None, i.to_string());
+            stats.banned(addr(), i.to_string());
+        }
+
+        let info = stats.info(PeerId::random(), |_| ProtocolVersion::LATEST);
+        assert_eq!(info.dial_failures.len(), MAX_RECENT_EVENTS);
+        assert_eq!(info.bans.len(), MAX_RECENT_EVENTS);
+        assert_eq!(info.dial_failures[0].error, "10");
+        assert_eq!(
+            info.dial_failures.last().unwrap().error,
+            (MAX_RECENT_EVENTS + 9).to_string()
+        );
+        assert_eq!(info.bans[0].reason, "10");
+    }
+}
@@ -5,10 +5,16 @@ mod behaviour;
 mod command;
 mod config;
 mod error;
+mod info;
 mod network;
 mod protocol;
+mod trace;
 mod transport;
 
 pub use config::Config;
 pub use error::{Error, Result};
+pub use info::{Ban, DialFailure, NetworkInfo, PeerInfo};
 pub use network::Network;
+pub use protocol::{ProtocolVersion, VersionedEvent};
+pub use trace::TraceContext;
+pub use whitelist_ips::{Config as IpFilter, IpNet};
@@ -2,33 +2,41 @@ use crate::{
     behaviour::{Behaviour, BehaviourEvent},
     command::Command,
     error::Result,
-    protocol::{PolyProtocol, Request, Response},
-    transport::create_transport,
+    info::{NetworkInfo, NetworkStats},
+    protocol::{PolyProtocol, ProtocolVersion, Request, Response, SizedRequest, VersionedEvent},
+    trace::TraceContext,
+    transport::{create_transport, tcp_fallback, tcp_fallback_listener},
     Error,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use futures_util::StreamExt;
 use libp2p::{
+    identify,
     identity::Keypair,
     request_response,
-    swarm::{keep_alive, SwarmBuilder, SwarmEvent},
+    swarm::{keep_alive, DialError, ListenError, SwarmBuilder, SwarmEvent},
     Multiaddr, PeerId,
 };
 use parking_lot::Mutex;
 use std::{
     collections::{HashMap, HashSet},
     fmt::Debug,
-    marker::PhantomData,
 };
-use std::{net::IpAddr, sync::Arc};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
 use tokio::{select, sync::mpsc, sync::oneshot, sync::Mutex as AsyncMutex};
 use tracing::{debug, error, info};
 
+/// The application protocol family reported to peers through `identify`
+const IDENTIFY_PROTOCOL_VERSION: &str = "/polybase";
+
 pub struct Network<NetworkEvent>
 where
     NetworkEvent: Debug + Clone + Send + BorshSerialize + BorshDeserialize + 'static,
 {
-    netin_rx: AsyncMutex<mpsc::UnboundedReceiver<(PeerId, NetworkEvent)>>,
+    netin_rx: AsyncMutex<mpsc::UnboundedReceiver<(PeerId, NetworkEvent, Option<TraceContext>)>>,
     netout_tx: mpsc::UnboundedSender<Command<NetworkEvent>>,
     local_peer_id: PeerId,
     shared: Arc<NetworkShared>,
@@ -36,49 +44,67 @@ where
 
 impl<NetworkEvent> Network<NetworkEvent>
 where
-    NetworkEvent: Debug + Clone + Sync + Send + BorshSerialize + BorshDeserialize + 'static,
+    NetworkEvent:
+        VersionedEvent + Debug + Clone + Sync + Send + BorshSerialize + BorshDeserialize + 'static,
 {
     pub fn new(
         keypair: &Keypair,
         listenaddrs: impl Iterator<Item = Multiaddr>,
         dialaddrs: impl Iterator<Item = Multiaddr>,
-        whitelisted_ips: HashSet<IpAddr>,
+        ip_filter: whitelist_ips::Config,
+        protocol_versions: Vec<ProtocolVersion>,
     ) -> Result<Network<NetworkEvent>> {
         let local_peer_id = PeerId::from(keypair.public());
         let transport = create_transport(keypair);
-        let protocols = vec![(
-            PolyProtocol(PhantomData),
-            request_response::ProtocolSupport::Full,
-        )];
+
+        // Protocols are offered in order when opening a substream, so list the newest first to
+        // make peers settle on the highest version they have in common
+        let mut protocol_versions = protocol_versions;
+        protocol_versions.sort_unstable_by(|a, b| b.cmp(a));
+        protocol_versions.dedup();
+        let Some(&newest_version) = protocol_versions.first() else {
+            return Err(Error::NoProtocolVersions);
+        };
+
+        let protocols = protocol_versions
+            .iter()
+            .map(|version| {
+                (
+                    PolyProtocol::new(*version),
+                    request_response::ProtocolSupport::Full,
+                )
+            })
+            .collect::<Vec<_>>();
         let rr_config = request_response::Config::default();
         let mut swarm = {
-            let whitelist = {
-                if whitelisted_ips.is_empty() {
-                    None
-                } else {
-                    let mut whitelist_config = whitelist_ips::Config::default();
-                    whitelist_config.whitelisted_ips = whitelisted_ips;
-                    let whitelist_ips = whitelist_ips::Behaviour::new_with_config(whitelist_config);
-                    Some(whitelist_ips)
-                }
-            }
-            .into();
-
             let behaviour = Behaviour {
                 rr: request_response::Behaviour::new(
-                    PolyProtocol(PhantomData),
+                    PolyProtocol::new(newest_version),
                     protocols,
                     rr_config,
                 ),
                 keep_alive: keep_alive::Behaviour,
-                whitelist,
+                identify: identify::Behaviour::new(identify::Config::new(
+                    IDENTIFY_PROTOCOL_VERSION.to_string(),
+                    keypair.public(),
+                )),
+                whitelist: whitelist_ips::Behaviour::new_with_config(ip_filter),
             };
             SwarmBuilder::with_tokio_executor(transport, behaviour, local_peer_id).build()
         };
 
         // Listen on given addresses
+        //
+        // QUIC listeners also get a TCP listener, so peers that can't reach us over UDP can fall
+        // back to it. The TCP listener is only started once the QUIC listener is bound, so that it
+        // uses the same port even if the QUIC address asked for a random one (port 0).
+        let mut pending_fallbacks = HashMap::new();
         for addr in listenaddrs {
-            swarm.listen_on(addr)?;
+            let listener_id = swarm.listen_on(addr.clone())?;
+
+            if tcp_fallback(&addr).is_some() {
+                pending_fallbacks.insert(listener_id, addr);
+            }
         }
 
         // Connect to peers
@@ -88,7 +114,8 @@ where
         }
 
         // Channel to receive NetworkEvents from the network
-        let (netin_tx, netin_rx) = mpsc::unbounded_channel::<(PeerId, NetworkEvent)>();
+        let (netin_tx, netin_rx) =
+            mpsc::unbounded_channel::<(PeerId, NetworkEvent, Option<TraceContext>)>();
         let (netout_tx, mut netout_rx) = mpsc::unbounded_channel::<Command<NetworkEvent>>();
 
         // Shared state between the network and the spawned network behaviour event loop
@@ -98,44 +125,83 @@ where
         tokio::spawn(async move {
             let shared = shared_clone;
             let mut requests = HashMap::new();
+            let mut stats = NetworkStats::default();
 
             // TODO: add cancel loop
             loop {
                 select! {
                     Some(cmd) = netout_rx.recv() => {
                         match cmd {
-                            Command::Send(peer_id, event, response) => {
-                                let request_id = swarm.behaviour_mut().rr.send_request(&peer_id, Request::V1 ( event ));
-                                requests.insert(request_id, response);
+                            Command::Send(peer_id, event, trace, response) => {
+                                // The wire version is picked by the codec once the protocol has been negotiated
+                                let request = SizedRequest::new(Request::for_protocol(event, trace, ProtocolVersion::LATEST));
+                                let wire_len = Arc::clone(&request.wire_len);
+                                let request_id = swarm.behaviour_mut().rr.send_request(&peer_id, request);
+                                requests.insert(request_id, (response, Instant::now(), wire_len));
                             }
                             Command::Dial(peer_id, response) => {
                                 response.send(swarm.dial(peer_id)).ok();
                             }
+                            Command::SetIpFilter(ip_filter, response) => {
+                                info!(whitelisted_ips = ?ip_filter.whitelisted_ips, banned_ips = ?ip_filter.banned_ips, "Updating IP filter");
+                                swarm.behaviour_mut().whitelist.set_config(ip_filter);
+                                response.send(()).ok();
+                            }
+                            Command::Info(response) => {
+                                response.send(stats.info(local_peer_id, |peer_id| shared.peer_version(peer_id))).ok();
+                            }
                         }
                     }
                     event = swarm.select_next_some() => match event {
-                        SwarmEvent::NewListenAddr { address, .. } => {
+                        SwarmEvent::NewListenAddr { listener_id, address } => {
                             info!(addr = ?address, "Listening on");
+
+                            if let Some(requested) = pending_fallbacks.remove(&listener_id) {
+                                if let Some(fallback) = tcp_fallback_listener(&requested, &address) {
+                                    info!(addr = ?fallback, "Listening on TCP fallback");
+                                    if let Err(err) = swarm.listen_on(fallback) {
+                                        error!(err = ?err, "Failed to listen on TCP fallback");
+                                    }
+                                }
+                            }
                         }
                         SwarmEvent::Dialing(peer_id) => {
                             info!(peer_id = ?peer_id, "Dialing peer");
                         }
-                        SwarmEvent::ConnectionEstablished { peer_id, established_in, .. } => {
+                        SwarmEvent::ConnectionEstablished { peer_id, endpoint, established_in, .. } => {
                             info!(peer_id = ?peer_id, established_in = ?established_in, "Connection established");
                             shared.add_peer(peer_id);
+                            stats.connection_established(peer_id, endpoint.get_remote_address().clone());
                         }
                         SwarmEvent::ConnectionClosed { peer_id, endpoint, num_established, cause } => {
                             info!(peer_id = ?peer_id, num_established = num_established, endpoint = ?endpoint, cause = ?cause, "Connection closed");
                             shared.remove_peer(&peer_id);
+                            stats.connection_closed(&peer_id, num_established);
                         }
                         SwarmEvent::IncomingConnection { local_addr, send_back_addr } => {
                             info!(local_addr = ?local_addr, send_back_addr = ?send_back_addr, "Incoming connection");
                         }
                         SwarmEvent::IncomingConnectionError { local_addr, send_back_addr, error } => {
                             error!(local_addr = ?local_addr, send_back_addr = ?send_back_addr, err = ?error, "Incoming connection error");
+
+                            if let ListenError::Denied { cause } = &error {
+                                stats.banned(send_back_addr, cause.to_string());
+                            }
                         }
                         SwarmEvent::OutgoingConnectionError { peer_id, error } => {
                             error!(peer_id = ?peer_id, err = ?error, "Outgoing connection error");
+                            stats.dial_failed(peer_id, error.to_string());
+
+                            // Retry failed QUIC dials over TCP
+                            if let DialError::Transport(errors) = &error {
+                                for (addr, _) in errors {
+                                    let Some(fallback) = tcp_fallback(addr) else { continue };
+                                    info!(addr = ?addr, fallback = ?fallback, "QUIC dial failed, falling back to TCP");
+                                    if let Err(err) = swarm.dial(fallback) {
+                                        error!(err = ?err, "Failed to dial TCP fallback");
+                                    }
+                                }
+                            }
                         }
                         SwarmEvent::ListenerClosed { listener_id, addresses, reason } => {
                             error!(listener_id = ?listener_id, addresses = ?addresses, reason = ?reason, "Listener closed");
@@ -147,12 +213,16 @@ where
                             match message {
                                 request_response::Message::Response{ request_id, .. } => {
                                     // Notify sender that request/response process is complete
-                                    if let Some(tx) = requests.remove(&request_id) {
+                                    if let Some((tx, sent_at, wire_len)) = requests.remove(&request_id) {
+                                        stats.bytes_out(&peer, wire_len.load(Ordering::Relaxed));
+                                        stats.response_received(&peer, sent_at.elapsed());
                                         tx.send(()).ok();
                                     }
                                 },
-                                request_response::Message::Request{ request: Request::V1(request), channel, .. } => {
-                                        match netin_tx.send((peer, request)) {
+                                request_response::Message::Request{ request, channel, .. } => {
+                                        stats.bytes_in(&peer, request.wire_len());
+                                        let (request, trace) = request.request.into_parts();
+                                        match netin_tx.send((peer, request, trace)) {
                                             Ok(_) => {},
                                             Err(err) => {
                                                 error!(?err, peer_id = ?peer, "Failed to send, dropping event");
@@ -167,7 +237,32 @@ where
                                 }
                            }
                         }
+                        SwarmEvent::Behaviour(BehaviourEvent::Rr(request_response::Event::OutboundFailure { peer, request_id, error })) => {
+                            error!(peer_id = ?peer, err = ?error, "Failed to send request");
+                            // The request may have been written before it failed
+                            if let Some((_, _, wire_len)) = requests.remove(&request_id) {
+                                stats.bytes_out(&peer, wire_len.load(Ordering::Relaxed));
+                            }
+                        }
                         SwarmEvent::Behaviour(BehaviourEvent::Rr(request_response::Event::ResponseSent { .. })) => {}
+                        SwarmEvent::Behaviour(BehaviourEvent::Identify(identify::Event::Received { peer_id, info })) => {
+                            let version = info
+                                .protocols
+                                .iter()
+                                .filter_map(|name| ProtocolVersion::from_protocol_name(name))
+                                .filter(|version| protocol_versions.contains(version))
+                                .max();
+
+                            match version {
+                                Some(version) => {
+                                    debug!(peer_id = ?peer_id, version = version.protocol_name(), "Negotiated protocol version");
+                                    shared.set_peer_version(peer_id, version);
+                                }
+                                None => {
+                                    error!(peer_id = ?peer_id, protocols = ?info.protocols, "Peer has no protocol version in common");
+                                }
+                            }
+                        }
                         event => {
                             debug!(event = ?event, "Swarm event");
                         }
@@ -198,6 +293,19 @@ where
         Ok(res?)
     }
 
+    /// Replace the IP whitelist and banlist
+    ///
+    /// Connections from IPs that are no longer allowed are closed
+    pub async fn set_ip_filter(&self, ip_filter: whitelist_ips::Config) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+
+        self.netout_tx
+            .send(Command::SetIpFilter(ip_filter, tx))
+            .map_err(|err| Error::ChannelError(err.to_string()))?;
+
+        rx.await.map_err(|err| Error::ChannelError(err.to_string()))
+    }
+
     pub async fn send(&self, peer: &PeerId, event: NetworkEvent) {
         self._send(peer, event).await;
     }
@@ -213,15 +321,46 @@ where
         futures::future::join_all(futures).await;
     }
 
+    /// A snapshot of connected peers, recent dial failures and bans
+    pub async fn info(&self) -> Result<NetworkInfo> {
+        let (tx, rx) = oneshot::channel();
+
+        self.netout_tx
+            .send(Command::Info(tx))
+            .map_err(|err| Error::ChannelError(err.to_string()))?;
+
+        rx.await.map_err(|err| Error::ChannelError(err.to_string()))
+    }
+
+    /// The protocol version negotiated with a connected peer
+    ///
+    /// Until the peer has identified itself, this is [`ProtocolVersion::OLDEST`]
+    pub fn peer_protocol_version(&self, peer: &PeerId) -> ProtocolVersion {
+        self.shared.peer_version(peer)
+    }
+
     async fn _send(&self, peer: &PeerId, event: NetworkEvent) -> Option<oneshot::Receiver<()>> {
         // Don't send messages to self
         if self.local_peer_id == *peer {
             return None;
         }
 
+        // Don't send events the peer can't decode
+        let peer_version = self.shared.peer_version(peer);
+        if event.min_protocol_version() > peer_version {
+            debug!(
+                peer_id = ?peer,
+                peer_version = peer_version.protocol_name(),
+                required_version = event.min_protocol_version().protocol_name(),
+                "Peer protocol version is too old for event, skipping"
+            );
+            return None;
+        }
+
         let (tx, rx) = oneshot::channel();
+        let trace = TraceContext::current();
 
-        match self.netout_tx.send(Command::Send(*peer, event, tx)) {
+        match self.netout_tx.send(Command::Send(*peer, event, trace, tx)) {
             Ok(_) => {}
             Err(err) => {
                 error!(?err, peer_id = ?peer, "Failed to send, dropping event");
@@ -231,7 +370,9 @@ where
         Some(rx)
     }
 
-    pub async fn next(&self) -> Option<(PeerId, NetworkEvent)> {
+    /// Receive the next event from the network, along with the peer that sent it and the trace it
+    /// was sent from (if the peer is tracing and supports propagating traces)
+    pub async fn next(&self) -> Option<(PeerId, NetworkEvent, Option<TraceContext>)> {
         self.netin_rx.lock().await.recv().await
     }
 }
@@ -245,6 +386,7 @@ impl NetworkShared {
         NetworkShared {
             state: Mutex::new(NetworkSharedState {
                 connected_peers: HashSet::new(),
+                peer_versions: HashMap::new(),
             }),
         }
     }
@@ -254,10 +396,70 @@ impl NetworkShared {
     }
 
     fn remove_peer(&self, peer_id: &PeerId) {
-        self.state.lock().connected_peers.remove(peer_id);
+        let mut state = self.state.lock();
+        state.connected_peers.remove(peer_id);
+        state.peer_versions.remove(peer_id);
+    }
+
+    fn set_peer_version(&self, peer_id: PeerId, version: ProtocolVersion) {
+        self.state.lock().peer_versions.insert(peer_id, version);
+    }
+
+    fn peer_version(&self, peer_id: &PeerId) -> ProtocolVersion {
+        self.state
+            .lock()
+            .peer_versions
+            .get(peer_id)
+            .copied()
+            .unwrap_or(ProtocolVersion::OLDEST)
     }
 }
 
 struct NetworkSharedState {
     connected_peers: HashSet<PeerId>,
+
+    /// The highest protocol version each peer has in common with us
+    peer_versions: HashMap<PeerId, ProtocolVersion>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::tests::TestEvent;
+
+    #[tokio::test]
+    async fn send_skips_events_newer_than_peer_version() {
+        let (_netin_tx, netin_rx) = mpsc::unbounded_channel();
+        let (netout_tx, mut netout_rx) = mpsc::unbounded_channel();
+        let network = Network::<TestEvent> {
+            netin_rx: AsyncMutex::new(netin_rx),
+            netout_tx,
+            local_peer_id: PeerId::random(),
+            shared: Arc::new(NetworkShared::new()),
+        };
+
+        let peer = PeerId::random();
+        network
+            .shared
+            .set_peer_version(peer, ProtocolVersion::V0_1_0);
+
+        assert!(network._send(&peer, TestEvent::New(1)).await.is_none());
+        assert!(netout_rx.try_recv().is_err());
+
+        assert!(network._send(&peer, TestEvent::Old(1)).await.is_some());
+        assert!(matches!(
+            netout_rx.try_recv(),
+            Ok(Command::Send(to, TestEvent::Old(1), _, _)) if to == peer
+        ));
+
+        // once the peer is upgraded, new events are sent
+        network
+            .shared
+            .set_peer_version(peer, ProtocolVersion::V0_2_0);
+        assert!(network._send(&peer, TestEvent::New(1)).await.is_some());
+        assert!(matches!(
+            netout_rx.try_recv(),
+            Ok(Command::Send(_, TestEvent::New(1), _, _))
+        ));
+    }
 }
@@ -1,24 +1,111 @@
-use std::marker::PhantomData;
+use std::{
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 // use super::events::NetworkEvent;
 use async_trait::async_trait;
 use borsh::{BorshDeserialize, BorshSerialize};
 use futures::prelude::*;
 use libp2p::request_response;
+use serde::Deserialize;
 use tokio::io;
 use wire_message::{wire_message, WireMessage};
 
+use crate::trace::TraceContext;
+
+/// A version of the request/response protocol spoken between nodes
+///
+/// Every version is advertised as a separate libp2p protocol name, so nodes running different
+/// releases negotiate the highest version they have in common when opening a substream. The
+/// negotiated version decides which [`Request`] wire version is sent to the peer.
+///
+/// Variants must be declared oldest first, as the derived [`Ord`] is used to compare versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum ProtocolVersion {
+    #[serde(rename = "0.1.0")]
+    V0_1_0,
+
+    /// Adds the sender's [`TraceContext`] to requests
+    #[serde(rename = "0.2.0")]
+    V0_2_0,
+}
+
+impl ProtocolVersion {
+    /// All supported protocol versions, newest first
+    pub const ALL: &'static [ProtocolVersion] = &[ProtocolVersion::V0_2_0, ProtocolVersion::V0_1_0];
+
+    /// The newest supported protocol version
+    pub const LATEST: ProtocolVersion = ProtocolVersion::V0_2_0;
+
+    /// The oldest supported protocol version, assumed for peers whose versions are not yet known
+    pub const OLDEST: ProtocolVersion = ProtocolVersion::V0_1_0;
+
+    /// The libp2p protocol name advertised for this version
+    pub fn protocol_name(&self) -> &'static str {
+        match self {
+            Self::V0_1_0 => "/polybase/0.1.0",
+            Self::V0_2_0 => "/polybase/0.2.0",
+        }
+    }
+
+    /// Parse a libp2p protocol name into a [`ProtocolVersion`], if it is one of ours
+    pub fn from_protocol_name(name: &str) -> Option<ProtocolVersion> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|version| version.protocol_name() == name)
+    }
+
+    /// The [`Request`] wire version that is sent to peers that negotiated this protocol version
+    pub fn request_version(&self) -> u64 {
+        match self {
+            Self::V0_1_0 => 1,
+            Self::V0_2_0 => 2,
+        }
+    }
+}
+
+/// Events that can be sent over the network
+///
+/// New event variants should only be sent to peers that understand them, so that a network can be
+/// upgraded without every node switching over at the same time. Implementers return the oldest
+/// [`ProtocolVersion`] that knows about a given event, and [`Network`][crate::Network] will skip
+/// peers that only speak an older version.
+pub trait VersionedEvent {
+    /// The oldest protocol version that can carry this event
+    fn min_protocol_version(&self) -> ProtocolVersion {
+        ProtocolVersion::OLDEST
+    }
+}
+
 #[derive(Clone)]
-pub struct PolyProtocol<NetworkEvent: Clone + Send + BorshSerialize + BorshDeserialize + 'static>(
-    pub PhantomData<NetworkEvent>,
-);
+pub struct PolyProtocol<NetworkEvent: Clone + Send + BorshSerialize + BorshDeserialize + 'static> {
+    pub version: ProtocolVersion,
+    _event: PhantomData<NetworkEvent>,
+}
+
+impl<NetworkEvent> PolyProtocol<NetworkEvent>
+where
+    NetworkEvent: Clone + Send + BorshSerialize + BorshDeserialize + 'static,
+{
+    pub fn new(version: ProtocolVersion) -> Self {
+        Self {
+            version,
+            _event: PhantomData,
+        }
+    }
+}
 
 impl<NetworkEvent> request_response::ProtocolName for PolyProtocol<NetworkEvent>
 where
     NetworkEvent: Clone + Send + BorshSerialize + BorshDeserialize + 'static,
 {
     fn protocol_name(&self) -> &[u8] {
-        b"/polybase/0.1.0"
+        self.version.protocol_name().as_bytes()
     }
 }
 
@@ -26,6 +113,13 @@ where
 #[wire_message]
 pub enum Request<T> {
     V1(T),
+    V2(RequestV2<T>),
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct RequestV2<T> {
+    pub event: T,
+    pub trace: Option<TraceContext>,
 }
 
 impl<T> WireMessage for Request<T>
@@ -38,16 +132,81 @@ where
     fn version(&self) -> u64 {
         match self {
             Self::V1(_) => 1,
+            Self::V2(_) => 2,
         }
     }
 
     fn upgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, wire_message::Error> {
         match self {
-            Self::V1(_) => Err(Self::max_version_error()),
+            Self::V1(event) => Ok(Self::V2(RequestV2 { event, trace: None })),
+            Self::V2(_) => Err(Self::max_version_error()),
+        }
+    }
+
+    // Versions that can't carry a [`TraceContext`] drop it
+    fn downgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, wire_message::Error> {
+        match self {
+            Self::V1(_) => Err(Self::min_version_error()),
+            Self::V2(RequestV2 { event, .. }) => Ok(Self::V1(event)),
         }
     }
 }
 
+impl<T> Request<T>
+where
+    T: Clone + BorshSerialize + BorshDeserialize + Send + Sync + 'static,
+{
+    /// Wrap an event in the [`Request`] wire version used by the given protocol version
+    ///
+    /// Versions that can't carry a [`TraceContext`] drop it
+    pub fn for_protocol(event: T, trace: Option<TraceContext>, version: ProtocolVersion) -> Self {
+        match version {
+            ProtocolVersion::V0_1_0 => Self::V1(event),
+            ProtocolVersion::V0_2_0 => Self::V2(RequestV2 { event, trace }),
+        }
+    }
+
+    /// The event carried by this request
+    pub fn event(&self) -> &T {
+        match self {
+            Self::V1(event) => event,
+            Self::V2(RequestV2 { event, .. }) => event,
+        }
+    }
+
+    /// The event carried by this request, and the trace it was sent from (if known)
+    pub fn into_parts(self) -> (T, Option<TraceContext>) {
+        match self {
+            Self::V1(event) => (event, None),
+            Self::V2(RequestV2 { event, trace }) => (event, trace),
+        }
+    }
+}
+
+/// A [`Request`] and its size on the wire
+///
+/// The codec sets the size when it writes or reads the request, so traffic can be counted from
+/// the bytes that were actually sent, without serializing the event again
+#[derive(Debug)]
+pub struct SizedRequest<T> {
+    pub request: Request<T>,
+    pub wire_len: Arc<AtomicUsize>,
+}
+
+impl<T> SizedRequest<T> {
+    pub fn new(request: Request<T>) -> Self {
+        Self {
+            request,
+            wire_len: Arc::default(),
+        }
+    }
+
+    /// The number of bytes the request took on the wire, or 0 if it hasn't been written yet
+    pub fn wire_len(&self) -> usize {
+        self.wire_len.load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Debug)]
 #[wire_message]
 pub enum Response {
@@ -74,15 +233,16 @@ impl WireMessage for Response {
 #[async_trait]
 impl<NetworkEvent> request_response::Codec for PolyProtocol<NetworkEvent>
 where
-    NetworkEvent: Clone + Send + Sync + BorshSerialize + BorshDeserialize + 'static,
+    NetworkEvent:
+        VersionedEvent + Clone + Send + Sync + BorshSerialize + BorshDeserialize + 'static,
 {
     type Protocol = PolyProtocol<NetworkEvent>;
-    type Request = Request<NetworkEvent>;
+    type Request = SizedRequest<NetworkEvent>;
     type Response = Response;
 
     async fn read_request<T>(
         &mut self,
-        _: &PolyProtocol<NetworkEvent>,
+        protocol: &PolyProtocol<NetworkEvent>,
         io: &mut T,
     ) -> io::Result<Self::Request>
     where
@@ -92,7 +252,22 @@ where
         io.read_to_end(&mut buf).await?;
         let request =
             Request::from_bytes(&buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        Ok(request)
+
+        if request.version() > protocol.version.request_version() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "request version {} is not allowed on protocol {}",
+                    request.version(),
+                    protocol.version.protocol_name()
+                ),
+            ));
+        }
+
+        Ok(SizedRequest {
+            request,
+            wire_len: Arc::new(AtomicUsize::new(buf.len())),
+        })
     }
 
     async fn read_response<T>(
@@ -112,18 +287,37 @@ where
 
     async fn write_request<T>(
         &mut self,
-        _: &Self::Protocol,
+        protocol: &Self::Protocol,
         io: &mut T,
         request: Self::Request,
     ) -> io::Result<()>
     where
         T: AsyncWrite + Unpin + Send,
     {
+        let event = request.request.event();
+
+        // `Network` only sends events to peers that advertised a recent enough protocol, so this
+        // should only happen if the peer negotiated a different version than it advertised
+        if event.min_protocol_version() > protocol.version {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "event requires protocol {}, but {} was negotiated",
+                    event.min_protocol_version().protocol_name(),
+                    protocol.version.protocol_name()
+                ),
+            ));
+        }
+
         let data = request
-            .to_bytes()
+            .request
+            .to_bytes_at_version(protocol.version.request_version(), &mut ())
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        io.write_all(&data).await
+        io.write_all(&data).await?;
+        request.wire_len.store(data.len(), Ordering::Relaxed);
+
+        Ok(())
     }
 
     async fn write_response<T>(
@@ -141,3 +335,125 @@ where
         io.write_all(&data).await
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use futures::io::Cursor;
+    use libp2p::request_response::Codec;
+
+    use super::*;
+
+    /// An event with a variant that only newer peers understand
+    #[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+    pub(crate) enum TestEvent {
+        Old(u8),
+        New(u8),
+    }
+
+    impl VersionedEvent for TestEvent {
+        fn min_protocol_version(&self) -> ProtocolVersion {
+            match self {
+                Self::Old(_) => ProtocolVersion::V0_1_0,
+                Self::New(_) => ProtocolVersion::V0_2_0,
+            }
+        }
+    }
+
+    pub(crate) fn trace() -> TraceContext {
+        TraceContext {
+            trace_id: [1; 16],
+            span_id: [2; 8],
+            trace_flags: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_request_at_negotiated_version() {
+        for &version in ProtocolVersion::ALL {
+            let mut protocol = PolyProtocol::<TestEvent>::new(version);
+            let request = SizedRequest::new(Request::for_protocol(
+                TestEvent::Old(1),
+                Some(trace()),
+                ProtocolVersion::LATEST,
+            ));
+            let wire_len = Arc::clone(&request.wire_len);
+
+            let mut io = Cursor::new(Vec::new());
+            protocol
+                .write_request(&PolyProtocol::new(version), &mut io, request)
+                .await
+                .unwrap();
+            let bytes = io.into_inner();
+
+            let written = Request::<TestEvent>::from_bytes(&bytes).unwrap();
+            assert_eq!(written.version(), version.request_version());
+            assert_eq!(written.event(), &TestEvent::Old(1));
+            assert_eq!(wire_len.load(Ordering::Relaxed), bytes.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn write_rejects_event_newer_than_protocol() {
+        let mut protocol = PolyProtocol::<TestEvent>::new(ProtocolVersion::V0_1_0);
+        let request = SizedRequest::new(Request::for_protocol(
+            TestEvent::New(1),
+            None,
+            ProtocolVersion::LATEST,
+        ));
+
+        let err = protocol
+            .write_request(
+                &PolyProtocol::new(ProtocolVersion::V0_1_0),
+                &mut Cursor::new(Vec::new()),
+                request,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn read_rejects_request_newer_than_protocol() {
+        let bytes = Request::V2(RequestV2 {
+            event: TestEvent::Old(1),
+            trace: Some(trace()),
+        })
+        .to_bytes()
+        .unwrap();
+
+        let mut protocol = PolyProtocol::<TestEvent>::new(ProtocolVersion::V0_1_0);
+        let err = protocol
+            .read_request(
+                &PolyProtocol::new(ProtocolVersion::V0_1_0),
+                &mut Cursor::new(bytes.clone()),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let request = protocol
+            .read_request(
+                &PolyProtocol::new(ProtocolVersion::V0_2_0),
+                &mut Cursor::new(bytes.clone()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(request.wire_len(), bytes.len());
+        assert_eq!(
+            request.request.into_parts(),
+            (TestEvent::Old(1), Some(trace()))
+        );
+    }
+
+    #[test]
+    fn downgrade_drops_trace() {
+        let request = Request::V2(RequestV2 {
+            event: TestEvent::Old(1),
+            trace: Some(trace()),
+        });
+
+        let downgraded = request.downgrade_once(&mut ()).unwrap();
+        assert_eq!(downgraded, Request::V1(TestEvent::Old(1)));
+        assert_eq!(downgraded.into_parts(), (TestEvent::Old(1), None));
+    }
+}
@@ -0,0 +1,133 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// The OpenTelemetry span a request was sent from
+///
+/// This is carried alongside events (from [`ProtocolVersion::V0_2_0`][crate::ProtocolVersion])
+/// so that spans on the receiving node can be joined to the trace that produced the event, for
+/// example to follow a txn from RPC submission to the validator that included it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub trace_flags: u8,
+}
+
+impl TraceContext {
+    /// The context of the current [`tracing::Span`]
+    ///
+    /// Returns `None` if there is no active OpenTelemetry span (e.g. if OpenTelemetry tracing
+    /// is disabled)
+    pub fn current() -> Option<TraceContext> {
+        let context = tracing::Span::current().context();
+        let span = context.span();
+        let span_context = span.span_context();
+
+        if !span_context.is_valid() {
+            return None;
+        }
+
+        Some(TraceContext {
+            trace_id: span_context.trace_id().to_bytes(),
+            span_id: span_context.span_id().to_bytes(),
+            trace_flags: span_context.trace_flags().to_u8(),
+        })
+    }
+
+    /// Make the remote span described by this context the parent of `span`
+    pub fn set_as_parent(&self, span: &tracing::Span) {
+        let span_context = SpanContext::new(
+            TraceId::from_bytes(self.trace_id),
+            SpanId::from_bytes(self.span_id),
+            TraceFlags::new(self.trace_flags),
+            true,
+            TraceState::default(),
+        );
+
+        span.set_parent(opentelemetry::Context::new().with_remote_span_context(span_context));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::layer::SubscriberExt;
+    use wire_message::WireMessage;
+
+    use super::*;
+    use crate::{
+        protocol::{tests::TestEvent, Request},
+        ProtocolVersion,
+    };
+
+    fn with_tracing(f: impl FnOnce()) {
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("p2p2-test")));
+
+        tracing::subscriber::with_default(subscriber, f);
+    }
+
+    fn trace_id(span: &tracing::Span) -> TraceId {
+        span.context().span().span_context().trace_id()
+    }
+
+    #[test]
+    fn no_trace_without_opentelemetry() {
+        let span = tracing::info_span!("send");
+        let _guard = span.enter();
+
+        assert_eq!(TraceContext::current(), None);
+    }
+
+    #[test]
+    fn trace_round_trips_through_request() {
+        with_tracing(|| {
+            let send_span = tracing::info_span!("send");
+            let _guard = send_span.enter();
+
+            let trace = TraceContext::current().unwrap();
+            assert_eq!(trace.trace_id, trace_id(&send_span).to_bytes());
+
+            let bytes =
+                Request::for_protocol(TestEvent::Old(1), Some(trace), ProtocolVersion::V0_2_0)
+                    .to_bytes()
+                    .unwrap();
+            let (event, received) = Request::<TestEvent>::from_bytes(&bytes)
+                .unwrap()
+                .into_parts();
+            assert_eq!(event, TestEvent::Old(1));
+            assert_eq!(received, Some(trace));
+
+            let receive_span = tracing::info_span!(parent: None, "receive");
+            received.unwrap().set_as_parent(&receive_span);
+            assert_eq!(trace_id(&receive_span), trace_id(&send_span));
+        });
+    }
+
+    #[test]
+    fn v1_peers_get_no_trace() {
+        with_tracing(|| {
+            let span = tracing::info_span!("send");
+            let _guard = span.enter();
+            let trace = TraceContext::current();
+            assert!(trace.is_some());
+
+            let request = Request::for_protocol(TestEvent::Old(1), trace, ProtocolVersion::V0_1_0);
+            let bytes = request.to_bytes().unwrap();
+            let (_, received) = Request::<TestEvent>::from_bytes(&bytes)
+                .unwrap()
+                .into_parts();
+            assert_eq!(received, None);
+
+            // a V2 request written for a V1 peer drops the trace too
+            let request = Request::for_protocol(TestEvent::Old(1), trace, ProtocolVersion::V0_2_0);
+            let bytes = request.to_bytes_at_version(1, &mut ()).unwrap();
+            let (_, received) = Request::<TestEvent>::from_bytes(&bytes)
+                .unwrap()
+                .into_parts();
+            assert_eq!(received, None);
+        });
+    }
+}
@@ -1,11 +1,16 @@
+use futures::future::Either;
 use libp2p::{
     core::{muxing::StreamMuxerBox, transport::Boxed, upgrade},
     dns::TokioDnsConfig,
     identity::Keypair,
-    noise, tcp, yamux, PeerId, Transport,
+    multiaddr::Protocol,
+    noise, quic, tcp, yamux, Multiaddr, PeerId, Transport,
 };
 
 /// Create the transports for the swarm, we use TCP/IP and quic.
+///
+/// The transport used for a connection is picked from the address being dialed or listened on:
+/// `/udp/<port>/quic-v1` addresses use QUIC, and `/tcp/<port>` addresses use TCP.
 pub fn create_transport(keypair: &Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
     // Set up an encrypted DNS-enabled TCP Transport over the yamux protocol.
     let tcp_transport = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
@@ -15,5 +20,114 @@ pub fn create_transport(keypair: &Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
         .timeout(std::time::Duration::from_secs(20))
         .boxed();
 
-    TokioDnsConfig::system(tcp_transport).unwrap().boxed()
+    // QUIC does encryption and multiplexing itself, so it doesn't need to be upgraded
+    let quic_transport = quic::tokio::Transport::new(quic::Config::new(keypair));
+
+    let transport = quic_transport
+        .or_transport(tcp_transport)
+        .map(|output, _| match output {
+            Either::Left((peer_id, connection)) => (peer_id, StreamMuxerBox::new(connection)),
+            Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+        });
+
+    TokioDnsConfig::system(transport).unwrap().boxed()
+}
+
+/// Is this a QUIC address
+pub(crate) fn is_quic(addr: &Multiaddr) -> bool {
+    addr.iter()
+        .any(|protocol| matches!(protocol, Protocol::QuicV1 | Protocol::Quic))
+}
+
+/// The TCP address to fall back to when a QUIC address can't be reached (for example, because UDP
+/// is blocked somewhere between the two nodes)
+///
+/// `/ip4/1.2.3.4/udp/5000/quic-v1` falls back to `/ip4/1.2.3.4/tcp/5000`. Returns `None` if the
+/// address isn't a QUIC address.
+pub(crate) fn tcp_fallback(addr: &Multiaddr) -> Option<Multiaddr> {
+    if !is_quic(addr) {
+        return None;
+    }
+
+    let fallback = addr
+        .iter()
+        .filter_map(|protocol| match protocol {
+            Protocol::Udp(port) => Some(Protocol::Tcp(port)),
+            Protocol::QuicV1 | Protocol::Quic => None,
+            other => Some(other),
+        })
+        .collect();
+
+    Some(fallback)
+}
+
+/// The TCP address to listen on as a fallback for a QUIC listener
+///
+/// `requested` is the QUIC address passed to `listen_on`, and `bound` is an address the listener
+/// reported once it was bound. The UDP port is taken from `bound`, so a listener on port 0 gets a
+/// TCP fallback on the same port that dialers will derive from its QUIC address, while the rest of
+/// the address (e.g. an unspecified `0.0.0.0` IP) is kept from `requested`.
+pub(crate) fn tcp_fallback_listener(requested: &Multiaddr, bound: &Multiaddr) -> Option<Multiaddr> {
+    let port = bound.iter().find_map(|protocol| match protocol {
+        Protocol::Udp(port) => Some(port),
+        _ => None,
+    })?;
+
+    let addr = requested
+        .iter()
+        .map(|protocol| match protocol {
+            Protocol::Udp(_) => Protocol::Udp(port),
+            other => other,
+        })
+        .collect();
+
+    tcp_fallback(&addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn detects_quic_addresses() {
+        assert!(is_quic(&addr("/ip4/1.2.3.4/udp/5000/quic-v1")));
+        assert!(is_quic(&addr("/ip6/::1/udp/5000/quic")));
+        assert!(!is_quic(&addr("/ip4/1.2.3.4/tcp/5000")));
+        assert!(!is_quic(&addr("/dns4/example.com/tcp/5000")));
+    }
+
+    #[test]
+    fn tcp_fallback_keeps_port() {
+        assert_eq!(
+            tcp_fallback(&addr("/ip4/1.2.3.4/udp/5000/quic-v1")),
+            Some(addr("/ip4/1.2.3.4/tcp/5000"))
+        );
+        assert_eq!(
+            tcp_fallback(&addr("/dns4/example.com/udp/5000/quic-v1")),
+            Some(addr("/dns4/example.com/tcp/5000"))
+        );
+        assert_eq!(tcp_fallback(&addr("/ip4/1.2.3.4/tcp/5000")), None);
+    }
+
+    #[test]
+    fn tcp_fallback_listener_uses_bound_port() {
+        assert_eq!(
+            tcp_fallback_listener(
+                &addr("/ip4/0.0.0.0/udp/0/quic-v1"),
+                &addr("/ip4/192.168.1.2/udp/41234/quic-v1"),
+            ),
+            Some(addr("/ip4/0.0.0.0/tcp/41234"))
+        );
+        assert_eq!(
+            tcp_fallback_listener(
+                &addr("/ip4/0.0.0.0/tcp/0"),
+                &addr("/ip4/192.168.1.2/tcp/41234"),
+            ),
+            None
+        );
+    }
 }
@@ -1,11 +1,11 @@
 use std::fmt::Debug;
 
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use rand_derive2::RandGen;
 use serde::{Deserialize, Serialize};
 
 microtype::microtype! {
-    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, BorshSerialize, BorshDeserialize, RandGen, Serialize, Deserialize)]
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, BorshSerialize, BorshDeserialize, BorshSchema, RandGen, Serialize, Deserialize)]
     pub u64 {
         #[derive(Default)]
         #[int]  // add maths traits
@@ -1,5 +1,5 @@
 use crate::u256;
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::borrow::Borrow;
@@ -18,6 +18,7 @@ use web3::types::H256;
     Deserialize,
     BorshSerialize,
     BorshDeserialize,
+    BorshSchema,
 )]
 // Serialize transparently with serde
 // because otherwise it would be serialized as a tuple.
@@ -1,5 +1,6 @@
 pub mod block_height;
 pub mod hash;
+pub mod migration;
 pub mod pagination;
 pub mod peer;
 pub mod pool;
@@ -0,0 +1,427 @@
+//! Migrations of the data in a store (e.g. the block store) between format versions
+//!
+//! A store lists its [`Migration`]s in order with [`Migrate::migrations`], and records how many of
+//! them have been applied as its version. Migrations run in steps, and the cursor returned by each
+//! step is saved as a [`Checkpoint`], so an interrupted migration resumes where it stopped instead
+//! of starting over
+
+use std::fmt;
+
+use serde::Serialize;
+use tracing::info;
+
+/// A change to the data in a store of type `S`
+pub struct Migration<S: Migrate + ?Sized> {
+    /// A short, unique name for the migration
+    pub id: &'static str,
+    pub description: &'static str,
+    /// Run the next step of the migration, starting at `cursor`, or at the beginning if it's
+    /// `None`
+    ///
+    /// If the process exits before the step's checkpoint is saved, the step runs again, so steps
+    /// must be idempotent. In a dry run, the step must not change the store
+    pub step: fn(
+        store: &S,
+        ctx: &S::Context,
+        cursor: Option<&[u8]>,
+        dry_run: bool,
+    ) -> Result<Step, S::Error>,
+}
+
+/// The result of a [`Migration::step`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// There is more to migrate, starting at `cursor`
+    Continue { cursor: Vec<u8>, items: u64 },
+    /// The migration is complete
+    Done { items: u64 },
+}
+
+/// A store with versioned data
+pub trait Migrate {
+    /// Anything the migrations need, other than the store itself
+    type Context;
+    type Error: From<Error>;
+
+    /// The store's migrations, oldest first
+    ///
+    /// Migrations must never be removed or reordered, since a store's version is the number of
+    /// these migrations that have been applied to it
+    fn migrations() -> Vec<Migration<Self>>;
+
+    /// The number of migrations that have been applied to the store
+    fn version(&self) -> Result<u64, Self::Error>;
+    fn set_version(&self, version: u64) -> Result<(), Self::Error>;
+
+    /// The progress of the migration that was running when the process last exited, if any
+    fn checkpoint(&self) -> Result<Option<Checkpoint>, Self::Error>;
+    fn set_checkpoint(&self, checkpoint: Option<&Checkpoint>) -> Result<(), Self::Error>;
+
+    /// The version of a store with every migration applied, which new stores start at
+    fn latest_version() -> u64
+    where
+        Self: Sized,
+    {
+        Self::migrations().len() as u64
+    }
+}
+
+/// The progress of an interrupted migration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The version the store is being migrated to
+    pub version: u64,
+    /// The cursor to run the next step from
+    pub cursor: Vec<u8>,
+    /// The number of items that have been migrated so far
+    pub items: u64,
+}
+
+impl Checkpoint {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(16 + self.cursor.len());
+        out.extend_from_slice(&self.version.to_be_bytes());
+        out.extend_from_slice(&self.items.to_be_bytes());
+        out.extend_from_slice(&self.cursor);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 16 {
+            return Err(Error::InvalidCheckpoint);
+        }
+
+        Ok(Self {
+            version: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            items: u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+            cursor: bytes[16..].to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The store is at a newer version than this build knows about
+    UnknownVersion { version: u64, latest: u64 },
+    /// The saved checkpoint couldn't be decoded
+    InvalidCheckpoint,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownVersion { version, latest } => write!(
+                f,
+                "store is at version {version}, but the latest known version is {latest}"
+            ),
+            Self::InvalidCheckpoint => write!(f, "invalid migration checkpoint"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Whether a migration has been applied to a store, as returned by [`status`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationStatus {
+    /// The store's version once this migration is applied
+    pub version: u64,
+    pub id: &'static str,
+    pub description: &'static str,
+    pub applied: bool,
+    /// The number of items migrated so far, if the migration was interrupted
+    pub progress: Option<u64>,
+}
+
+/// The number of items a migration migrated, as returned by [`migrate`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationRun {
+    pub id: &'static str,
+    pub items: u64,
+}
+
+/// The status of each of the store's migrations, oldest first
+pub fn status<S: Migrate>(store: &S) -> Result<Vec<MigrationStatus>, S::Error> {
+    let version = store.version()?;
+    let checkpoint = store.checkpoint()?;
+
+    Ok(S::migrations()
+        .into_iter()
+        .zip(1..)
+        .map(|(migration, migration_version)| MigrationStatus {
+            version: migration_version,
+            id: migration.id,
+            description: migration.description,
+            applied: migration_version <= version,
+            progress: checkpoint
+                .as_ref()
+                .filter(|checkpoint| checkpoint.version == migration_version)
+                .map(|checkpoint| checkpoint.items),
+        })
+        .collect())
+}
+
+/// Apply the store's pending migrations in order, resuming an interrupted migration from its
+/// checkpoint
+///
+/// In a dry run, each pending migration is run against the current data without changing the
+/// store, to find how many items it would migrate
+pub fn migrate<S: Migrate>(
+    store: &S,
+    ctx: &S::Context,
+    dry_run: bool,
+) -> Result<Vec<MigrationRun>, S::Error> {
+    let migrations = S::migrations();
+    let latest = migrations.len() as u64;
+    let version = store.version()?;
+
+    if version > latest {
+        return Err(Error::UnknownVersion { version, latest }.into());
+    }
+
+    let mut checkpoint = store.checkpoint()?;
+    let mut runs = Vec::new();
+
+    for (migration, migration_version) in migrations.iter().zip(1..).skip(version as usize) {
+        let (mut cursor, mut items) = match checkpoint.take() {
+            Some(checkpoint) if checkpoint.version == migration_version => {
+                info!(
+                    id = migration.id,
+                    items = checkpoint.items,
+                    dry_run,
+                    "Resuming migration"
+                );
+                (Some(checkpoint.cursor), checkpoint.items)
+            }
+            _ => {
+                info!(
+                    id = migration.id,
+                    description = migration.description,
+                    dry_run,
+                    "Starting migration"
+                );
+                (None, 0)
+            }
+        };
+
+        loop {
+            match (migration.step)(store, ctx, cursor.as_deref(), dry_run)? {
+                Step::Continue {
+                    cursor: next,
+                    items: step_items,
+                } => {
+                    items += step_items;
+                    info!(id = migration.id, items, dry_run, "Migration progress");
+
+                    if !dry_run {
+                        store.set_checkpoint(Some(&Checkpoint {
+                            version: migration_version,
+                            cursor: next.clone(),
+                            items,
+                        }))?;
+                    }
+
+                    cursor = Some(next);
+                }
+                Step::Done { items: step_items } => {
+                    items += step_items;
+                    break;
+                }
+            }
+        }
+
+        if !dry_run {
+            store.set_version(migration_version)?;
+            store.set_checkpoint(None)?;
+        }
+
+        info!(id = migration.id, items, dry_run, "Finished migration");
+
+        runs.push(MigrationRun {
+            id: migration.id,
+            items,
+        });
+    }
+
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    /// A store of numbers, where each migration doubles every number, one number per step
+    #[derive(Default)]
+    struct Numbers {
+        numbers: RefCell<Vec<u64>>,
+        version: Cell<u64>,
+        checkpoint: RefCell<Option<Checkpoint>>,
+        /// Fail after this many steps, to simulate the process exiting
+        fail_after: Cell<Option<u64>>,
+    }
+
+    impl Numbers {
+        fn double(&self, _ctx: &(), cursor: Option<&[u8]>, dry_run: bool) -> Result<Step, Error> {
+            if let Some(steps) = self.fail_after.get() {
+                if steps == 0 {
+                    return Err(Error::InvalidCheckpoint);
+                }
+                self.fail_after.set(Some(steps - 1));
+            }
+
+            let index = cursor.map_or(0, |cursor| cursor[0] as usize);
+            let mut numbers = self.numbers.borrow_mut();
+
+            if index >= numbers.len() {
+                return Ok(Step::Done { items: 0 });
+            }
+
+            if !dry_run {
+                numbers[index] *= 2;
+            }
+
+            Ok(Step::Continue {
+                cursor: vec![index as u8 + 1],
+                items: 1,
+            })
+        }
+    }
+
+    impl Migrate for Numbers {
+        type Context = ();
+        type Error = Error;
+
+        fn migrations() -> Vec<Migration<Self>> {
+            vec![
+                Migration {
+                    id: "double",
+                    description: "Double every number",
+                    step: Self::double,
+                },
+                Migration {
+                    id: "double-again",
+                    description: "Double every number again",
+                    step: Self::double,
+                },
+            ]
+        }
+
+        fn version(&self) -> Result<u64, Error> {
+            Ok(self.version.get())
+        }
+
+        fn set_version(&self, version: u64) -> Result<(), Error> {
+            self.version.set(version);
+            Ok(())
+        }
+
+        fn checkpoint(&self) -> Result<Option<Checkpoint>, Error> {
+            Ok(self.checkpoint.borrow().clone())
+        }
+
+        fn set_checkpoint(&self, checkpoint: Option<&Checkpoint>) -> Result<(), Error> {
+            *self.checkpoint.borrow_mut() = checkpoint.cloned();
+            Ok(())
+        }
+    }
+
+    fn numbers() -> Numbers {
+        Numbers {
+            numbers: RefCell::new(vec![1, 2, 3]),
+            ..Numbers::default()
+        }
+    }
+
+    #[test]
+    fn applies_pending_migrations() {
+        let store = numbers();
+
+        let runs = migrate(&store, &(), false).unwrap();
+        assert_eq!(
+            runs,
+            [
+                MigrationRun {
+                    id: "double",
+                    items: 3
+                },
+                MigrationRun {
+                    id: "double-again",
+                    items: 3
+                },
+            ]
+        );
+        assert_eq!(*store.numbers.borrow(), [4, 8, 12]);
+        assert_eq!(store.version.get(), Numbers::latest_version());
+        assert!(status(&store).unwrap().iter().all(|status| status.applied));
+
+        // nothing is pending now
+        assert!(migrate(&store, &(), false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn dry_run_does_not_change_the_store() {
+        let store = numbers();
+
+        let runs = migrate(&store, &(), true).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].items, 3);
+
+        assert_eq!(*store.numbers.borrow(), [1, 2, 3]);
+        assert_eq!(store.version.get(), 0);
+        assert_eq!(*store.checkpoint.borrow(), None);
+    }
+
+    #[test]
+    fn interrupted_migration_resumes() {
+        let store = numbers();
+
+        store.fail_after.set(Some(2));
+        migrate(&store, &(), false).unwrap_err();
+
+        assert_eq!(*store.numbers.borrow(), [2, 4, 3]);
+        assert_eq!(store.version.get(), 0);
+        assert_eq!(status(&store).unwrap()[0].progress, Some(2));
+
+        store.fail_after.set(None);
+        let runs = migrate(&store, &(), false).unwrap();
+
+        // already migrated numbers aren't doubled again
+        assert_eq!(runs[0].items, 3);
+        assert_eq!(*store.numbers.borrow(), [4, 8, 12]);
+        assert_eq!(store.version.get(), 2);
+    }
+
+    #[test]
+    fn unknown_version() {
+        let store = numbers();
+        store.version.set(3);
+
+        assert_eq!(
+            migrate(&store, &(), false).unwrap_err(),
+            Error::UnknownVersion {
+                version: 3,
+                latest: 2
+            }
+        );
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let checkpoint = Checkpoint {
+            version: 2,
+            cursor: vec![1, 2, 3],
+            items: 1000,
+        };
+
+        assert_eq!(
+            Checkpoint::from_bytes(&checkpoint.to_bytes()).unwrap(),
+            checkpoint
+        );
+        assert_eq!(
+            Checkpoint::from_bytes(&[0; 15]),
+            Err(Error::InvalidCheckpoint)
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{hash::CryptoHash, peer::Address};
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use secp256k1::{
     ecdsa::{self, RecoveryId},
     Message, SECP256K1,
@@ -9,7 +9,18 @@ use sha3::{Digest, Keccak256};
 
 const NETWORK: &str = "Polybase";
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    BorshSerialize,
+    BorshDeserialize,
+    BorshSchema,
+    Serialize,
+    Deserialize,
+)]
 #[serde(transparent)]
 pub struct Signature(#[serde(with = "hex::serde")] pub [u8; 65]);
 
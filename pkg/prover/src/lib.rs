@@ -11,8 +11,9 @@ use constants::{UTXO_AGG_LEAVES, UTXO_AGG_NUMBER};
 use contracts::RollupContract;
 use ethereum_types::H256;
 use primitives::sig::Signature;
+use serde::{Deserialize, Serialize};
 use smirk::{
-    hash_cache::{NoopHashCache, SimpleHashCache},
+    hash_cache::{HashCache, NoopHashCache},
     Element, Tree,
 };
 use smirk_metadata::SmirkMetadata;
@@ -86,7 +87,7 @@ impl Transaction {
         Self { proof }
     }
 }
-#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize)]
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 pub struct Proof {
     pub proof: Vec<u8>,
     pub agg_instances: Vec<Element>,
@@ -101,7 +102,7 @@ impl Proof {
     }
 }
 
-#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize)]
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 pub struct RollupInput {
     proof: Proof,
     height: u64,
@@ -143,9 +144,9 @@ impl Prover {
     }
 
     #[tracing::instrument(err, skip_all, fields(height, txns_len = txns.len()))]
-    pub async fn prove(
+    pub async fn prove<C: HashCache + Clone + Send>(
         self: &Arc<Self>,
-        notes_tree: &MerkleTree<SimpleHashCache>,
+        notes_tree: &MerkleTree<C>,
         _ban_tree: &MerkleTree,
         height: u64,
         txns: [Option<Transaction>; MAXIMUM_TXNS],
@@ -249,9 +250,9 @@ impl Prover {
     }
 
     #[tracing::instrument(err, skip_all)]
-    fn generate_aggregate_proof(
+    fn generate_aggregate_proof<C: HashCache>(
         &self,
-        tree: &mut MerkleTree<SimpleHashCache>,
+        tree: &mut MerkleTree<C>,
         txns: [Option<Transaction>; 6],
         current_block: u64,
     ) -> Result<(AggregateAgg<1>, Vec<u8>), Error> {
@@ -314,9 +315,9 @@ impl Prover {
     }
 
     #[tracing::instrument(err, skip_all)]
-    fn aggregate_utxo(
+    fn aggregate_utxo<C: HashCache>(
         &self,
-        tree: &mut MerkleTree<SimpleHashCache>,
+        tree: &mut MerkleTree<C>,
         utxos: [Transaction; UTXO_AGG_NUMBER],
         current_block: u64,
     ) -> Result<AggregateUtxo<UTXO_AGG_NUMBER, MERKLE_TREE_DEPTH, UTXO_AGG_LEAVES>, Error> {
@@ -334,9 +335,9 @@ impl Prover {
     }
 
     #[tracing::instrument(err, skip_all)]
-    fn gen_batch(
+    fn gen_batch<C: HashCache>(
         &self,
-        tree: &mut MerkleTree<SimpleHashCache>,
+        tree: &mut MerkleTree<C>,
         txns: &[Transaction; UTXO_AGG_NUMBER],
         current_block: u64,
     ) -> Result<(
@@ -426,26 +427,54 @@ impl Prover {
         Ok(path)
     }
 
-    // TODO: We don't use this yet, when we do, make the ban tree persistent
-    // /// Bans a given address, adding them to the banned list
-    // pub async fn ban_address(&self, address: Base) -> Result<Vec<Base>, Error> {
-    //     let mut ban_tree = self.ban_tree.lock();
-    //     let el = Element::from_base(address);
+    // TODO: the ban tree isn't persisted yet, so bans are lost when the prover restarts
+    /// Bans a given address, adding them to the banned list
+    ///
+    /// Returns the address's path in the updated ban tree
+    pub async fn ban_address(
+        &self,
+        ban_tree: &mut MerkleTree,
+        address: Base,
+        height: u64,
+    ) -> Result<Vec<Base>, Error> {
+        let el = Element::from_base(address);
 
-    //     // Insert address into banned tree
-    //     ban_tree.insert(el, ())?;
+        // Insert address into banned tree
+        ban_tree.insert(
+            el,
+            SmirkMetadata {
+                inserted_in: height,
+            },
+        )?;
 
-    //     let path = ban_tree.path_for(el);
+        let path = ban_tree.path_for(el);
 
-    //     let path = path
-    //         .siblings_deepest_first()
-    //         .iter()
-    //         .copied()
-    //         .map(Element::to_base)
-    //         .collect();
+        let path = path
+            .siblings_deepest_first()
+            .iter()
+            .copied()
+            .map(Element::to_base)
+            .collect();
+
+        Ok(path)
+    }
 
-    //     Ok(path)
-    // }
+    /// Un-bans the given addresses, removing them from the banned list
+    ///
+    /// Returns the addresses that were banned. Addresses that weren't banned are ignored
+    pub async fn unban_addresses(
+        &self,
+        ban_tree: &mut MerkleTree,
+        addresses: impl IntoIterator<Item = Base>,
+    ) -> Vec<Base> {
+        let elements = addresses.into_iter().map(Element::from_base);
+
+        ban_tree
+            .remove_batch(elements, |_| {}, |_| {})
+            .into_iter()
+            .map(|(el, _)| el.to_base())
+            .collect()
+    }
 
     /// Checks if the address is in the banned list
     pub async fn compliance_check(
@@ -1,10 +1,11 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
 
 /// The block number of the block that a particular [`Element`] was inserted into a [`Tree`]
 ///
 /// [`Element`]: smirk::Element
 /// [`Tree`]: smirk::Tree
-#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct SmirkMetadata {
     pub inserted_in: u64,
@@ -2,7 +2,11 @@ use std::{hint::black_box, process::Command};
 
 use benchy::{benchmark, BenchmarkRun};
 use rand::thread_rng;
-use smirk::{hash_merge, storage::Persistent, Batch, Element};
+use smirk::{
+    hash_merge,
+    storage::{Lazy, Persistent},
+    Batch, Element,
+};
 use tempdir::TempDir;
 
 fn make_batch(n: usize) -> Batch<160, ()> {
@@ -106,12 +110,45 @@ pub fn storage_load(b: &mut BenchmarkRun) {
     Command::new("cp")
         .arg("-r")
         .arg(dir.path())
-        .arg(this_dir.path())
+        .arg(this_dir.path().join("db"))
         .status()
         .unwrap();
 
     b.run(|| {
-        let tree = Persistent::<160, ()>::load(this_dir.path()).unwrap();
+        let tree = Persistent::<160, ()>::load(this_dir.path().join("db")).unwrap();
+        black_box(tree);
+    });
+
+    b.metrics
+        .insert("hash_count".into(), zk_primitives::hash_count());
+
+    b.metrics.insert(
+        "hash_element_count".into(),
+        zk_primitives::hash_element_count(),
+    );
+}
+
+#[benchmark]
+pub fn storage_load_lazy(b: &mut BenchmarkRun) {
+    let dir = TempDir::new("smirk-benchmark").unwrap();
+
+    let batch = make_batch(1000);
+
+    let mut lazy = Lazy::<160, ()>::load(dir.path()).unwrap();
+    lazy.insert_batch(batch).unwrap();
+    drop(lazy);
+
+    // if we don't copy it to its own path, we get rocksdb errors
+    let this_dir = TempDir::new("smirk-benchmark").unwrap();
+    Command::new("cp")
+        .arg("-r")
+        .arg(dir.path())
+        .arg(this_dir.path().join("db"))
+        .status()
+        .unwrap();
+
+    b.run(|| {
+        let tree = Lazy::<160, ()>::load(this_dir.path().join("db")).unwrap();
         black_box(tree);
     });
 
@@ -129,4 +166,5 @@ benchy::main!(
     // hash_merge_1_000_000_cached,
     create_tree,
     storage_load,
+    storage_load_lazy,
 );
@@ -0,0 +1,365 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash, Hasher},
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use zk_primitives::{hash_merge, Element};
+
+use super::{CacheMetrics, HashCache, KnownHash, PersistentHashCache};
+
+/// The number of independently locked shards, so that parallel hashing doesn't contend on a single
+/// lock
+const SHARDS: usize = 16;
+
+/// The approximate number of bytes used by each cached hash (the slot, plus its entry in the
+/// index)
+pub const ENTRY_SIZE: usize = size_of::<Slot>() + size_of::<((Element, Element), usize)>();
+
+/// The memory budget used by [`LruHashCache::default`] (256 MiB)
+pub const DEFAULT_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+/// A thread-safe cache with a bounded memory footprint
+///
+/// Unlike [`SimpleHashCache`], which keeps every hash it has ever seen, this cache holds at most
+/// `memory_budget` bytes of hashes (see [`ENTRY_SIZE`]). When it is full, the least recently used
+/// hashes are evicted, using the clock (second chance) approximation of LRU.
+///
+/// Like [`SimpleHashCache`], it is cheap to clone, and clones share the same underlying cache
+///
+/// ```rust
+/// # use smirk::*;
+/// # use smirk::hash_cache::*;
+/// // enough space for roughly 1000 hashes
+/// let cache = LruHashCache::with_memory_budget(1000 * ENTRY_SIZE);
+/// let mut tree = Tree::<64, i32, _>::new_with_cache(cache);
+///
+/// tree.insert(Element::new(1), 123).unwrap();
+///
+/// assert!(tree.cache().len() <= tree.cache().capacity());
+/// ```
+///
+/// [`SimpleHashCache`]: super::SimpleHashCache
+#[derive(Debug, Clone)]
+pub struct LruHashCache {
+    shards: Arc<[Mutex<Shard>]>,
+    shard_capacity: usize,
+    hasher: RandomState,
+    metrics: CacheMetrics,
+}
+
+impl Default for LruHashCache {
+    fn default() -> Self {
+        Self::with_memory_budget(DEFAULT_MEMORY_BUDGET)
+    }
+}
+
+impl HashCache for LruHashCache {
+    #[inline]
+    fn hash(&self, left: Element, right: Element) -> Element {
+        self.metrics.incr_hashes();
+
+        let shard = self.shard(left, right);
+
+        if let Some(result) = shard.lock().unwrap().get(left, right) {
+            self.metrics.incr_cache_hits();
+            return result;
+        }
+
+        self.metrics.incr_cache_misses();
+
+        // compute the hash without holding the lock, since it's much slower than the lookup
+        let result = hash_merge([left, right]);
+
+        if shard.lock().unwrap().insert(left, right, result) {
+            self.metrics.incr_evictions();
+        }
+
+        result
+    }
+}
+
+impl PersistentHashCache for LruHashCache {
+    fn provide_known_hashes(&mut self, hashes: Vec<KnownHash>) {
+        for KnownHash {
+            left,
+            right,
+            result,
+        } in hashes
+        {
+            let shard = self.shard(left, right);
+
+            if shard.lock().unwrap().insert(left, right, result) {
+                self.metrics.incr_evictions();
+            }
+        }
+    }
+
+    fn evict(&self, left: Element, right: Element) {
+        LruHashCache::evict(self, left, right);
+    }
+}
+
+impl LruHashCache {
+    /// Create a new, empty [`LruHashCache`] that uses at most `memory_budget` bytes
+    ///
+    /// The budget is split evenly into 16 shards, so a budget smaller than `16 * ENTRY_SIZE`
+    /// caches nothing
+    #[must_use]
+    pub fn with_memory_budget(memory_budget: usize) -> Self {
+        let shard_capacity = memory_budget / ENTRY_SIZE / SHARDS;
+
+        Self {
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(Shard::new(shard_capacity)))
+                .collect(),
+            shard_capacity,
+            hasher: RandomState::new(),
+            metrics: CacheMetrics::default(),
+        }
+    }
+
+    /// The maximum number of hashes this cache can hold
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.shard_capacity * SHARDS
+    }
+
+    /// The number of hashes currently in this cache
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().slots.len())
+            .sum()
+    }
+
+    /// Whether this cache contains no entries
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The approximate number of bytes used by the hashes in this cache
+    #[inline]
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        self.len() * ENTRY_SIZE
+    }
+
+    /// Remove the result of a hash from memory
+    ///
+    /// This is not counted as an eviction in [`CacheMetrics`]
+    #[inline]
+    pub fn evict(&self, left: Element, right: Element) {
+        self.shard(left, right).lock().unwrap().remove(left, right);
+    }
+
+    /// Remove all hashes from the cache
+    pub fn evict_all(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().unwrap().clear();
+        }
+    }
+
+    /// Get metrics for this cache
+    #[inline]
+    #[must_use]
+    pub fn metrics(&self) -> &CacheMetrics {
+        &self.metrics
+    }
+
+    fn shard(&self, left: Element, right: Element) -> &Mutex<Shard> {
+        let mut hasher = self.hasher.build_hasher();
+        (left, right).hash(&mut hasher);
+
+        let [byte, ..] = hasher.finish().to_le_bytes();
+        &self.shards[usize::from(byte) % SHARDS]
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    left: Element,
+    right: Element,
+    result: Element,
+    referenced: bool,
+}
+
+/// A fixed-capacity clock cache
+///
+/// Each slot has a "referenced" bit that is set when it is read. To evict, the hand sweeps over
+/// the slots, clearing referenced bits, until it finds a slot that hasn't been referenced since
+/// the last sweep
+#[derive(Debug)]
+struct Shard {
+    capacity: usize,
+    slots: Vec<Slot>,
+    index: HashMap<(Element, Element), usize>,
+    hand: usize,
+}
+
+impl Shard {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            slots: Vec::new(),
+            index: HashMap::new(),
+            hand: 0,
+        }
+    }
+
+    fn get(&mut self, left: Element, right: Element) -> Option<Element> {
+        let slot = &mut self.slots[*self.index.get(&(left, right))?];
+        slot.referenced = true;
+
+        Some(slot.result)
+    }
+
+    /// Returns `true` if another hash was evicted to make space
+    fn insert(&mut self, left: Element, right: Element, result: Element) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+
+        let slot = Slot {
+            left,
+            right,
+            result,
+            referenced: false,
+        };
+
+        if let Some(&i) = self.index.get(&(left, right)) {
+            self.slots[i] = slot;
+            return false;
+        }
+
+        if self.slots.len() < self.capacity {
+            self.index.insert((left, right), self.slots.len());
+            self.slots.push(slot);
+            return false;
+        }
+
+        while self.slots[self.hand].referenced {
+            self.slots[self.hand].referenced = false;
+            self.hand = (self.hand + 1) % self.slots.len();
+        }
+
+        let evicted = core::mem::replace(&mut self.slots[self.hand], slot);
+        self.index.remove(&(evicted.left, evicted.right));
+        self.index.insert((left, right), self.hand);
+        self.hand = (self.hand + 1) % self.slots.len();
+
+        true
+    }
+
+    fn remove(&mut self, left: Element, right: Element) {
+        let Some(i) = self.index.remove(&(left, right)) else {
+            return;
+        };
+
+        self.slots.swap_remove(i);
+
+        if let Some(moved) = self.slots.get(i) {
+            self.index.insert((moved.left, moved.right), i);
+        }
+
+        if self.hand >= self.slots.len() {
+            self.hand = 0;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.slots.clear();
+        self.index.clear();
+        self.hand = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::proptest;
+
+    use crate::{hash_cache::NoopHashCache, Batch, Tree};
+
+    use super::*;
+
+    #[test]
+    fn cache_is_bounded() {
+        let cache = LruHashCache::with_memory_budget(SHARDS * 4 * ENTRY_SIZE);
+        assert_eq!(cache.capacity(), SHARDS * 4);
+
+        for i in 0..1000 {
+            cache.hash(Element::new(i), Element::new(i));
+        }
+
+        assert_eq!(cache.len(), cache.capacity());
+        assert!(cache.memory_usage() <= SHARDS * 4 * ENTRY_SIZE);
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.cache_misses(), 1000);
+        assert_eq!(metrics.evictions(), 1000 - cache.capacity());
+    }
+
+    #[test]
+    fn referenced_hashes_survive_eviction() {
+        let mut shard = Shard::new(2);
+        shard.insert(Element::new(1), Element::new(1), Element::new(10));
+        shard.insert(Element::new(2), Element::new(2), Element::new(20));
+
+        assert_eq!(
+            shard.get(Element::new(1), Element::new(1)),
+            Some(Element::new(10))
+        );
+
+        assert!(shard.insert(Element::new(3), Element::new(3), Element::new(30)));
+
+        assert_eq!(
+            shard.get(Element::new(1), Element::new(1)),
+            Some(Element::new(10))
+        );
+        assert_eq!(shard.get(Element::new(2), Element::new(2)), None);
+    }
+
+    #[test]
+    fn remove_keeps_index_consistent() {
+        let mut shard = Shard::new(3);
+
+        for i in 1..=3 {
+            shard.insert(Element::new(i), Element::new(i), Element::new(i * 10));
+        }
+
+        shard.remove(Element::new(1), Element::new(1));
+
+        assert_eq!(shard.get(Element::new(1), Element::new(1)), None);
+        assert_eq!(
+            shard.get(Element::new(3), Element::new(3)),
+            Some(Element::new(30))
+        );
+        assert_eq!(shard.slots.len(), shard.index.len());
+    }
+
+    #[test]
+    fn zero_budget_caches_nothing() {
+        let cache = LruHashCache::with_memory_budget(0);
+        let hash = cache.hash(Element::new(1), Element::new(2));
+
+        assert_eq!(hash, hash_merge([Element::new(1), Element::new(2)]));
+        assert!(cache.is_empty());
+    }
+
+    #[proptest]
+    fn small_cache_gives_correct_root_hash(batch: Batch<16, i32>) {
+        let cache = LruHashCache::with_memory_budget(SHARDS * 2 * ENTRY_SIZE);
+
+        let mut tree = Tree::<16, i32, _>::new_with_cache(cache);
+        tree.insert_batch(batch.clone(), |_| {}, |_| {}).unwrap();
+
+        let mut expected = Tree::<16, i32, NoopHashCache>::new();
+        expected.insert_batch(batch, |_| {}, |_| {}).unwrap();
+
+        assert_eq!(tree.root_hash(), expected.root_hash());
+    }
+}
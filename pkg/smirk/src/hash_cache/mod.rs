@@ -3,8 +3,10 @@ use std::sync::Arc;
 use dashmap::{mapref::entry::Entry, DashMap};
 use zk_primitives::{hash_merge, Element};
 
+pub use self::lru::{LruHashCache, DEFAULT_MEMORY_BUDGET, ENTRY_SIZE};
 pub use self::metrics::CacheMetrics;
 
+mod lru;
 mod metrics;
 
 /// A known result of computation [`hash_merge([left, right])`][hash_merge]
@@ -46,6 +48,23 @@ pub trait HashCache: Sync + 'static {
     }
 }
 
+/// A [`HashCache`] that can be used by a [`Persistent`] tree
+///
+/// [`Persistent`] stores the hashes it computes in rocksdb, so it needs to be able to load those
+/// hashes into the cache, and to remove hashes that are no longer part of the tree
+///
+/// [`Persistent`]: crate::storage::Persistent
+pub trait PersistentHashCache: HashCache {
+    /// Provide a set of known hashes to this cache (e.g. those loaded from disk)
+    ///
+    /// Note that these hashes will not be validated - providing incorrect hashes will lead to
+    /// incorrect results
+    fn provide_known_hashes(&mut self, hashes: Vec<KnownHash>);
+
+    /// Remove the result of a hash from memory
+    fn evict(&self, left: Element, right: Element);
+}
+
 /// A ZST that does no caching - the default cache for [`Tree`]
 ///
 /// [`Tree`]: crate::Tree
@@ -90,6 +109,16 @@ impl HashCache for SimpleHashCache {
     }
 }
 
+impl PersistentHashCache for SimpleHashCache {
+    fn provide_known_hashes(&mut self, hashes: Vec<KnownHash>) {
+        SimpleHashCache::provide_known_hashes(self, hashes);
+    }
+
+    fn evict(&self, left: Element, right: Element) {
+        SimpleHashCache::evict(self, left, right);
+    }
+}
+
 impl SimpleHashCache {
     /// Create a new, empty [`SimpleHashCache`]
     #[inline]
@@ -80,5 +80,8 @@ mod tree;
 
 pub use batch::Batch;
 pub use hash::empty_tree_hash;
-pub use tree::{Collision, CollisionError, Path, Tree};
+pub use tree::{
+    Collision, CollisionError, CompressedPath, CompressedPathError, Diff, DiffError,
+    NonMembershipProof, Path, Snapshot, SubtreeId, Tree,
+};
 pub use zk_primitives::*;
@@ -8,16 +8,17 @@ use rocksdb::WriteBatch;
 use wire_message::WireMessage;
 
 use crate::{
+    hash_cache::PersistentHashCache,
     storage::format::{ValueFormat, ValueV2},
-    Batch,
+    Batch, Element,
 };
 
 use super::{
     format::{KeyFormat, KeyV2},
-    Error, Persistent,
+    lazy, Error, Persistent,
 };
 
-impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
+impl<const DEPTH: usize, V, C: PersistentHashCache> Persistent<DEPTH, V, C> {
     /// Insert a [`Batch`] into this [`Persistent`] tree
     ///
     /// ```rust
@@ -38,6 +39,8 @@ impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
     where
         V: BorshSerialize + BorshDeserialize + Send + Sync + 'static + Clone,
     {
+        self.check_writable()?;
+
         if batch.is_empty() {
             return Ok(());
         }
@@ -89,10 +92,100 @@ impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
             write_batch.put(key.to_bytes().unwrap(), value.to_bytes().unwrap());
         }
 
+        // the set of elements changed, so the node hashes stored by `Lazy` are out of date
+        lazy::invalidate(&mut write_batch);
+
         self.db.write(write_batch)?;
 
         // TODO: handle case where rocksdb fails with pending list
 
         Ok(())
     }
+
+    /// Remove multiple elements from this [`Persistent`] tree, returning the entries that were
+    /// present
+    ///
+    /// The elements are deleted from rocksdb along with any known hashes that are no longer part of
+    /// the tree, and those hashes are evicted from the cache
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// # use smirk::storage::*;
+    /// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
+    /// # let path = dir.path().join("db");
+    /// let mut persistent = Persistent::<64, ()>::new(&path).unwrap();
+    /// persistent.insert_batch(batch! { 1, 2, 3 }).unwrap();
+    ///
+    /// let removed = persistent.remove_batch([Element::new(1), Element::new(2)]).unwrap();
+    /// assert_eq!(removed.len(), 2);
+    ///
+    /// assert!(!persistent.tree().contains_element(&Element::new(1)));
+    /// assert!(!persistent.tree().contains_element(&Element::new(2)));
+    /// assert!(persistent.tree().contains_element(&Element::new(3)));
+    /// ```
+    pub fn remove_batch<I: IntoIterator<Item = Element>>(
+        &mut self,
+        elements: I,
+    ) -> Result<Vec<(Element, V)>, Error>
+    where
+        V: BorshSerialize + BorshDeserialize + Send + Sync + 'static + Clone,
+    {
+        self.check_writable()?;
+
+        let hash_changes = Arc::new(Mutex::new(HashMap::new()));
+        let removed = self.tree.remove_batch(
+            elements,
+            |(left, right)| {
+                hash_changes.lock().unwrap().insert((*left, *right), None);
+            },
+            |(left, right, result)| {
+                hash_changes
+                    .lock()
+                    .unwrap()
+                    .insert((*left, *right), Some(*result));
+            },
+        );
+
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        let (hashes_to_insert, hashes_to_remove): (Vec<_>, Vec<_>) = Arc::try_unwrap(hash_changes)
+            .unwrap()
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .partition(|(_hash, result)| result.is_some());
+
+        let mut write_batch = WriteBatch::default();
+
+        for (element, _) in &removed {
+            // the element could be stored under either the v1 or v2 key
+            let new_key = KeyFormat::V2(KeyV2::Element(*element));
+            write_batch.delete(new_key.to_bytes().unwrap());
+
+            let old_key = KeyFormat::V1(*element);
+            write_batch.delete(old_key.to_bytes().unwrap());
+        }
+
+        for ((left, right), _) in hashes_to_remove {
+            let key = KeyFormat::V2(KeyV2::KnownHash { left, right });
+            write_batch.delete(key.to_bytes().unwrap());
+
+            self.tree.cache().evict(left, right);
+        }
+
+        for ((left, right), result) in hashes_to_insert {
+            let key = KeyFormat::V2(KeyV2::KnownHash { left, right });
+            let value = ValueFormat::<V>::V2(ValueV2::KnownHash(result.unwrap()));
+            write_batch.put(key.to_bytes().unwrap(), value.to_bytes().unwrap());
+        }
+
+        // the set of elements changed, so the node hashes stored by `Lazy` are out of date
+        lazy::invalidate(&mut write_batch);
+
+        self.db.write(write_batch)?;
+
+        Ok(removed)
+    }
 }
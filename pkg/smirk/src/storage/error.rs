@@ -29,4 +29,10 @@ pub enum Error {
     /// Database consistency
     #[error("the database contained inconsistent data")]
     DatabaseConsistency,
+
+    /// The tree was opened with [`Persistent::open_secondary`], so it can't be changed
+    ///
+    /// [`Persistent::open_secondary`]: crate::storage::Persistent::open_secondary
+    #[error("the tree is a read-only secondary instance")]
+    ReadOnly,
 }
@@ -4,10 +4,16 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use wire_message::{wire_message, WireMessage};
 use zk_primitives::Element;
 
+/// The format of the keys of a [`Persistent`][super::Persistent] rocksdb database
+///
+/// Each key is stored with a [`ValueFormat`] value
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[wire_message]
-pub(super) enum KeyFormat {
+pub enum KeyFormat {
+    /// An element in the tree
     V1(Element),
+    /// An element in the tree, or a cached hash
     V2(KeyV2),
 }
 
@@ -31,15 +37,36 @@ impl WireMessage for KeyFormat {
 }
 
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub(super) enum KeyV2 {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// A [`KeyFormat::V2`] key
+pub enum KeyV2 {
+    /// An element in the tree, with [`ValueV2::Metadata`]
     Element(Element),
-    KnownHash { left: Element, right: Element },
+    /// The merge of two hashes, with [`ValueV2::KnownHash`]
+    KnownHash {
+        /// The left hash
+        left: Element,
+        /// The right hash
+        right: Element,
+    },
+    /// The position of a node hash stored by [`Lazy`][super::Lazy], with [`ValueV2::Node`]
+    Node {
+        /// The depth of the node
+        depth: u8,
+        /// The index of the node at its depth
+        index: Element,
+    },
 }
 
+/// The format of the values of a [`Persistent`][super::Persistent] rocksdb database, see
+/// [`KeyFormat`]
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[wire_message]
-pub(super) enum ValueFormat<T: Clone> {
+pub enum ValueFormat<T: Clone> {
+    /// The metadata of an element
     V1(Arc<T>),
+    /// The metadata of an element, or a cached hash
     V2(ValueV2<T>),
 }
 
@@ -66,7 +93,13 @@ where
 }
 
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub(super) enum ValueV2<V: Clone> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// A [`ValueFormat::V2`] value
+pub enum ValueV2<V: Clone> {
+    /// The metadata of an element
     Metadata(Arc<V>),
+    /// The result of merging two hashes
     KnownHash(Element),
+    /// The hash of a node
+    Node(Element),
 }
@@ -0,0 +1,109 @@
+use crate::{Element, Path, Snapshot};
+
+use super::Persistent;
+
+impl<const DEPTH: usize, V, C> Persistent<DEPTH, V, C> {
+    /// Set the number of versions to keep [`Snapshot`]s of
+    ///
+    /// This is `0` by default, meaning no history is kept. If `len` is smaller than the current
+    /// number of versions, the oldest versions are dropped
+    ///
+    /// History is only kept in memory, so it starts empty when a [`Persistent`] is loaded. Earlier
+    /// versions can be added back with [`Persistent::insert_version`]
+    pub fn set_history_len(&mut self, len: usize) {
+        self.history_len = len;
+        self.trim_history();
+    }
+
+    /// Record the current state of the tree as `version` (e.g. a block height)
+    ///
+    /// Versions are expected to increase. Committing a version that is not newer than the latest
+    /// committed version discards the history from that version onwards (e.g. after a rollback)
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// # use smirk::storage::*;
+    /// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
+    /// # let path = dir.path().join("db");
+    /// let mut persistent = Persistent::<64, ()>::new(&path).unwrap();
+    /// persistent.set_history_len(2);
+    ///
+    /// persistent.insert(Element::new(1), ()).unwrap();
+    /// persistent.commit_version(1);
+    /// let root_1 = persistent.tree().root_hash();
+    ///
+    /// persistent.insert(Element::new(2), ()).unwrap();
+    /// persistent.commit_version(2);
+    ///
+    /// assert_eq!(persistent.root_hash_at(1), Some(root_1));
+    ///
+    /// let path = persistent.path_for_at(1, Element::new(1)).unwrap();
+    /// assert_eq!(path.actual_root_hash(), root_1);
+    ///
+    /// persistent.insert(Element::new(3), ()).unwrap();
+    /// persistent.commit_version(3);
+    ///
+    /// // only the last 2 versions are kept
+    /// assert_eq!(persistent.root_hash_at(1), None);
+    /// ```
+    pub fn commit_version(&mut self, version: u64) {
+        while matches!(self.history.back(), Some((latest, _)) if *latest >= version) {
+            self.history.pop_back();
+        }
+
+        self.history.push_back((version, self.tree.snapshot()));
+        self.trim_history();
+    }
+
+    /// Record `snapshot` as the state of the tree at `version`, for versions that weren't
+    /// committed while the tree was at them (e.g. versions before the tree was loaded, rebuilt
+    /// with [`Snapshot::without`])
+    ///
+    /// Unlike [`Persistent::commit_version`], this doesn't discard newer versions. If `version` is
+    /// already in the history, it is left as it is. If the history is full, the oldest version is
+    /// dropped, which can be `version` itself
+    pub fn insert_version(&mut self, version: u64, snapshot: Snapshot<DEPTH>) {
+        if let Err(index) = self
+            .history
+            .binary_search_by_key(&version, |(version, _)| *version)
+        {
+            self.history.insert(index, (version, snapshot));
+            self.trim_history();
+        }
+    }
+
+    /// The [`Snapshot`] of the tree at `version`, if it is still in the history
+    #[must_use]
+    pub fn snapshot_at(&self, version: u64) -> Option<&Snapshot<DEPTH>> {
+        let index = self
+            .history
+            .binary_search_by_key(&version, |(version, _)| *version)
+            .ok()?;
+
+        Some(&self.history[index].1)
+    }
+
+    /// The root hash of the tree at `version`, if it is still in the history
+    #[must_use]
+    pub fn root_hash_at(&self, version: u64) -> Option<Element> {
+        self.snapshot_at(version).map(Snapshot::root_hash)
+    }
+
+    /// A [`Path`] for `element` in the tree at `version`, if it is still in the history
+    #[must_use]
+    pub fn path_for_at(&self, version: u64, element: Element) -> Option<Path<DEPTH>> {
+        self.snapshot_at(version)
+            .map(|snapshot| snapshot.path_for(element))
+    }
+
+    /// The versions that are currently in the history, oldest first
+    pub fn versions(&self) -> impl Iterator<Item = u64> + '_ {
+        self.history.iter().map(|(version, _)| *version)
+    }
+
+    fn trim_history(&mut self) {
+        while self.history.len() > self.history_len {
+            self.history.pop_front();
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use core::fmt::Debug;
+use std::{collections::HashSet, path::Path};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use rayon::prelude::*;
+use rocksdb::{Options, WriteBatch, DB};
+use wire_message::WireMessage;
+
+use crate::{hash_cache::KnownHash, hash_merge, Batch, Element, Tree};
+
+use super::{
+    format::{KeyFormat, KeyV2, ValueFormat, ValueV2},
+    lazy,
+    load::{entries, RocksbEntry},
+    Error,
+};
+
+/// The result of [`check_integrity`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// The root hash of the elements in the database, computed without using any stored hashes
+    pub root_hash: Element,
+    /// The number of elements in the database
+    pub elements: usize,
+    /// Stored known hashes whose result is not the hash of their inputs
+    ///
+    /// These are the dangerous ones, since loading the tree would use them and produce the wrong
+    /// root hash
+    pub incorrect_hashes: Vec<KnownHash>,
+    /// Stored known hashes that are correct, but aren't part of the tree
+    pub stale_hashes: Vec<KnownHash>,
+    /// Known hashes that are part of the tree, but aren't stored
+    pub missing_hashes: Vec<KnownHash>,
+    /// The number of entries in the database that couldn't be decoded
+    pub unreadable_entries: usize,
+}
+
+impl IntegrityReport {
+    /// Returns `true` if the stored known hashes exactly match the elements, and every entry could
+    /// be decoded
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        self.incorrect_hashes.is_empty()
+            && self.stale_hashes.is_empty()
+            && self.missing_hashes.is_empty()
+            && self.unreadable_entries == 0
+    }
+}
+
+/// Check that the known hashes stored in the database at `path` are consistent with the elements
+///
+/// Every hash is recomputed from the elements, so this is much slower than
+/// [`Persistent::load`][super::Persistent::load]. The database is opened read-only, so it is
+/// never modified (or created, if it doesn't exist). To fix any inconsistencies, use [`repair`]
+///
+/// ```rust
+/// # use smirk::*;
+/// # use smirk::storage::*;
+/// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
+/// # let path = dir.path().join("db");
+/// let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+/// persistent.insert_batch(batch! { 1 => 10, 2 => 20 }).unwrap();
+/// let root_hash = persistent.tree().root_hash();
+/// drop(persistent);
+///
+/// let report = check_integrity::<64, i32, _>(&path).unwrap();
+///
+/// assert!(report.is_consistent());
+/// assert_eq!(report.root_hash, root_hash);
+/// assert_eq!(report.elements, 2);
+/// ```
+pub fn check_integrity<const DEPTH: usize, V, P: AsRef<Path>>(
+    path: P,
+) -> Result<IntegrityReport, Error>
+where
+    V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+{
+    let db = DB::open_for_read_only(&Options::default(), path, false)?;
+    inspect::<DEPTH, V>(&db)
+}
+
+/// Fix the inconsistencies found by [`check_integrity`] in the database at `path`
+///
+/// Incorrect and stale known hashes are deleted, and missing known hashes are written, so that
+/// the stored hashes exactly match the elements. The elements themselves are never changed, and
+/// unreadable entries are left in place
+///
+/// Returns the report from before the repair
+pub fn repair<const DEPTH: usize, V, P: AsRef<Path>>(path: P) -> Result<IntegrityReport, Error>
+where
+    V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+{
+    // a missing database is an error, rather than an empty one to repair
+    let mut options = Options::default();
+    options.create_if_missing(false);
+
+    let db = DB::open(&options, path)?;
+    let report = inspect::<DEPTH, V>(&db)?;
+
+    let mut write_batch = WriteBatch::default();
+
+    for hash in report.incorrect_hashes.iter().chain(&report.stale_hashes) {
+        let key = KeyFormat::V2(KeyV2::KnownHash {
+            left: hash.left,
+            right: hash.right,
+        });
+        write_batch.delete(key.to_bytes()?);
+    }
+
+    for hash in &report.missing_hashes {
+        let key = KeyFormat::V2(KeyV2::KnownHash {
+            left: hash.left,
+            right: hash.right,
+        });
+        let value = ValueFormat::<V>::V2(ValueV2::KnownHash(hash.result));
+        write_batch.put(key.to_bytes()?, value.to_bytes()?);
+    }
+
+    lazy::invalidate(&mut write_batch);
+
+    db.write(write_batch)?;
+
+    Ok(report)
+}
+
+fn inspect<const DEPTH: usize, V>(db: &DB) -> Result<IntegrityReport, Error>
+where
+    V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+{
+    let mut stored_hashes = Vec::new();
+    let mut batch = Batch::new();
+    let mut unreadable_entries = 0;
+
+    for entry in entries::<V>(db) {
+        match entry {
+            Ok(RocksbEntry::SmirkKV { key, value }) => batch.insert(key, value)?,
+            Ok(RocksbEntry::KnownHash(hash)) => stored_hashes.push(hash),
+            Ok(RocksbEntry::Node { .. }) => {}
+            Err(_) => unreadable_entries += 1,
+        }
+    }
+
+    // the tree has no hash cache, so every hash is computed from scratch
+    let mut tree = Tree::<DEPTH, V>::new();
+    tree.insert_batch(batch, |_| {}, |_| {})?;
+
+    let expected_hashes: HashSet<_> = tree.known_hashes().into_iter().collect();
+    let stored_set: HashSet<_> = stored_hashes.iter().copied().collect();
+
+    let (mut incorrect_hashes, mut stale_hashes): (Vec<_>, Vec<_>) = stored_hashes
+        .into_par_iter()
+        .filter(|hash| !expected_hashes.contains(hash))
+        .partition(|hash| hash_merge([hash.left, hash.right]) != hash.result);
+
+    let mut missing_hashes: Vec<_> = expected_hashes
+        .into_iter()
+        .filter(|hash| !stored_set.contains(hash))
+        .collect();
+
+    incorrect_hashes.sort_unstable();
+    stale_hashes.sort_unstable();
+    missing_hashes.sort_unstable();
+
+    Ok(IntegrityReport {
+        root_hash: tree.root_hash(),
+        elements: tree.len(),
+        incorrect_hashes,
+        stale_hashes,
+        missing_hashes,
+        unreadable_entries,
+    })
+}
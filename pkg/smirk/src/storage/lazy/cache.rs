@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::Element;
+
+use super::Position;
+
+/// A bounded cache of node hashes
+///
+/// Entries are split into two generations. New entries go into `recent`, and when `recent` is
+/// full, it replaces `old` (dropping whatever was in `old`). Hits in `old` are promoted back into
+/// `recent`, so frequently used nodes (e.g. those near the root) stay cached. This approximates an
+/// LRU cache without any per-entry bookkeeping, and never holds more than `capacity` entries
+#[derive(Debug)]
+pub(super) struct NodeCache {
+    capacity: usize,
+    recent: HashMap<Position, Element>,
+    old: HashMap<Position, Element>,
+}
+
+impl NodeCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            recent: HashMap::new(),
+            old: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, position: &Position) -> Option<Element> {
+        if let Some(hash) = self.recent.get(position) {
+            return Some(*hash);
+        }
+
+        let hash = self.old.remove(position)?;
+        self.insert(*position, hash);
+
+        Some(hash)
+    }
+
+    pub fn insert(&mut self, position: Position, hash: Element) {
+        let generation_len = self.capacity / 2;
+
+        if generation_len == 0 {
+            return;
+        }
+
+        if self.recent.len() >= generation_len {
+            self.old = core::mem::take(&mut self.recent);
+        }
+
+        self.recent.insert(position, hash);
+    }
+
+    pub fn remove(&mut self, position: &Position) {
+        self.recent.remove(position);
+        self.old.remove(position);
+    }
+
+    pub fn clear(&mut self) {
+        self.recent.clear();
+        self.old.clear();
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.recent.len() + self.old.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(index: u64) -> Position {
+        Position {
+            depth: 10,
+            index: Element::new(index),
+        }
+    }
+
+    #[test]
+    fn cache_is_bounded() {
+        let mut cache = NodeCache::new(10);
+
+        for i in 0..100 {
+            cache.insert(position(i), Element::new(i));
+            assert!(cache.len() <= 10);
+        }
+
+        assert_eq!(cache.get(&position(99)), Some(Element::new(99)));
+        assert_eq!(cache.get(&position(0)), None);
+    }
+
+    #[test]
+    fn frequently_used_entries_stay_cached() {
+        let mut cache = NodeCache::new(10);
+        cache.insert(position(0), Element::new(0));
+
+        for i in 1..100 {
+            cache.insert(position(i), Element::new(i));
+            assert_eq!(cache.get(&position(0)), Some(Element::new(0)));
+        }
+    }
+
+    #[test]
+    fn zero_capacity_caches_nothing() {
+        let mut cache = NodeCache::new(0);
+        cache.insert(position(0), Element::new(0));
+
+        assert_eq!(cache.get(&position(0)), None);
+        assert_eq!(cache.len(), 0);
+    }
+}
@@ -0,0 +1,486 @@
+use core::{fmt::Debug, marker::PhantomData};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path as FsPath,
+    sync::{Arc, Mutex},
+};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use rayon::prelude::*;
+use rocksdb::{WriteBatch, DB};
+use wire_message::WireMessage;
+
+use crate::{
+    empty_tree_hash, hash_merge, tree::StructName, Batch, Collision, CollisionError, Element, Path,
+};
+
+use self::cache::NodeCache;
+
+use super::{
+    format::{KeyFormat, KeyV2, ValueFormat, ValueV2},
+    load::{entries, RocksbEntry},
+    Error,
+};
+
+mod cache;
+
+/// The number of node hashes kept in memory by [`Lazy::load`]
+pub const DEFAULT_CACHE_CAPACITY: usize = 1 << 16;
+
+/// A sparse Merkle tree that lives in a rocksdb instance, and only reads the parts of the tree it
+/// needs
+///
+/// Unlike [`Persistent`], which loads every element into an in-memory [`Tree`] on startup, a
+/// [`Lazy`] tree stores the hash of every non-empty node of the tree in rocksdb, and fetches them
+/// on demand. Loading a [`Lazy`] tree only reads the root hash, so it takes roughly constant time
+/// regardless of the size of the tree. Recently used hashes are kept in a bounded in-memory cache
+///
+/// The trade-off is that reads can hit the disk, and every insert or remove writes `DEPTH` node
+/// hashes per element
+///
+/// ```rust
+/// # use smirk::*;
+/// # use smirk::storage::*;
+/// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
+/// # let path = dir.path().join("db");
+/// let mut lazy = Lazy::<64, i32>::load(&path).unwrap();
+/// lazy.insert_batch(batch! { 1 => 10, 2 => 20 }).unwrap();
+///
+/// let tree: Tree<64, i32> = smirk! { 1 => 10, 2 => 20 };
+/// assert_eq!(lazy.root_hash(), tree.root_hash());
+///
+/// drop(lazy);
+///
+/// let lazy = Lazy::<64, i32>::load(&path).unwrap();
+/// assert_eq!(lazy.root_hash(), tree.root_hash());
+/// assert_eq!(lazy.get(Element::new(1)).unwrap(), Some(10));
+///
+/// let path = lazy.path_for(Element::new(2)).unwrap();
+/// assert!(path.proves(Element::new(2)));
+/// ```
+///
+/// [`Lazy`] and [`Persistent`] use the same format for elements and values, so they can open the
+/// same database (though not at the same time). Writes made by [`Persistent`] invalidate the node
+/// hashes, and the next [`Lazy::load`] rebuilds them from the elements, which requires reading the
+/// whole database once
+///
+/// The node still uses [`Persistent`] for its notes tree, since it relies on in-memory
+/// [`Snapshot`]s for the tree history, and on secondary instances for replicas, neither of which
+/// [`Lazy`] supports
+///
+/// [`Persistent`]: super::Persistent
+/// [`Snapshot`]: crate::Snapshot
+/// [`Tree`]: crate::Tree
+pub struct Lazy<const DEPTH: usize, V> {
+    db: DB,
+    root_hash: Element,
+    cache: Mutex<NodeCache>,
+    _values: PhantomData<fn() -> V>,
+}
+
+/// The position of a node in the tree
+///
+/// `depth` is the distance from the root (so the root has depth `0` and leaves have depth
+/// `DEPTH - 1`), and `index` is the position of the node within that layer of the tree, which is
+/// the top `depth` bits of the `DEPTH - 1` least significant bits of any element below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Position {
+    depth: u8,
+    index: Element,
+}
+
+impl Position {
+    const ROOT: Self = Self {
+        depth: 0,
+        index: Element::ZERO,
+    };
+
+    fn leaf<const DEPTH: usize>(element: Element) -> Self {
+        let depth = u8::try_from(DEPTH - 1).expect("DEPTH must be at most 256");
+        let mask = (Element::ONE << depth) - 1u8;
+
+        Self {
+            depth,
+            index: element & mask,
+        }
+    }
+
+    fn parent(self) -> Self {
+        Self {
+            depth: self.depth - 1,
+            index: self.index >> 1u8,
+        }
+    }
+
+    fn sibling(self) -> Self {
+        Self {
+            depth: self.depth,
+            index: self.index ^ 1u8,
+        }
+    }
+
+    fn children(self) -> [Self; 2] {
+        let left = self.index << 1u8;
+
+        [left, left | 1u8].map(|index| Self {
+            depth: self.depth + 1,
+            index,
+        })
+    }
+
+    fn empty_hash<const DEPTH: usize>(self) -> Element {
+        empty_tree_hash(DEPTH - usize::from(self.depth))
+    }
+
+    fn key(self) -> KeyFormat {
+        KeyFormat::V2(KeyV2::Node {
+            depth: self.depth,
+            index: self.index,
+        })
+    }
+}
+
+/// Mark the node hashes of a [`Lazy`] tree as out of date
+///
+/// This is called by [`Persistent`][super::Persistent] whenever it changes the set of elements
+pub(super) fn invalidate(write_batch: &mut WriteBatch) {
+    write_batch.delete(Position::ROOT.key().to_bytes().unwrap());
+}
+
+impl<const DEPTH: usize, V> Lazy<DEPTH, V>
+where
+    V: BorshSerialize + BorshDeserialize + Debug + Clone + Send + Sync + 'static,
+{
+    /// Open a [`Lazy`] tree backed by a rocksdb database located at `path`, creating it if it
+    /// doesn't exist
+    ///
+    /// This keeps up to [`DEFAULT_CACHE_CAPACITY`] node hashes in memory. To change this, use
+    /// [`Lazy::load_with_cache_capacity`]
+    pub fn load<P: AsRef<FsPath>>(path: P) -> Result<Self, Error> {
+        Self::load_with_cache_capacity(path, DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Open a [`Lazy`] tree backed by a rocksdb database located at `path`, keeping up to
+    /// `cache_capacity` node hashes in memory
+    ///
+    /// If the database was last written by [`Persistent`][super::Persistent], the node hashes are
+    /// rebuilt first, which requires reading every element
+    pub fn load_with_cache_capacity<P: AsRef<FsPath>>(
+        path: P,
+        cache_capacity: usize,
+    ) -> Result<Self, Error> {
+        let db = DB::open_default(path)?;
+
+        let mut lazy = Self {
+            db,
+            root_hash: empty_tree_hash(DEPTH),
+            cache: Mutex::new(NodeCache::new(cache_capacity)),
+            _values: PhantomData,
+        };
+
+        match lazy.stored_hash(Position::ROOT)? {
+            Some(root_hash) => lazy.root_hash = root_hash,
+            None => lazy.rebuild()?,
+        }
+
+        Ok(lazy)
+    }
+
+    /// Get a reference to the rocksdb instance
+    #[inline]
+    #[must_use]
+    pub fn db(&self) -> &DB {
+        &self.db
+    }
+
+    /// The root hash of the tree
+    #[inline]
+    #[must_use]
+    pub fn root_hash(&self) -> Element {
+        self.root_hash
+    }
+
+    /// Returns `true` if the tree contains `element`
+    pub fn contains_element(&self, element: Element) -> Result<bool, Error> {
+        if element == Element::NULL_HASH {
+            return Ok(false);
+        }
+
+        Ok(self.node_hash(Position::leaf::<DEPTH>(element))? == element)
+    }
+
+    /// Get the value associated with `element`, if it is in the tree
+    pub fn get(&self, element: Element) -> Result<Option<V>, Error> {
+        let keys = [
+            KeyFormat::V2(KeyV2::Element(element)),
+            KeyFormat::V1(element),
+        ];
+
+        for key in keys {
+            let Some(bytes) = self.db.get(key.to_bytes()?)? else {
+                continue;
+            };
+
+            return match ValueFormat::<V>::from_bytes(&bytes)? {
+                ValueFormat::V1(value) | ValueFormat::V2(ValueV2::Metadata(value)) => Ok(Some(
+                    Arc::try_unwrap(value).unwrap_or_else(|value| (*value).clone()),
+                )),
+                ValueFormat::V2(_) => Err(Error::DatabaseConsistency),
+            };
+        }
+
+        Ok(None)
+    }
+
+    /// Generate a [`Path`] for `element`
+    ///
+    /// See [`Tree::path_for`][crate::Tree::path_for] for more details
+    pub fn path_for(&self, element: Element) -> Result<Path<DEPTH>, Error> {
+        let mut siblings = [Element::NULL_HASH; DEPTH];
+        let mut position = Position::leaf::<DEPTH>(element);
+
+        for sibling in &mut siblings[..DEPTH - 1] {
+            *sibling = self.node_hash(position.sibling())?;
+            position = position.parent();
+        }
+
+        siblings[DEPTH - 1] = element;
+
+        Ok(Path {
+            siblings,
+            root_hash: self.root_hash,
+        })
+    }
+
+    /// Insert an element and a value into the tree
+    ///
+    /// If you are inserting many elements, use [`Lazy::insert_batch`], which only writes each
+    /// changed node once
+    pub fn insert(&mut self, element: Element, value: V) -> Result<(), Error> {
+        self.insert_batch(crate::batch! { element => value })
+    }
+
+    /// Insert a [`Batch`] into the tree
+    ///
+    /// Like [`Tree::insert_batch`][crate::Tree::insert_batch], this fails without changing the
+    /// tree if any element collides with an element already in the tree
+    pub fn insert_batch(&mut self, batch: Batch<DEPTH, V>) -> Result<(), Error> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let mut collisions = CollisionError::new();
+        let mut leaves = BTreeMap::new();
+
+        for element in batch.elements() {
+            let position = Position::leaf::<DEPTH>(element);
+            let in_tree = self.node_hash(position)?;
+
+            if in_tree != Element::NULL_HASH {
+                collisions.push(Collision {
+                    in_tree,
+                    inserted: element,
+                    depth: DEPTH,
+                    struct_name: StructName::Tree,
+                });
+            }
+
+            leaves.insert(position.index, element);
+        }
+
+        if !collisions.is_empty() {
+            return Err(collisions.into());
+        }
+
+        let mut write_batch = WriteBatch::default();
+
+        for (element, value) in batch.entries() {
+            let key = KeyFormat::V2(KeyV2::Element(*element));
+            let value = ValueFormat::V2(ValueV2::Metadata(Arc::new(value.clone())));
+            write_batch.put(key.to_bytes()?, value.to_bytes()?);
+
+            // make sure we don't end up with the v1 and v2 key for the same element at the same
+            // time
+            write_batch.delete(KeyFormat::V1(*element).to_bytes()?);
+        }
+
+        let root_hash = self.update_hashes(leaves, &mut write_batch)?;
+
+        self.db.write(write_batch)?;
+        self.root_hash = root_hash;
+
+        Ok(())
+    }
+
+    /// Remove multiple elements from the tree, returning the entries that were present
+    ///
+    /// Elements that aren't in the tree are ignored
+    pub fn remove_batch<I: IntoIterator<Item = Element>>(
+        &mut self,
+        elements: I,
+    ) -> Result<Vec<(Element, V)>, Error> {
+        let mut removed = Vec::new();
+        let mut leaves = BTreeMap::new();
+        let mut write_batch = WriteBatch::default();
+
+        for element in elements {
+            let position = Position::leaf::<DEPTH>(element);
+
+            if leaves.contains_key(&position.index) || !self.contains_element(element)? {
+                continue;
+            }
+
+            let value = self.get(element)?.ok_or(Error::DatabaseConsistency)?;
+
+            write_batch.delete(KeyFormat::V2(KeyV2::Element(element)).to_bytes()?);
+            write_batch.delete(KeyFormat::V1(element).to_bytes()?);
+
+            leaves.insert(position.index, Element::NULL_HASH);
+            removed.push((element, value));
+        }
+
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        let root_hash = self.update_hashes(leaves, &mut write_batch)?;
+
+        self.db.write(write_batch)?;
+        self.root_hash = root_hash;
+
+        Ok(removed)
+    }
+
+    /// Recalculate the hashes of every ancestor of `leaves` (a map of leaf index to the new leaf
+    /// hash), adding the changes to `write_batch` and returning the new root hash
+    ///
+    /// `leaves` must not be empty
+    ///
+    /// Only one layer of the tree is kept in memory at a time, and the hashes of each layer are
+    /// calculated in parallel
+    fn update_hashes(
+        &self,
+        leaves: BTreeMap<Element, Element>,
+        write_batch: &mut WriteBatch,
+    ) -> Result<Element, Error> {
+        let mut depth = u8::try_from(DEPTH - 1).expect("DEPTH must be at most 256");
+        let mut layer = leaves;
+
+        loop {
+            for (&index, &hash) in &layer {
+                self.put_node(write_batch, Position { depth, index }, hash)?;
+            }
+
+            if depth == 0 {
+                return Ok(layer[&Element::ZERO]);
+            }
+
+            let parents: BTreeSet<_> = layer.keys().map(|index| *index >> 1u8).collect();
+
+            layer = parents
+                .into_par_iter()
+                .map(|index| -> Result<_, Error> {
+                    let parent = Position {
+                        depth: depth - 1,
+                        index,
+                    };
+
+                    // unchanged children are read from the cache or rocksdb
+                    let child_hash = |child: Position| match layer.get(&child.index) {
+                        Some(hash) => Ok(*hash),
+                        None => self.node_hash(child),
+                    };
+
+                    let [left, right] = parent.children().map(child_hash);
+
+                    Ok((index, hash_merge([left?, right?])))
+                })
+                .collect::<Result<_, Error>>()?;
+
+            depth -= 1;
+        }
+    }
+
+    /// Rebuild every node hash from the elements in the database
+    fn rebuild(&mut self) -> Result<(), Error> {
+        let mut stale_nodes = WriteBatch::default();
+        let mut leaves = BTreeMap::new();
+
+        for entry in entries::<V>(&self.db) {
+            match entry? {
+                RocksbEntry::SmirkKV { key, .. } => {
+                    leaves.insert(Position::leaf::<DEPTH>(key).index, key);
+                }
+                RocksbEntry::Node { depth, index } => {
+                    stale_nodes.delete(Position { depth, index }.key().to_bytes()?);
+                }
+                RocksbEntry::KnownHash(_) => {}
+            }
+        }
+
+        // with the old nodes gone, every non-empty node is an ancestor of one of `leaves`
+        self.db.write(stale_nodes)?;
+        self.cache.lock().unwrap().clear();
+
+        let mut write_batch = WriteBatch::default();
+        let root_hash = match leaves.is_empty() {
+            true => {
+                let root_hash = empty_tree_hash(DEPTH);
+                self.put_node(&mut write_batch, Position::ROOT, root_hash)?;
+                root_hash
+            }
+            false => self.update_hashes(leaves, &mut write_batch)?,
+        };
+
+        self.db.write(write_batch)?;
+        self.root_hash = root_hash;
+
+        Ok(())
+    }
+
+    fn node_hash(&self, position: Position) -> Result<Element, Error> {
+        if let Some(hash) = self.cache.lock().unwrap().get(&position) {
+            return Ok(hash);
+        }
+
+        let hash = self
+            .stored_hash(position)?
+            .unwrap_or_else(|| position.empty_hash::<DEPTH>());
+
+        self.cache.lock().unwrap().insert(position, hash);
+
+        Ok(hash)
+    }
+
+    fn stored_hash(&self, position: Position) -> Result<Option<Element>, Error> {
+        let Some(bytes) = self.db.get(position.key().to_bytes()?)? else {
+            return Ok(None);
+        };
+
+        match ValueFormat::<V>::from_bytes(&bytes)? {
+            ValueFormat::V2(ValueV2::Node(hash)) => Ok(Some(hash)),
+            _ => Err(Error::DatabaseConsistency),
+        }
+    }
+
+    fn put_node(
+        &self,
+        write_batch: &mut WriteBatch,
+        position: Position,
+        hash: Element,
+    ) -> Result<(), Error> {
+        let key = position.key().to_bytes()?;
+
+        // empty subtrees aren't stored, except for the root, which marks the hashes as up to date
+        if hash == position.empty_hash::<DEPTH>() && position != Position::ROOT {
+            write_batch.delete(key);
+        } else {
+            let value = ValueFormat::<V>::V2(ValueV2::Node(hash));
+            write_batch.put(key, value.to_bytes()?);
+        }
+
+        self.cache.lock().unwrap().remove(&position);
+
+        Ok(())
+    }
+}
@@ -7,7 +7,7 @@ use wire_message::WireMessage;
 use zk_primitives::Element;
 
 use crate::{
-    hash_cache::{KnownHash, SimpleHashCache},
+    hash_cache::{KnownHash, PersistentHashCache},
     storage::format::{KeyV2, ValueFormat},
     Batch, Tree,
 };
@@ -17,9 +17,10 @@ use super::{
     Error,
 };
 
-pub(super) fn load_tree<const DEPTH: usize, V>(
+pub(super) fn load_tree<const DEPTH: usize, V, C: PersistentHashCache>(
     db: &DB,
-) -> Result<Tree<DEPTH, V, SimpleHashCache>, Error>
+    mut cache: C,
+) -> Result<Tree<DEPTH, V, C>, Error>
 where
     V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
 {
@@ -30,15 +31,15 @@ where
         match entry {
             Ok(RocksbEntry::KnownHash(hash)) => known_hashes.push(hash),
             Ok(RocksbEntry::SmirkKV { key, value }) => smirk_kv.push((key, value)),
+            // the in-memory tree computes its own hashes, so the lazy index isn't needed
+            Ok(RocksbEntry::Node { .. }) => {}
             Err(err) => return Err(err),
         }
     }
 
-    let mut cache = SimpleHashCache::new();
-
     cache.provide_known_hashes(known_hashes);
 
-    let mut smirk = Tree::<DEPTH, V, SimpleHashCache>::new_with_cache(cache);
+    let mut smirk = Tree::<DEPTH, V, C>::new_with_cache(cache);
 
     let mut batch = Batch::new();
     for (key, value) in smirk_kv {
@@ -81,6 +82,10 @@ where
                     right,
                     result,
                 })),
+                // a node hash used by `Lazy`
+                (KeyFormat::V2(KeyV2::Node { depth, index }), ValueFormat::V2(ValueV2::Node(_))) => {
+                    Ok(RocksbEntry::Node { depth, index })
+                }
                 // Any other case shouldn't be possible
                 _ => Err(Error::DatabaseConsistency),
             }
@@ -93,4 +98,6 @@ pub(crate) enum RocksbEntry<V> {
     SmirkKV { key: Element, value: V },
     /// A precomputed hash merge
     KnownHash(KnownHash),
+    /// The position of a node hash stored by [`Lazy`][super::Lazy]
+    Node { depth: u8, index: Element },
 }
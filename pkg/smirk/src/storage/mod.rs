@@ -1,17 +1,30 @@
 use core::fmt::Debug;
-use std::path::Path;
+use std::{collections::VecDeque, path::Path};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use rocksdb::DB;
 
 pub use error::Error;
+pub use format::{KeyFormat, KeyV2, ValueFormat, ValueV2};
+pub use integrity::{check_integrity, repair, IntegrityReport};
+pub use lazy::{Lazy, DEFAULT_CACHE_CAPACITY};
+pub use secondary::PrimaryChanges;
 
-use crate::{hash_cache::SimpleHashCache, Element, Tree};
+use crate::{
+    hash_cache::{PersistentHashCache, SimpleHashCache},
+    Element, Snapshot, Tree,
+};
 
 mod batch;
 mod error;
+// `#[wire_message]` adds undocumented `try_as_*` methods to the formats
+#[allow(missing_docs)]
 mod format;
+mod history;
+mod integrity;
+mod lazy;
 mod load;
+mod secondary;
 mod store;
 
 #[cfg(test)]
@@ -19,15 +32,29 @@ mod tests;
 
 /// A wrapper around [`Tree`] that persists data to a rocksdb instance
 ///
+/// By default, hashes are cached in a [`SimpleHashCache`], which is never cleared. To bound the
+/// memory used by the cache, use a different [`PersistentHashCache`] (e.g.
+/// [`LruHashCache`][crate::hash_cache::LruHashCache]) with [`Persistent::new_with_cache`] or
+/// [`Persistent::load_with_cache`]
+///
 /// ```rust
 /// # use smirk::*;
 /// # use smirk::storage::*;
 /// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
 /// # let path = dir.path().join("db");
 /// ```
-pub struct Persistent<const DEPTH: usize, V> {
-    tree: Tree<DEPTH, V, SimpleHashCache>,
+pub struct Persistent<const DEPTH: usize, V, C = SimpleHashCache> {
+    tree: Tree<DEPTH, V, C>,
     db: DB,
+    /// Snapshots of the tree at recently committed versions, oldest first
+    history: VecDeque<(u64, Snapshot<DEPTH>)>,
+    /// The maximum number of snapshots to keep in `history`
+    history_len: usize,
+    /// Whether `db` is a secondary instance opened by [`Persistent::open_secondary`], which
+    /// can't be written to
+    secondary: bool,
+    /// The rocksdb sequence number the tree of a secondary instance has been caught up to
+    caught_up_to: u64,
 }
 
 impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
@@ -44,10 +71,7 @@ impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
     /// println!("{}", persistent.tree().root_hash());
     /// ```
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-        let db = DB::open_default(path)?;
-        let tree = Tree::new();
-
-        Ok(Self { tree, db })
+        Self::new_with_cache(path, SimpleHashCache::new())
     }
 
     /// Load a [`Persistent`] [`Tree`] from a rocksdb database located at `path`
@@ -70,10 +94,49 @@ impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
     where
         V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
     {
+        Self::load_with_cache(path, SimpleHashCache::new())
+    }
+}
+
+impl<const DEPTH: usize, V, C: PersistentHashCache> Persistent<DEPTH, V, C> {
+    /// Create a new, empty [`Persistent`] [`Tree`] backed by a rocksdb instance at `path`, which
+    /// uses `cache` to cache hashes
+    pub fn new_with_cache<P: AsRef<Path>>(path: P, cache: C) -> Result<Self, Error> {
         let db = DB::open_default(path)?;
-        let tree = load::load_tree(&db)?;
+        let tree = Tree::new_with_cache(cache);
 
-        Ok(Self { tree, db })
+        Ok(Self::from_parts(tree, db))
+    }
+
+    /// Load a [`Persistent`] [`Tree`] from a rocksdb database located at `path`, which uses
+    /// `cache` to cache hashes
+    ///
+    /// The known hashes stored in the database are provided to `cache` before the tree is built.
+    /// If the cache can't hold all of them, the rest are recomputed, which makes loading slower
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// # use smirk::hash_cache::*;
+    /// # use smirk::storage::*;
+    /// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
+    /// # let path = dir.path().join("db");
+    /// let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+    /// persistent.insert(Element::ONE, 123).unwrap();
+    /// persistent.persist_hashes().unwrap();
+    /// drop(persistent);
+    ///
+    /// let cache = LruHashCache::with_memory_budget(1024 * 1024);
+    /// let persistent = Persistent::<64, i32, _>::load_with_cache(&path, cache).unwrap();
+    /// assert_eq!(persistent.tree().get(Element::ONE), Some(&123));
+    /// ```
+    pub fn load_with_cache<P: AsRef<Path>>(path: P, cache: C) -> Result<Self, Error>
+    where
+        V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+    {
+        let db = DB::open_default(path)?;
+        let tree = load::load_tree(&db, cache)?;
+
+        Ok(Self::from_parts(tree, db))
     }
 
     /// Get a reference to the wrapped tree
@@ -91,7 +154,7 @@ impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
     /// ```
     #[inline]
     #[must_use]
-    pub fn tree(&self) -> &Tree<DEPTH, V, SimpleHashCache> {
+    pub fn tree(&self) -> &Tree<DEPTH, V, C> {
         &self.tree
     }
 
@@ -108,11 +171,22 @@ impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
     /// get mutable access to the inner tree
     #[inline]
     #[must_use]
-    pub fn into_parts(self) -> (Tree<DEPTH, V, SimpleHashCache>, DB) {
-        let Self { tree, db } = self;
+    pub fn into_parts(self) -> (Tree<DEPTH, V, C>, DB) {
+        let Self { tree, db, .. } = self;
         (tree, db)
     }
 
+    fn from_parts(tree: Tree<DEPTH, V, C>, db: DB) -> Self {
+        Self {
+            tree,
+            db,
+            history: VecDeque::new(),
+            history_len: 0,
+            secondary: false,
+            caught_up_to: 0,
+        }
+    }
+
     /// Insert an element into the in-memory tree, and persist the element to the backing rocksdb
     /// store
     ///
@@ -142,6 +216,57 @@ impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
         self.insert_batch(crate::batch! { element => value })
     }
 
+    /// Remove an element from the in-memory tree, and delete it from the backing rocksdb store,
+    /// returning its value if it was present
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// # use smirk::storage::*;
+    /// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
+    /// # let path = dir.path().join("db");
+    /// let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+    /// persistent.insert(Element::ONE, 123).unwrap();
+    ///
+    /// assert_eq!(persistent.remove(Element::ONE).unwrap(), Some(123));
+    /// drop(persistent);
+    ///
+    /// let persistent = Persistent::<64, i32>::load(&path).unwrap();
+    /// assert!(persistent.tree().is_empty());
+    /// ```
+    /// Like [`Persistent::insert`], this recalculates hashes after each call, so prefer
+    /// [`Persistent::remove_batch`] when removing many elements
+    pub fn remove(&mut self, element: Element) -> Result<Option<V>, Error>
+    where
+        V: BorshSerialize + BorshDeserialize + Send + Sync + 'static + Clone,
+    {
+        Ok(self.remove_batch([element])?.pop().map(|(_, value)| value))
+    }
+
+    /// Create a consistent copy of the backing rocksdb database at `path`
+    ///
+    /// `path` must not exist yet. Files are hard linked where possible, so a checkpoint on the same
+    /// filesystem is cheap. Hashes that haven't been persisted with [`Persistent::persist_hashes`]
+    /// are not included, and will be recomputed when the checkpoint is loaded
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// # use smirk::storage::*;
+    /// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
+    /// # let path = dir.path().join("db");
+    /// # let checkpoint_path = dir.path().join("checkpoint");
+    /// let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+    /// persistent.insert(Element::ONE, 123).unwrap();
+    ///
+    /// persistent.create_checkpoint(&checkpoint_path).unwrap();
+    ///
+    /// let checkpoint = Persistent::<64, i32>::load(&checkpoint_path).unwrap();
+    /// assert_eq!(checkpoint.tree().get(Element::ONE), Some(&123));
+    /// ```
+    pub fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        rocksdb::checkpoint::Checkpoint::new(&self.db)?.create_checkpoint(path)?;
+        Ok(())
+    }
+
     /// Store all computed hashes from the in-memory tree into rocksdb
     ///
     /// Note that this function is never called automatically when inserting. Make sure to call
@@ -150,6 +275,8 @@ impl<const DEPTH: usize, V> Persistent<DEPTH, V> {
     where
         V: BorshSerialize + BorshDeserialize + Send + Sync + 'static + Clone,
     {
+        self.check_writable()?;
+
         store::synchronize_hashes(&self.db, &self.tree)
     }
 }
@@ -0,0 +1,298 @@
+use core::fmt::Debug;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use rocksdb::{WriteBatchIterator, DB};
+use wire_message::WireMessage;
+
+use crate::{hash_cache::PersistentHashCache, Batch, Element};
+
+use super::{
+    format::{KeyFormat, KeyV2, ValueFormat, ValueV2},
+    load::{self, RocksbEntry},
+    Error, Persistent,
+};
+
+impl<const DEPTH: usize, V, C: PersistentHashCache> Persistent<DEPTH, V, C> {
+    /// Open a read-only [`Persistent`] [`Tree`][crate::Tree] that follows the rocksdb database
+    /// of another [`Persistent`] at `primary_path`
+    ///
+    /// `secondary_path` is where rocksdb keeps the secondary instance's own logs, and must be
+    /// different for each secondary instance. The tree only sees changes made by the primary
+    /// after calling [`Persistent::catch_up_with_primary`], and any attempt to change it fails
+    /// with [`Error::ReadOnly`]
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// # use smirk::hash_cache::*;
+    /// # use smirk::storage::*;
+    /// # let dir = tempdir::TempDir::new("smirk_doctest").unwrap();
+    /// # let path = dir.path().join("db");
+    /// # let secondary_path = dir.path().join("secondary");
+    /// let mut primary = Persistent::<64, i32>::new(&path).unwrap();
+    /// primary.insert(Element::ONE, 123).unwrap();
+    ///
+    /// let mut secondary =
+    ///     Persistent::<64, i32>::open_secondary(&path, &secondary_path, SimpleHashCache::new())
+    ///         .unwrap();
+    /// assert_eq!(secondary.tree().get(Element::ONE), Some(&123));
+    ///
+    /// primary.insert(Element::new(2), 456).unwrap();
+    /// assert!(secondary.catch_up_with_primary().unwrap());
+    /// assert_eq!(secondary.tree().root_hash(), primary.tree().root_hash());
+    /// ```
+    pub fn open_secondary<P: AsRef<Path>>(
+        primary_path: P,
+        secondary_path: P,
+        cache: C,
+    ) -> Result<Self, Error>
+    where
+        V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+    {
+        let mut opts = rocksdb::Options::default();
+        // required by secondary instances, so they can see files added by the primary
+        opts.set_max_open_files(-1);
+
+        let db = DB::open_as_secondary(&opts, primary_path, secondary_path)?;
+        let caught_up_to = db.latest_sequence_number();
+        let tree = load::load_tree(&db, cache)?;
+
+        let mut persistent = Self::from_parts(tree, db);
+        persistent.secondary = true;
+        persistent.caught_up_to = caught_up_to;
+
+        Ok(persistent)
+    }
+
+    /// Whether this tree was opened with [`Persistent::open_secondary`]
+    #[inline]
+    #[must_use]
+    pub fn is_secondary(&self) -> bool {
+        self.secondary
+    }
+
+    /// Read the changes the primary has made since the tree was opened or last caught up, and
+    /// apply them to the in-memory tree
+    ///
+    /// This is [`Persistent::primary_changes`] followed by [`Persistent::apply_primary_changes`].
+    /// Returns whether the tree changed
+    pub fn catch_up_with_primary(&mut self) -> Result<bool, Error>
+    where
+        V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+    {
+        match self.primary_changes()? {
+            Some(changes) => self.apply_primary_changes(changes),
+            None => Ok(false),
+        }
+    }
+
+    /// Read the changes the primary has made since the tree was opened or last caught up,
+    /// without changing the tree, so the tree can still be read while they are found
+    ///
+    /// The changes are read from the primary's write-ahead log. If the log no longer has all of
+    /// them (e.g. because the primary flushed it), every stored element is read instead, and
+    /// compared with the tree. Returns `None` if the primary hasn't written anything
+    pub fn primary_changes(&self) -> Result<Option<PrimaryChanges<DEPTH, V>>, Error>
+    where
+        V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+    {
+        if !self.secondary {
+            return Ok(None);
+        }
+
+        self.db.try_catch_up_with_primary()?;
+
+        let sequence_number = self.db.latest_sequence_number();
+        if sequence_number == self.caught_up_to {
+            return Ok(None);
+        }
+
+        let (removed, inserted) = match self.logged_changes(sequence_number)? {
+            Some(changes) => self.diff(changes)?,
+            None => self.diff(self.stored_elements()?)?,
+        };
+
+        Ok(Some(PrimaryChanges {
+            sequence_number,
+            removed,
+            inserted,
+        }))
+    }
+
+    /// Apply changes read by [`Persistent::primary_changes`]. Returns whether the tree changed
+    pub fn apply_primary_changes(
+        &mut self,
+        changes: PrimaryChanges<DEPTH, V>,
+    ) -> Result<bool, Error>
+    where
+        V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+    {
+        let PrimaryChanges {
+            sequence_number,
+            removed,
+            inserted,
+        } = changes;
+
+        self.caught_up_to = sequence_number;
+
+        if removed.is_empty() && inserted.is_empty() {
+            return Ok(false);
+        }
+
+        self.tree.remove_batch(removed, |_| {}, |_| {});
+        self.tree.insert_batch(inserted, |_| {}, |_| {})?;
+
+        Ok(true)
+    }
+
+    /// The elements written or deleted by the primary since the tree last caught up, up to
+    /// `sequence_number`, or `None` if the write-ahead log doesn't have all of those writes
+    fn logged_changes(
+        &self,
+        sequence_number: u64,
+    ) -> Result<Option<HashMap<Element, Option<V>>>, Error>
+    where
+        V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+    {
+        let Ok(updates) = self.db.get_updates_since(self.caught_up_to + 1) else {
+            return Ok(None);
+        };
+
+        let mut changes = ElementChanges {
+            changes: HashMap::new(),
+            error: None,
+        };
+        let mut next = self.caught_up_to + 1;
+
+        for update in updates {
+            let Ok((first, batch)) = update else {
+                return Ok(None);
+            };
+
+            let end = first + batch.len() as u64;
+
+            if first > next {
+                // the log is missing the writes in between
+                return Ok(None);
+            }
+
+            // the log can start with a write the tree has already seen
+            if end > next {
+                batch.iterate(&mut changes);
+                next = end;
+            }
+        }
+
+        if next <= sequence_number {
+            return Ok(None);
+        }
+
+        match changes.error {
+            Some(err) => Err(err),
+            None => Ok(Some(changes.changes)),
+        }
+    }
+
+    /// Every element stored in rocksdb
+    fn stored_elements(&self) -> Result<HashMap<Element, Option<V>>, Error>
+    where
+        V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+    {
+        let mut stored = self
+            .tree
+            .elements()
+            .map(|(element, _)| (*element, None))
+            .collect::<HashMap<_, _>>();
+
+        for entry in load::entries::<V>(&self.db) {
+            if let RocksbEntry::SmirkKV { key, value } = entry? {
+                stored.insert(key, Some(value));
+            }
+        }
+
+        Ok(stored)
+    }
+
+    /// The elements to remove from and insert into the tree, so each element in `changes` is
+    /// in the tree if and only if it is `Some`
+    fn diff(
+        &self,
+        changes: HashMap<Element, Option<V>>,
+    ) -> Result<(Vec<Element>, Batch<DEPTH, V>), Error>
+    where
+        V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+    {
+        let mut removed = Vec::new();
+        let mut inserted = Batch::new();
+
+        for (element, value) in changes {
+            match (value, self.tree.contains_element(&element)) {
+                (None, true) => removed.push(element),
+                (Some(value), false) => inserted.insert(element, value)?,
+                _ => {}
+            }
+        }
+
+        Ok((removed, inserted))
+    }
+
+    pub(super) fn check_writable(&self) -> Result<(), Error> {
+        match self.secondary {
+            true => Err(Error::ReadOnly),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The changes a primary has made since its secondary [`Persistent`] last caught up, as returned
+/// by [`Persistent::primary_changes`]
+pub struct PrimaryChanges<const DEPTH: usize, V> {
+    sequence_number: u64,
+    removed: Vec<Element>,
+    inserted: Batch<DEPTH, V>,
+}
+
+/// Collects the elements put or deleted by the writes in the write-ahead log, ignoring cached
+/// hashes
+struct ElementChanges<V> {
+    changes: HashMap<Element, Option<V>>,
+    error: Option<Error>,
+}
+
+impl<V> WriteBatchIterator for ElementChanges<V>
+where
+    V: BorshDeserialize + BorshSerialize + Debug + Clone + Send + Sync + 'static,
+{
+    fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
+        let result = KeyFormat::from_bytes(&key).and_then(|key| {
+            let value = ValueFormat::<V>::from_bytes(&value)?;
+            Ok((key, value))
+        });
+
+        match result {
+            Ok((
+                KeyFormat::V1(element) | KeyFormat::V2(KeyV2::Element(element)),
+                ValueFormat::V1(metadata) | ValueFormat::V2(ValueV2::Metadata(metadata)),
+            )) => {
+                let metadata = Arc::try_unwrap(metadata).unwrap_or_else(|arc| (*arc).clone());
+                self.changes.insert(element, Some(metadata));
+            }
+            Ok(_) => {}
+            Err(err) => {
+                self.error.get_or_insert(err.into());
+            }
+        }
+    }
+
+    fn delete(&mut self, key: Box<[u8]>) {
+        match KeyFormat::from_bytes(&key) {
+            Ok(KeyFormat::V1(element) | KeyFormat::V2(KeyV2::Element(element))) => {
+                self.changes.insert(element, None);
+            }
+            Ok(_) => {}
+            Err(err) => {
+                self.error.get_or_insert(err.into());
+            }
+        }
+    }
+}
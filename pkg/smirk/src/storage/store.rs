@@ -4,16 +4,13 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use rocksdb::{IteratorMode, WriteBatch, DB};
 use wire_message::WireMessage;
 
-use crate::{
-    hash_cache::{KnownHash, SimpleHashCache},
-    Tree,
-};
+use crate::{hash_cache::KnownHash, Tree};
 
 use super::format::{KeyFormat, KeyV2, ValueFormat, ValueV2};
 
-pub(super) fn synchronize_hashes<const DEPTH: usize, V>(
+pub(super) fn synchronize_hashes<const DEPTH: usize, V, C>(
     db: &DB,
-    tree: &Tree<DEPTH, V, SimpleHashCache>,
+    tree: &Tree<DEPTH, V, C>,
 ) -> Result<(), super::Error>
 where
     V: Clone + Send + Sync + 'static + BorshDeserialize + BorshSerialize,
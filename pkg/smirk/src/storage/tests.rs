@@ -4,7 +4,11 @@ use expect_test::expect_file;
 use tempdir::TempDir;
 use test_strategy::proptest;
 
-use crate::{batch, Batch};
+use crate::{
+    batch,
+    hash_cache::{LruHashCache, ENTRY_SIZE},
+    smirk, Batch,
+};
 
 use super::*;
 
@@ -108,7 +112,7 @@ macro_rules! expect_storage_known_hashes {
             .iter()
             .filter_map(|entry| match entry {
                 RocksbEntry::KnownHash(hash) => Some(*hash),
-                RocksbEntry::SmirkKV { .. } => None,
+                RocksbEntry::SmirkKV { .. } | RocksbEntry::Node { .. } => None,
             })
             .collect::<Vec<_>>();
 
@@ -154,3 +158,495 @@ fn insert_batch_hash_test() {
         hashes: expect_file!["test-snapshots/known_hashes_2.txt"]
     );
 }
+
+#[test]
+fn remove_is_persisted() {
+    let (_dir, path) = setup_path();
+    let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+
+    persistent.insert(Element::new(1), 1).unwrap();
+    persistent.insert(Element::new(2), 2).unwrap();
+
+    assert_eq!(persistent.remove(Element::new(1)).unwrap(), Some(1));
+    assert_eq!(persistent.remove(Element::new(1)).unwrap(), None);
+
+    let root_hash = persistent.tree().root_hash();
+    drop(persistent);
+
+    let loaded = Persistent::<64, i32>::load(&path).unwrap();
+    assert!(!loaded.tree().contains_element(&Element::new(1)));
+    assert_eq!(loaded.tree().get(Element::new(2)), Some(&2));
+    assert_eq!(loaded.tree().root_hash(), root_hash);
+}
+
+#[test]
+fn secondary_follows_primary() {
+    let (dir, path) = setup_path();
+    let mut primary = Persistent::<64, i32>::new(&path).unwrap();
+
+    primary.insert(Element::new(1), 1).unwrap();
+    primary.insert(Element::new(2), 2).unwrap();
+
+    let mut secondary = Persistent::<64, i32>::open_secondary(
+        &path,
+        &dir.path().join("secondary"),
+        SimpleHashCache::new(),
+    )
+    .unwrap();
+    assert!(secondary.is_secondary());
+    assert_eq!(secondary.tree().root_hash(), primary.tree().root_hash());
+    assert!(!secondary.catch_up_with_primary().unwrap());
+
+    primary.remove(Element::new(1)).unwrap();
+    primary.insert(Element::new(3), 3).unwrap();
+
+    // changes aren't visible until the secondary catches up
+    assert!(secondary.tree().contains_element(&Element::new(1)));
+
+    assert!(secondary.catch_up_with_primary().unwrap());
+    assert!(!secondary.tree().contains_element(&Element::new(1)));
+    assert_eq!(secondary.tree().get(Element::new(3)), Some(&3));
+    assert_eq!(secondary.tree().root_hash(), primary.tree().root_hash());
+
+    assert!(matches!(
+        secondary.insert(Element::new(4), 4),
+        Err(Error::ReadOnly)
+    ));
+    assert!(matches!(
+        secondary.remove(Element::new(2)),
+        Err(Error::ReadOnly)
+    ));
+    assert!(!secondary.tree().contains_element(&Element::new(4)));
+}
+
+#[test]
+fn secondary_reads_changes_before_applying_them() {
+    let (dir, path) = setup_path();
+    let mut primary = Persistent::<64, i32>::new(&path).unwrap();
+    primary.insert(Element::new(1), 1).unwrap();
+
+    let mut secondary = Persistent::<64, i32>::open_secondary(
+        &path,
+        &dir.path().join("secondary"),
+        SimpleHashCache::new(),
+    )
+    .unwrap();
+    assert!(secondary.primary_changes().unwrap().is_none());
+
+    primary.insert_batch(batch! { 2 => 2, 3 => 3 }).unwrap();
+    primary.remove(Element::new(1)).unwrap();
+
+    let changes = secondary.primary_changes().unwrap().unwrap();
+
+    // the tree doesn't change until the changes are applied
+    assert!(secondary.tree().contains_element(&Element::new(1)));
+
+    assert!(secondary.apply_primary_changes(changes).unwrap());
+    assert!(!secondary.tree().contains_element(&Element::new(1)));
+    assert_eq!(secondary.tree().get(Element::new(3)), Some(&3));
+    assert_eq!(secondary.tree().root_hash(), primary.tree().root_hash());
+    assert!(secondary.primary_changes().unwrap().is_none());
+}
+
+#[test]
+fn remove_batch_hash_test() {
+    let (_dir, path) = setup_path();
+    let mut persistent = Persistent::<64, ()>::new(&path).unwrap();
+
+    persistent.insert_batch(batch! { 1, 2, 3 }).unwrap();
+    persistent.insert_batch(batch! { 4, 5, 6 }).unwrap();
+
+    persistent
+        .remove_batch([4, 5, 6].map(Element::new))
+        .unwrap();
+
+    // removing the second batch should leave the same known hashes as only inserting the first
+    expect_storage_known_hashes!(
+        persistent,
+        hashes: expect_file!["test-snapshots/known_hashes_1.txt"]
+    );
+
+    persistent
+        .remove_batch([1, 2, 3].map(Element::new))
+        .unwrap();
+    expect_storage_known_hashes!(
+        persistent,
+        hashes:
+            expect_test::expect![[r#"
+            []
+        "#]]
+    );
+}
+
+#[proptest(cases = cases())]
+fn insert_and_remove_batch_works(batch_1: Batch<64, i32>, mut batch_2: Batch<64, i32>) {
+    let (_dir1, path) = setup_path();
+    let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+
+    for element in batch_1.elements() {
+        batch_2.remove(element);
+    }
+
+    let batch_1_elements: HashSet<_> = batch_1.elements().collect();
+    let batch_2_elements: HashSet<_> = batch_2.elements().collect();
+
+    persistent.insert_batch(batch_1).unwrap();
+    let root_hash = persistent.tree().root_hash();
+
+    persistent.insert_batch(batch_2).unwrap();
+    let removed = persistent
+        .remove_batch(batch_2_elements.iter().copied())
+        .unwrap();
+
+    assert_eq!(removed.len(), batch_2_elements.len());
+    assert_eq!(persistent.tree().root_hash(), root_hash);
+
+    drop(persistent);
+
+    let loaded = Persistent::<64, i32>::load(&path).unwrap();
+
+    assert_eq!(loaded.tree().root_hash(), root_hash);
+
+    for element in batch_1_elements {
+        assert!(loaded.tree().contains_element(&element));
+    }
+
+    for element in batch_2_elements {
+        assert!(!loaded.tree().contains_element(&element));
+    }
+}
+
+#[proptest(cases = cases())]
+fn lru_cache_matches_simple_cache(batch_1: Batch<64, i32>, mut batch_2: Batch<64, i32>) {
+    let (_dir, path) = setup_path();
+    let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+
+    for element in batch_1.elements() {
+        batch_2.remove(element);
+    }
+
+    persistent.insert_batch(batch_1).unwrap();
+    persistent.persist_hashes().unwrap();
+    let root_hash = persistent.tree().root_hash();
+    drop(persistent);
+
+    // small enough that most of the stored hashes don't fit
+    let cache = LruHashCache::with_memory_budget(64 * ENTRY_SIZE);
+    let mut loaded = Persistent::<64, i32, _>::load_with_cache(&path, cache).unwrap();
+
+    assert_eq!(loaded.tree().root_hash(), root_hash);
+    assert!(loaded.tree().cache().len() <= loaded.tree().cache().capacity());
+
+    loaded.insert_batch(batch_2).unwrap();
+    let root_hash = loaded.tree().root_hash();
+    drop(loaded);
+
+    let loaded = Persistent::<64, i32>::load(&path).unwrap();
+    assert_eq!(loaded.tree().root_hash(), root_hash);
+}
+
+#[test]
+fn history_tracks_committed_versions() {
+    let (_dir, path) = setup_path();
+    let mut persistent = Persistent::<64, ()>::new(&path).unwrap();
+    persistent.set_history_len(3);
+
+    let mut roots = Vec::new();
+    for i in 1..=4 {
+        persistent.insert(Element::new(i), ()).unwrap();
+        persistent.commit_version(i);
+        roots.push(persistent.tree().root_hash());
+    }
+
+    assert_eq!(persistent.versions().collect::<Vec<_>>(), vec![2, 3, 4]);
+    assert_eq!(persistent.root_hash_at(1), None);
+
+    for (version, root) in (2..=4).zip(&roots[1..]) {
+        assert_eq!(persistent.root_hash_at(version), Some(*root));
+
+        let snapshot = persistent.snapshot_at(version).unwrap();
+        assert!(snapshot.contains_element(Element::new(version)));
+        assert!(!snapshot.contains_element(Element::new(version + 1)));
+
+        let path = persistent
+            .path_for_at(version, Element::new(version))
+            .unwrap();
+        assert!(path.proves(Element::new(version)));
+    }
+
+    // removals don't affect earlier versions
+    persistent.remove(Element::new(4)).unwrap();
+    persistent.commit_version(5);
+    assert_eq!(persistent.root_hash_at(4), Some(roots[3]));
+    assert_eq!(persistent.root_hash_at(5), Some(roots[2]));
+
+    // committing an older version discards newer history
+    persistent.commit_version(3);
+    assert_eq!(persistent.versions().collect::<Vec<_>>(), vec![3]);
+}
+
+#[test]
+fn history_inserts_earlier_versions() {
+    let (_dir, path) = setup_path();
+    let mut persistent = Persistent::<64, ()>::new(&path).unwrap();
+    persistent.set_history_len(3);
+
+    let mut roots = Vec::new();
+    for i in 1..=3 {
+        persistent.insert(Element::new(i), ()).unwrap();
+        roots.push(persistent.tree().root_hash());
+    }
+    drop(persistent);
+
+    // history starts empty after loading, so earlier versions are rebuilt from the latest one
+    let mut persistent = Persistent::<64, ()>::load(&path).unwrap();
+    persistent.set_history_len(3);
+    persistent.commit_version(3);
+
+    let mut snapshot = persistent.tree().snapshot();
+    for version in (1..3).rev() {
+        snapshot = snapshot.without(&[Element::new(version + 1)], persistent.tree().cache());
+        persistent.insert_version(version, snapshot.clone());
+    }
+
+    assert_eq!(persistent.versions().collect::<Vec<_>>(), vec![1, 2, 3]);
+    for (version, root) in (1..=3).zip(&roots) {
+        assert_eq!(persistent.root_hash_at(version), Some(*root));
+    }
+
+    // existing versions are kept, and older versions are dropped when the history is full
+    persistent.insert_version(2, persistent.tree().snapshot());
+    assert_eq!(persistent.root_hash_at(2), Some(roots[1]));
+    persistent.insert_version(0, persistent.tree().snapshot());
+    assert_eq!(persistent.versions().collect::<Vec<_>>(), vec![1, 2, 3]);
+}
+
+#[proptest(cases = cases())]
+fn lazy_matches_tree(batch_1: Batch<64, i32>, mut batch_2: Batch<64, i32>, element: Element) {
+    let (_dir, path) = setup_path();
+    let mut lazy = Lazy::<64, i32>::load(&path).unwrap();
+    let mut tree = Tree::<64, i32>::new();
+
+    for element in batch_1.elements() {
+        batch_2.remove(element);
+    }
+
+    tree.insert_batch(batch_1.clone(), |_| {}, |_| {}).unwrap();
+    lazy.insert_batch(batch_1).unwrap();
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+
+    drop(lazy);
+
+    // a small cache makes sure the hashes are read back from rocksdb
+    let mut lazy = Lazy::<64, i32>::load_with_cache_capacity(&path, 4).unwrap();
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+
+    tree.insert_batch(batch_2.clone(), |_| {}, |_| {}).unwrap();
+    lazy.insert_batch(batch_2).unwrap();
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+
+    for (element, value) in tree.iter() {
+        assert!(lazy.contains_element(*element).unwrap());
+        assert_eq!(lazy.get(*element).unwrap(), Some(*value));
+    }
+
+    assert_eq!(
+        lazy.contains_element(element).unwrap(),
+        tree.contains_element(&element)
+    );
+
+    let path = lazy.path_for(element).unwrap();
+    let expected = tree.path_for(element);
+    assert_eq!(path.siblings, expected.siblings);
+    assert_eq!(path.actual_root_hash(), expected.actual_root_hash());
+}
+
+#[proptest(cases = cases())]
+fn lazy_remove_matches_tree(batch: Batch<64, i32>, remove_mask: Vec<bool>) {
+    let (_dir, path) = setup_path();
+    let mut lazy = Lazy::<64, i32>::load(&path).unwrap();
+    let mut tree = Tree::<64, i32>::new();
+
+    let to_remove: Vec<_> = batch
+        .elements()
+        .zip(remove_mask)
+        .filter_map(|(element, remove)| remove.then_some(element))
+        .collect();
+
+    tree.insert_batch(batch.clone(), |_| {}, |_| {}).unwrap();
+    lazy.insert_batch(batch).unwrap();
+
+    let mut expected = tree.remove_batch(to_remove.iter().copied(), |_| {}, |_| {});
+    let mut removed = lazy.remove_batch(to_remove).unwrap();
+
+    expected.sort_unstable_by_key(|(element, _)| *element);
+    removed.sort_unstable_by_key(|(element, _)| *element);
+
+    assert_eq!(removed, expected);
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+
+    drop(lazy);
+
+    let lazy = Lazy::<64, i32>::load(&path).unwrap();
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+}
+
+#[test]
+fn lazy_rejects_collisions() {
+    let (_dir, path) = setup_path();
+    let mut lazy = Lazy::<64, ()>::load(&path).unwrap();
+    lazy.insert_batch(batch! { 1, 2 }).unwrap();
+    let root_hash = lazy.root_hash();
+
+    let colliding = Element::new(1) + (Element::new(1) << 100);
+    assert!(lazy.insert_batch(batch! { 3, colliding }).is_err());
+    assert!(lazy.insert(Element::new(2), ()).is_err());
+
+    assert_eq!(lazy.root_hash(), root_hash);
+    assert!(!lazy.contains_element(Element::new(3)).unwrap());
+}
+
+#[test]
+fn lazy_and_persistent_share_a_database() {
+    let (_dir, path) = setup_path();
+
+    let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+    persistent.insert_batch(batch! { 1 => 1, 2 => 2 }).unwrap();
+    drop(persistent);
+
+    // the node hashes are built from the elements written by `Persistent`
+    let mut lazy = Lazy::<64, i32>::load(&path).unwrap();
+    let tree: Tree<64, i32> = smirk! { 1 => 1, 2 => 2 };
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+
+    lazy.insert(Element::new(3), 3).unwrap();
+    drop(lazy);
+
+    let mut persistent = Persistent::<64, i32>::load(&path).unwrap();
+    let tree: Tree<64, i32> = smirk! { 1 => 1, 2 => 2, 3 => 3 };
+    assert_eq!(persistent.tree().root_hash(), tree.root_hash());
+
+    persistent.remove(Element::new(1)).unwrap();
+    drop(persistent);
+
+    // `Persistent` invalidated the node hashes, so they are rebuilt
+    let lazy = Lazy::<64, i32>::load(&path).unwrap();
+    let tree: Tree<64, i32> = smirk! { 2 => 2, 3 => 3 };
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+    assert!(!lazy.contains_element(Element::new(1)).unwrap());
+}
+
+#[test]
+fn integrity_check_finds_and_repairs_inconsistencies() {
+    use rocksdb::WriteBatch;
+    use wire_message::WireMessage;
+
+    use crate::hash_cache::KnownHash;
+
+    use super::format::{KeyFormat, KeyV2, ValueFormat, ValueV2};
+
+    let (_dir, path) = setup_path();
+    let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+    persistent
+        .insert_batch(batch! { 1 => 1, 2 => 2, 3 => 3 })
+        .unwrap();
+    let root_hash = persistent.tree().root_hash();
+
+    // corrupt the stored hashes, as if a write had only partially succeeded
+    let mut known_hashes = persistent.tree().known_hashes();
+    known_hashes.sort_unstable();
+    let missing = known_hashes[0];
+    let incorrect = KnownHash {
+        result: Element::new(1234),
+        ..known_hashes[1]
+    };
+    let stale = KnownHash {
+        left: Element::new(5),
+        right: Element::new(6),
+        result: crate::hash_merge([Element::new(5), Element::new(6)]),
+    };
+
+    let put = |batch: &mut WriteBatch, hash: KnownHash| {
+        let key = KeyFormat::V2(KeyV2::KnownHash {
+            left: hash.left,
+            right: hash.right,
+        });
+        let value = ValueFormat::<i32>::V2(ValueV2::KnownHash(hash.result));
+        batch.put(key.to_bytes().unwrap(), value.to_bytes().unwrap());
+    };
+
+    let mut write_batch = WriteBatch::default();
+    put(&mut write_batch, incorrect);
+    put(&mut write_batch, stale);
+    let missing_key = KeyFormat::V2(KeyV2::KnownHash {
+        left: missing.left,
+        right: missing.right,
+    });
+    write_batch.delete(missing_key.to_bytes().unwrap());
+    persistent.db().write(write_batch).unwrap();
+
+    drop(persistent);
+
+    let report = check_integrity::<64, i32, _>(&path).unwrap();
+    assert!(!report.is_consistent());
+    assert_eq!(report.root_hash, root_hash);
+    assert_eq!(report.elements, 3);
+    assert_eq!(report.incorrect_hashes, vec![incorrect]);
+    assert_eq!(report.stale_hashes, vec![stale]);
+    assert_eq!(report.missing_hashes, vec![missing, known_hashes[1]]);
+    assert_eq!(report.unreadable_entries, 0);
+
+    // the incorrect hash poisons the root hash of the loaded tree
+    let loaded = Persistent::<64, i32>::load(&path).unwrap();
+    assert_ne!(loaded.tree().root_hash(), root_hash);
+    drop(loaded);
+
+    let repaired = repair::<64, i32, _>(&path).unwrap();
+    assert_eq!(repaired, report);
+
+    let report = check_integrity::<64, i32, _>(&path).unwrap();
+    assert!(report.is_consistent());
+
+    let loaded = Persistent::<64, i32>::load(&path).unwrap();
+    assert_eq!(loaded.tree().root_hash(), root_hash);
+}
+
+#[test]
+fn integrity_check_and_repair_dont_create_missing_databases() {
+    let (_dir, path) = setup_path();
+
+    assert!(check_integrity::<64, i32, _>(&path).is_err());
+    assert!(repair::<64, i32, _>(&path).is_err());
+    assert!(!path.exists());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn stored_entries_can_be_read_as_json() {
+    use serde_json::json;
+    use wire_message::WireMessage;
+
+    let (_dir, path) = setup_path();
+    let mut persistent = Persistent::<64, i32>::new(&path).unwrap();
+    persistent.insert(Element::new(1), 123).unwrap();
+
+    let entries = persistent
+        .db()
+        .iterator(rocksdb::IteratorMode::Start)
+        .map(|entry| {
+            let (key, value) = entry.unwrap();
+            let key = KeyFormat::from_bytes(&key).unwrap();
+            let value = ValueFormat::<i32>::from_bytes(&value).unwrap();
+
+            (
+                serde_json::to_value(key).unwrap(),
+                serde_json::to_value(value).unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert!(entries.contains(&(
+        json!({ "V2": { "Element": Element::new(1) } }),
+        json!({ "V2": { "Metadata": 123 } }),
+    )));
+}
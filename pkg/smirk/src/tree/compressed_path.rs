@@ -0,0 +1,241 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{hash::empty_tree_hash, Element, Path};
+
+/// A compact encoding of the siblings of a [`Path`]
+///
+/// In a sparse tree, most siblings are the hash of an empty subtree (see [`empty_tree_hash`]),
+/// which can be recomputed by anyone. A [`CompressedPath`] stores a bitmap marking which siblings
+/// are *not* the empty subtree hash, and only the values of those siblings.
+///
+/// Like [`Path::siblings_deepest_first`], a `CompressedPath<DEPTH>` represents `DEPTH - 1`
+/// siblings. Bit `i` of the bitmap (i.e. bit `i % 8` of byte `i / 8`) corresponds to the `i`th
+/// sibling, with the deepest sibling first
+///
+/// ```rust
+/// # use smirk::*;
+/// let tree: Tree<64, _> = smirk! { 1, 2, 4 };
+/// let path = tree.path_for(Element::ONE);
+///
+/// let compressed = path.compress();
+///
+/// // only the subtrees containing 2 and 4 are non-empty
+/// assert_eq!(compressed.siblings.len(), 2);
+///
+/// let siblings = compressed.decompress().unwrap();
+/// assert_eq!(siblings, path.siblings_deepest_first());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressedPath<const DEPTH: usize> {
+    /// A bitmap of the siblings that are not the hash of an empty subtree
+    #[cfg_attr(feature = "serde", serde(with = "hex::serde"))]
+    pub bitmap: Vec<u8>,
+    /// The values of the siblings that are set in [`Self::bitmap`], deepest first
+    pub siblings: Vec<Element>,
+}
+
+/// An error that can occur when compressing or decompressing a [`CompressedPath`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CompressedPathError {
+    /// The path to compress doesn't have `DEPTH - 1` siblings
+    #[error("expected a path with {expected} siblings, but got {actual}")]
+    WrongPathLength {
+        /// The number of siblings of a path in a tree of depth `DEPTH`
+        expected: usize,
+        /// The number of siblings of the path
+        actual: usize,
+    },
+
+    /// The bitmap has the wrong number of bytes for the depth of the tree
+    #[error("expected a bitmap of {expected} bytes, but got {actual}")]
+    WrongBitmapLength {
+        /// The number of bytes needed for `DEPTH - 1` bits
+        expected: usize,
+        /// The length of the bitmap
+        actual: usize,
+    },
+
+    /// The bitmap has bits set past the last sibling
+    #[error("the bitmap has bits set past the last sibling")]
+    PaddingBitsSet,
+
+    /// The number of siblings doesn't match the number of bits set in the bitmap
+    #[error("expected {expected} siblings, but got {actual}")]
+    WrongNumberOfSiblings {
+        /// The number of bits set in the bitmap
+        expected: usize,
+        /// The number of siblings
+        actual: usize,
+    },
+}
+
+impl<const DEPTH: usize> CompressedPath<DEPTH> {
+    const SIBLINGS: usize = DEPTH - 1;
+    const BITMAP_LEN: usize = (Self::SIBLINGS + 7) / 8;
+
+    /// Compress the siblings of a path, with the deepest sibling first
+    ///
+    /// # Panics
+    ///
+    /// Panics if `siblings` doesn't contain exactly `DEPTH - 1` elements. Use
+    /// [`CompressedPath::try_compress`] for siblings that don't come from a [`Path`]
+    #[must_use]
+    pub fn compress(siblings: &[Element]) -> Self {
+        match Self::try_compress(siblings) {
+            Ok(compressed) => compressed,
+            Err(err) => panic!("a path in a tree of depth {DEPTH} can't be compressed: {err}"),
+        }
+    }
+
+    /// Compress the siblings of a path, with the deepest sibling first, or return an error if
+    /// `siblings` doesn't contain exactly `DEPTH - 1` elements
+    pub fn try_compress(siblings: &[Element]) -> Result<Self, CompressedPathError> {
+        if siblings.len() != Self::SIBLINGS {
+            return Err(CompressedPathError::WrongPathLength {
+                expected: Self::SIBLINGS,
+                actual: siblings.len(),
+            });
+        }
+
+        let mut bitmap = vec![0; Self::BITMAP_LEN];
+        let mut non_empty = Vec::new();
+
+        for (i, sibling) in siblings.iter().enumerate() {
+            if *sibling != empty_tree_hash(i + 1) {
+                bitmap[i / 8] |= 1 << (i % 8);
+                non_empty.push(*sibling);
+            }
+        }
+
+        Ok(Self {
+            bitmap,
+            siblings: non_empty,
+        })
+    }
+
+    /// Recover the `DEPTH - 1` siblings of the path, with the deepest sibling first
+    pub fn decompress(&self) -> Result<Vec<Element>, CompressedPathError> {
+        if self.bitmap.len() != Self::BITMAP_LEN {
+            return Err(CompressedPathError::WrongBitmapLength {
+                expected: Self::BITMAP_LEN,
+                actual: self.bitmap.len(),
+            });
+        }
+
+        let padding_bits = Self::BITMAP_LEN * 8 - Self::SIBLINGS;
+        if let Some(&last) = self.bitmap.last() {
+            if padding_bits > 0 && last >> (8 - padding_bits) != 0 {
+                return Err(CompressedPathError::PaddingBitsSet);
+            }
+        }
+
+        let set_bits: usize = self
+            .bitmap
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum();
+        if self.siblings.len() != set_bits {
+            return Err(CompressedPathError::WrongNumberOfSiblings {
+                expected: set_bits,
+                actual: self.siblings.len(),
+            });
+        }
+
+        let mut non_empty = self.siblings.iter();
+
+        let siblings = (0..Self::SIBLINGS)
+            .map(|i| match self.bitmap[i / 8] & (1 << (i % 8)) {
+                0 => empty_tree_hash(i + 1),
+                _ => *non_empty.next().unwrap(),
+            })
+            .collect();
+
+        Ok(siblings)
+    }
+}
+
+impl<const DEPTH: usize> Path<DEPTH> {
+    /// Compress the siblings of this path
+    ///
+    /// See [`CompressedPath`] for more details
+    #[inline]
+    #[must_use]
+    pub fn compress(&self) -> CompressedPath<DEPTH> {
+        CompressedPath::compress(self.siblings_deepest_first())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::proptest;
+
+    use crate::Tree;
+
+    use super::*;
+
+    #[proptest]
+    fn compress_round_trips(tree: Tree<64, i32>, element: Element) {
+        let path = tree.path_for(element);
+        let compressed = path.compress();
+
+        assert_eq!(
+            compressed.decompress().unwrap(),
+            path.siblings_deepest_first()
+        );
+    }
+
+    #[test]
+    fn empty_tree_has_no_siblings() {
+        let tree = Tree::<161, i32>::new();
+        let compressed = tree.path_for(Element::ONE).compress();
+
+        assert_eq!(compressed.bitmap, vec![0; 20]);
+        assert!(compressed.siblings.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        let tree: Tree<64, _> = crate::smirk! { 1, 2 };
+        let compressed = tree.path_for(Element::ONE).compress();
+
+        let mut short_bitmap = compressed.clone();
+        short_bitmap.bitmap.pop();
+        assert_eq!(
+            short_bitmap.decompress(),
+            Err(CompressedPathError::WrongBitmapLength {
+                expected: 8,
+                actual: 7
+            }),
+        );
+
+        let mut padding = compressed.clone();
+        padding.bitmap[7] |= 0x80;
+        assert_eq!(
+            padding.decompress(),
+            Err(CompressedPathError::PaddingBitsSet)
+        );
+
+        let siblings = tree
+            .path_for(Element::ONE)
+            .siblings_deepest_first()
+            .to_vec();
+        assert_eq!(
+            CompressedPath::<64>::try_compress(&siblings[1..]),
+            Err(CompressedPathError::WrongPathLength {
+                expected: 63,
+                actual: 62
+            }),
+        );
+
+        let mut extra_sibling = compressed;
+        extra_sibling.siblings.push(Element::ONE);
+        assert_eq!(
+            extra_sibling.decompress(),
+            Err(CompressedPathError::WrongNumberOfSiblings {
+                expected: 1,
+                actual: 2
+            }),
+        );
+    }
+}
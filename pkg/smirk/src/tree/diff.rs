@@ -0,0 +1,437 @@
+use core::convert::Infallible;
+use std::iter::zip;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{hash::empty_tree_hash, Element, Snapshot, Tree};
+
+use super::tree_repr::Node;
+
+/// Identifies a subtree of a [`Tree`]
+///
+/// `depth` is the distance from the root, so the root has depth `0`, and the leaves of a
+/// `Tree<DEPTH, _>` have depth `DEPTH - 1`. `index` is the position of the subtree within that
+/// layer of the tree, i.e. the top `depth` bits of the `DEPTH - 1` least significant bits of any
+/// element in the subtree
+///
+/// ```rust
+/// # use smirk::*;
+/// let root = SubtreeId::ROOT;
+/// let [left, right] = root.children();
+///
+/// assert_eq!(left, SubtreeId::new(1, Element::new(0)));
+/// assert_eq!(right, SubtreeId::new(1, Element::new(1)));
+/// ```
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, BorshSerialize, BorshDeserialize,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubtreeId {
+    depth: usize,
+    index: Element,
+}
+
+impl SubtreeId {
+    /// The whole tree
+    pub const ROOT: Self = Self {
+        depth: 0,
+        index: Element::ZERO,
+    };
+
+    /// Create a new [`SubtreeId`]
+    #[inline]
+    #[must_use]
+    pub fn new(depth: usize, index: Element) -> Self {
+        Self { depth, index }
+    }
+
+    /// The distance of this subtree from the root
+    #[inline]
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The position of this subtree within its layer of the tree
+    #[inline]
+    #[must_use]
+    pub fn index(&self) -> Element {
+        self.index
+    }
+
+    /// The left and right children of this subtree
+    #[must_use]
+    pub fn children(self) -> [Self; 2] {
+        let left = self.index << 1u8;
+
+        [left, left | 1u8].map(|index| Self {
+            depth: self.depth + 1,
+            index,
+        })
+    }
+
+    /// Returns `true` if this subtree exists in a tree of depth `DEPTH`
+    fn is_valid<const DEPTH: usize>(self) -> bool {
+        self.depth < DEPTH && self.index < Element::ONE << Element::from(self.depth as u64)
+    }
+}
+
+/// The differences between two trees, as returned by [`Tree::diff`] and [`Tree::diff_with`]
+///
+/// Elements are sorted in ascending order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    /// Elements that are in the other tree, but not in this tree
+    pub missing: Vec<Element>,
+    /// Elements that are in this tree, but not in the other tree
+    pub extra: Vec<Element>,
+}
+
+impl Diff {
+    /// Returns `true` if the trees contain the same elements
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// An error that can occur when comparing a tree with a remote tree
+#[derive(Debug, thiserror::Error)]
+pub enum DiffError<E> {
+    /// The callback failed to fetch subtree hashes
+    #[error("failed to fetch subtree hashes: {0}")]
+    Fetch(E),
+
+    /// The callback returned the wrong number of hashes
+    #[error("expected {expected} subtree hashes, but got {actual}")]
+    WrongNumberOfHashes {
+        /// The number of hashes that were requested
+        expected: usize,
+        /// The number of hashes that were returned
+        actual: usize,
+    },
+}
+
+impl<const DEPTH: usize, V, C> Tree<DEPTH, V, C> {
+    /// The hash of the subtree identified by `id`, or `None` if there is no such subtree in a tree
+    /// of depth `DEPTH`
+    ///
+    /// This is what a remote tree needs to answer the requests made by [`Tree::diff_with`]
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// let tree: Tree<64, _> = smirk! { 1, 2, 3 };
+    ///
+    /// assert_eq!(tree.subtree_hash(SubtreeId::ROOT), Some(tree.root_hash()));
+    /// assert_eq!(tree.subtree_hash(SubtreeId::new(63, Element::new(1))), Some(Element::new(1)));
+    /// assert_eq!(tree.subtree_hash(SubtreeId::new(64, Element::new(1))), None);
+    /// ```
+    #[must_use]
+    pub fn subtree_hash(&self, id: SubtreeId) -> Option<Element> {
+        id.is_valid::<DEPTH>()
+            .then(|| self.tree.subtree_hash::<DEPTH>(id))
+    }
+
+    /// The hashes of the subtrees identified by `ids`, or `None` if any of them don't exist in a
+    /// tree of depth `DEPTH`
+    #[must_use]
+    pub fn subtree_hashes(&self, ids: &[SubtreeId]) -> Option<Vec<Element>> {
+        ids.iter().map(|id| self.subtree_hash(*id)).collect()
+    }
+
+    /// Compare this tree with `other`, returning the elements that differ
+    ///
+    /// Only subtrees whose hashes differ are visited, so this is fast when the trees are similar
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// let a: Tree<64, _> = smirk! { 1, 2, 3 };
+    /// let b: Tree<64, _> = smirk! { 2, 3, 4 };
+    ///
+    /// let diff = a.diff(&b);
+    ///
+    /// assert_eq!(diff.missing, vec![Element::new(4)]);
+    /// assert_eq!(diff.extra, vec![Element::new(1)]);
+    /// ```
+    #[must_use]
+    pub fn diff<V2, C2>(&self, other: &Tree<DEPTH, V2, C2>) -> Diff {
+        self.tree.diff_local::<DEPTH>(&other.tree)
+    }
+
+    /// Compare this tree with a tree that isn't available locally (e.g. one on another node)
+    ///
+    /// `fetch` is called with a list of subtrees, and must return the hashes of those subtrees in
+    /// the other tree, in the same order (e.g. by calling [`Tree::subtree_hashes`] remotely). The
+    /// tree is compared one layer at a time, so `fetch` is called at most `DEPTH` times, and only
+    /// with the children of subtrees whose hashes differ
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// let local: Tree<64, _> = smirk! { 1, 2, 3 };
+    /// let remote: Tree<64, _> = smirk! { 1, 2, 3, 4 };
+    ///
+    /// let diff = local
+    ///     .diff_with(|ids| remote.subtree_hashes(ids).ok_or("invalid subtree"))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(diff.missing, vec![Element::new(4)]);
+    /// assert!(diff.extra.is_empty());
+    /// ```
+    pub fn diff_with<E>(
+        &self,
+        fetch: impl FnMut(&[SubtreeId]) -> Result<Vec<Element>, E>,
+    ) -> Result<Diff, DiffError<E>> {
+        self.tree.diff_with::<DEPTH, E>(fetch)
+    }
+}
+
+impl<const DEPTH: usize> Snapshot<DEPTH> {
+    /// The hash of the subtree identified by `id` when this snapshot was taken
+    ///
+    /// See [`Tree::subtree_hash`] for more details
+    #[must_use]
+    pub fn subtree_hash(&self, id: SubtreeId) -> Option<Element> {
+        id.is_valid::<DEPTH>()
+            .then(|| self.tree.subtree_hash::<DEPTH>(id))
+    }
+
+    /// The hashes of the subtrees identified by `ids` when this snapshot was taken
+    ///
+    /// See [`Tree::subtree_hashes`] for more details
+    #[must_use]
+    pub fn subtree_hashes(&self, ids: &[SubtreeId]) -> Option<Vec<Element>> {
+        ids.iter().map(|id| self.subtree_hash(*id)).collect()
+    }
+
+    /// Compare this snapshot with another snapshot
+    ///
+    /// See [`Tree::diff`] for more details
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> Diff {
+        self.tree.diff_local::<DEPTH>(&other.tree)
+    }
+
+    /// Compare this snapshot with a tree that isn't available locally
+    ///
+    /// See [`Tree::diff_with`] for more details
+    pub fn diff_with<E>(
+        &self,
+        fetch: impl FnMut(&[SubtreeId]) -> Result<Vec<Element>, E>,
+    ) -> Result<Diff, DiffError<E>> {
+        self.tree.diff_with::<DEPTH, E>(fetch)
+    }
+}
+
+impl Node {
+    /// The node at `id`, or `None` if `id` is inside an empty subtree
+    fn subtree(&self, id: SubtreeId) -> Option<&Node> {
+        let mut node = self;
+
+        for bit in id.index.lsb(id.depth) {
+            match node {
+                Node::Parent { left, right, .. } => match bit {
+                    false => node = left,
+                    true => node = right,
+                },
+                Node::Empty { .. } => return None,
+                Node::Leaf(_) => unreachable!("leaves are only at the bottom of the tree"),
+            }
+        }
+
+        Some(node)
+    }
+
+    fn subtree_hash<const DEPTH: usize>(&self, id: SubtreeId) -> Element {
+        self.subtree(id)
+            .map_or_else(|| empty_tree_hash(DEPTH - id.depth), Node::hash)
+    }
+
+    fn diff_local<const DEPTH: usize>(&self, other: &Node) -> Diff {
+        let fetch = |ids: &[SubtreeId]| {
+            let hashes = ids.iter().map(|id| other.subtree_hash::<DEPTH>(*id));
+            Ok::<_, Infallible>(hashes.collect())
+        };
+
+        match self.diff_with::<DEPTH, _>(fetch) {
+            Ok(diff) => diff,
+            Err(DiffError::Fetch(never)) => match never {},
+            Err(DiffError::WrongNumberOfHashes { .. }) => {
+                unreachable!("one hash is fetched per id")
+            }
+        }
+    }
+
+    fn diff_with<const DEPTH: usize, E>(
+        &self,
+        mut fetch: impl FnMut(&[SubtreeId]) -> Result<Vec<Element>, E>,
+    ) -> Result<Diff, DiffError<E>> {
+        let mut diff = Diff::default();
+
+        // the subtrees to compare in the current layer, and the corresponding local node (or
+        // `None` if the subtree is empty locally)
+        let mut layer = vec![(SubtreeId::ROOT, Some(self))];
+
+        while !layer.is_empty() {
+            let ids: Vec<_> = layer.iter().map(|(id, _)| *id).collect();
+            let hashes = fetch(&ids).map_err(DiffError::Fetch)?;
+
+            if hashes.len() != ids.len() {
+                return Err(DiffError::WrongNumberOfHashes {
+                    expected: ids.len(),
+                    actual: hashes.len(),
+                });
+            }
+
+            let mut next_layer = Vec::new();
+
+            for ((id, local), remote_hash) in zip(layer, hashes) {
+                let empty_hash = empty_tree_hash(DEPTH - id.depth);
+                let local_hash = local.map_or(empty_hash, Node::hash);
+
+                if local_hash == remote_hash {
+                    continue;
+                }
+
+                // the hash of a leaf is the element in it
+                if id.depth == DEPTH - 1 {
+                    if remote_hash != Element::NULL_HASH {
+                        diff.missing.push(remote_hash);
+                    }
+
+                    if local_hash != Element::NULL_HASH {
+                        diff.extra.push(local_hash);
+                    }
+
+                    continue;
+                }
+
+                // if the other subtree is empty, there's no need to ask about its children
+                if remote_hash == empty_hash {
+                    if let Some(local) = local {
+                        local.collect_elements(&mut diff.extra);
+                    }
+
+                    continue;
+                }
+
+                let children = match local {
+                    Some(Node::Parent { left, right, .. }) => [Some(&**left), Some(&**right)],
+                    _ => [None, None],
+                };
+
+                next_layer.extend(zip(id.children(), children));
+            }
+
+            layer = next_layer;
+        }
+
+        diff.missing.sort_unstable();
+        diff.extra.sort_unstable();
+
+        Ok(diff)
+    }
+
+    fn collect_elements(&self, elements: &mut Vec<Element>) {
+        match self {
+            Node::Leaf(element) => elements.push(*element),
+            Node::Parent { left, right, .. } => {
+                left.collect_elements(elements);
+                right.collect_elements(elements);
+            }
+            Node::Empty { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::BTreeSet};
+
+    use test_strategy::proptest;
+
+    use crate::smirk;
+
+    use super::*;
+
+    #[test]
+    fn identical_trees_only_compare_roots() {
+        let a: Tree<64, ()> = smirk! { 1, 2, 3 };
+        let b: Tree<64, ()> = smirk! { 3, 2, 1 };
+
+        let calls = Cell::new(0);
+        let diff = a
+            .diff_with(|ids| {
+                calls.set(calls.get() + 1);
+                assert_eq!(ids, [SubtreeId::ROOT]);
+                Ok::<_, Infallible>(b.subtree_hashes(ids).unwrap())
+            })
+            .unwrap();
+
+        assert!(diff.is_empty());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn colliding_elements_are_missing_and_extra() {
+        let colliding = Element::new(1) + (Element::new(1) << 100);
+        let a: Tree<64, ()> = smirk! { 1, 2 };
+        let b: Tree<64, ()> = smirk! { colliding, 2 };
+
+        let diff = a.diff(&b);
+
+        assert_eq!(diff.missing, vec![colliding]);
+        assert_eq!(diff.extra, vec![Element::new(1)]);
+    }
+
+    #[test]
+    fn wrong_number_of_hashes_is_an_error() {
+        let tree: Tree<64, ()> = smirk! { 1, 2, 3 };
+        let result = tree.diff_with(|_| Ok::<_, Infallible>(vec![]));
+
+        assert!(matches!(
+            result,
+            Err(DiffError::WrongNumberOfHashes {
+                expected: 1,
+                actual: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn invalid_subtree_ids() {
+        let tree: Tree<4, ()> = smirk! { 1, 2, 3 };
+
+        let hash = |depth, index| tree.subtree_hash(SubtreeId::new(depth, Element::new(index)));
+
+        assert!(hash(3, 7).is_some());
+        assert!(hash(3, 8).is_none());
+        assert!(hash(4, 0).is_none());
+    }
+
+    #[proptest]
+    fn diff_finds_missing_and_extra_elements(a: Tree<16, i32>, b: Tree<16, i32>) {
+        let a_elements: BTreeSet<_> = a.elements().map(|(element, _)| *element).collect();
+        let b_elements: BTreeSet<_> = b.elements().map(|(element, _)| *element).collect();
+
+        let diff = a.diff(&b);
+
+        // sets iterate in ascending order, which is also the order of the diff
+        let missing: Vec<_> = (&b_elements - &a_elements).into_iter().collect();
+        let extra: Vec<_> = (&a_elements - &b_elements).into_iter().collect();
+
+        assert_eq!(diff.missing, missing);
+        assert_eq!(diff.extra, extra);
+    }
+
+    #[proptest]
+    fn snapshot_diff_matches_tree_diff(a: Tree<16, i32>, b: Tree<16, i32>) {
+        let diff = a
+            .snapshot()
+            .diff_with(|ids| b.subtree_hashes(ids).ok_or(()))
+            .unwrap();
+
+        assert_eq!(diff, a.diff(&b));
+        assert_eq!(diff, a.snapshot().diff(&b.snapshot()));
+    }
+}
@@ -5,17 +5,26 @@ use crate::{
 use std::collections::BTreeMap;
 
 mod batch;
+mod compressed_path;
+mod diff;
 mod error;
 mod insert;
 mod iter;
 mod known_hashes;
+mod non_membership;
 mod path;
 mod raw_api;
+mod remove;
+mod snapshot;
 mod tree_repr;
 
+pub use compressed_path::{CompressedPath, CompressedPathError};
+pub use diff::{Diff, DiffError, SubtreeId};
 pub use error::{Collision, CollisionError};
 pub use iter::{Elements, IntoIter, Iter};
+pub use non_membership::NonMembershipProof;
 pub use path::Path;
+pub use snapshot::Snapshot;
 
 pub(crate) use error::StructName;
 
@@ -0,0 +1,276 @@
+use std::iter::zip;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use rayon::prelude::*;
+
+use crate::{Element, Tree};
+
+use super::tree_repr::Node;
+
+/// A proof that an [`Element`] is *not* in a [`Tree`] with a given root hash
+///
+/// Every element has a slot in the tree, determined by its `DEPTH - 1` least significant bits.
+/// An element is absent if its slot is either empty, or occupied by a different element with the
+/// same least significant bits (i.e. an element that [collides][Element::collides_with] with it).
+/// In the second case, the proof carries the colliding element, since it is needed to recompute
+/// the root hash.
+///
+/// ```rust
+/// # use smirk::*;
+/// let tree: Tree<64, _> = smirk! { 1, 2, 3 };
+///
+/// let proof = tree.non_membership_proof(Element::new(4)).unwrap();
+/// assert!(proof.verify(tree.root_hash()));
+///
+/// // elements in the tree don't have non-membership proofs
+/// assert!(tree.non_membership_proof(Element::new(1)).is_none());
+///
+/// // an element that collides with 1 is still absent
+/// let colliding = Element::new(1) + (Element::new(1) << 100);
+/// let proof = tree.non_membership_proof(colliding).unwrap();
+/// assert_eq!(proof.occupant(), Some(Element::new(1)));
+/// assert!(proof.verify(tree.root_hash()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NonMembershipProof<const DEPTH: usize> {
+    element: Element,
+    occupant: Option<Element>,
+    /// The siblings of the slot, deepest first
+    ///
+    /// This is a `Vec` rather than an array because serde can't (de)serialize arrays with a const
+    /// generic length. A proof with the wrong number of siblings never verifies
+    siblings: Vec<Element>,
+}
+
+impl<const DEPTH: usize> NonMembershipProof<DEPTH> {
+    /// The [`Element`] that this proof proves the absence of
+    #[inline]
+    #[must_use]
+    pub fn element(&self) -> Element {
+        self.element
+    }
+
+    /// The colliding [`Element`] that occupies the slot of [`Self::element`], or `None` if the slot
+    /// is empty
+    #[inline]
+    #[must_use]
+    pub fn occupant(&self) -> Option<Element> {
+        self.occupant
+    }
+
+    /// The siblings of the slot, with the deepest siblings first
+    #[inline]
+    #[must_use]
+    pub fn siblings_deepest_first(&self) -> &[Element] {
+        &self.siblings
+    }
+
+    /// Check that this proof shows that [`Self::element`] is absent from a tree with `root_hash`
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// let mut tree: Tree<64, _> = smirk! { 1, 2, 3 };
+    /// let proof = tree.non_membership_proof(Element::new(4)).unwrap();
+    ///
+    /// assert!(proof.verify(tree.root_hash()));
+    ///
+    /// // once 4 is inserted, the proof is no longer valid for the new root
+    /// tree.insert(Element::new(4), ()).unwrap();
+    /// assert!(!proof.verify(tree.root_hash()));
+    /// ```
+    #[must_use]
+    pub fn verify(&self, root_hash: Element) -> bool {
+        if self.siblings.len() != DEPTH - 1 {
+            return false;
+        }
+
+        let leaf = match self.occupant {
+            None => Element::NULL_HASH,
+            Some(occupant) => {
+                if occupant == self.element
+                    || occupant == Element::NULL_HASH
+                    || !occupant.collides_with::<DEPTH>(self.element)
+                {
+                    return false;
+                }
+
+                occupant
+            }
+        };
+
+        // `.lsb()` yields bits in *big endian* order - so we need to reverse them
+        let bits = self.element.lsb(DEPTH - 1).into_iter().rev();
+        let siblings = self.siblings.iter().copied();
+
+        zk_primitives::compute_merkle_root(leaf, zip(siblings, bits)) == root_hash
+    }
+
+    /// Check that every proof in `proofs` verifies against `root_hash`
+    ///
+    /// Proofs are verified in parallel
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// let tree: Tree<64, _> = smirk! { 1, 2, 3 };
+    /// let proofs = tree.non_membership_proofs(&[4, 5, 6].map(Element::new));
+    /// let proofs: Vec<_> = proofs.into_iter().map(Option::unwrap).collect();
+    ///
+    /// assert!(NonMembershipProof::verify_all(&proofs, tree.root_hash()));
+    /// ```
+    #[must_use]
+    pub fn verify_all(proofs: &[Self], root_hash: Element) -> bool {
+        proofs.par_iter().all(|proof| proof.verify(root_hash))
+    }
+}
+
+impl<const DEPTH: usize, V, C> Tree<DEPTH, V, C> {
+    /// Generate a [`NonMembershipProof`] for `element`, or `None` if `element` is in the tree
+    #[must_use]
+    pub fn non_membership_proof(&self, element: Element) -> Option<NonMembershipProof<DEPTH>> {
+        if self.contains_element(&element) {
+            return None;
+        }
+
+        let path = self.path_for(element);
+        let occupant = self.tree.leaf_for(element, DEPTH);
+
+        Some(NonMembershipProof {
+            element,
+            occupant,
+            siblings: path.siblings_deepest_first().to_vec(),
+        })
+    }
+
+    /// Generate [`NonMembershipProof`]s for many elements in parallel
+    ///
+    /// The result has the same order as `elements`, with `None` for each element that is in the
+    /// tree
+    #[must_use]
+    pub fn non_membership_proofs(
+        &self,
+        elements: &[Element],
+    ) -> Vec<Option<NonMembershipProof<DEPTH>>>
+    where
+        V: Sync,
+        C: Sync,
+    {
+        elements
+            .par_iter()
+            .map(|element| self.non_membership_proof(*element))
+            .collect()
+    }
+}
+
+impl Node {
+    /// The element in the slot for `element` in a tree of depth `depth`, if any
+    pub(super) fn leaf_for(&self, element: Element, depth: usize) -> Option<Element> {
+        let mut node = self;
+
+        for bit in element.lsb(depth - 1) {
+            match node {
+                Node::Parent { left, right, .. } => match bit {
+                    false => node = left,
+                    true => node = right,
+                },
+                Node::Empty { .. } => return None,
+                Node::Leaf(_) => unreachable!("leaves are only at the bottom of the tree"),
+            }
+        }
+
+        match node {
+            Node::Leaf(leaf) => Some(*leaf),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::proptest;
+
+    use crate::smirk;
+
+    use super::*;
+
+    #[test]
+    fn empty_slot_proof() {
+        let tree: Tree<64, ()> = smirk! { 1, 2, 3 };
+        let proof = tree.non_membership_proof(Element::new(4)).unwrap();
+
+        assert_eq!(proof.element(), Element::new(4));
+        assert_eq!(proof.occupant(), None);
+        assert!(proof.verify(tree.root_hash()));
+    }
+
+    #[test]
+    fn colliding_slot_proof() {
+        let colliding = Element::new(1) + (Element::new(1) << 100);
+        let tree: Tree<64, ()> = smirk! { 1, 2, 3 };
+        let proof = tree.non_membership_proof(colliding).unwrap();
+
+        assert_eq!(proof.occupant(), Some(Element::new(1)));
+        assert!(proof.verify(tree.root_hash()));
+
+        // the occupant can't be swapped for the element itself or a non-colliding element
+        let mut forged = proof.clone();
+        forged.occupant = Some(colliding);
+        assert!(!forged.verify(tree.root_hash()));
+
+        forged.occupant = Some(Element::new(2));
+        assert!(!forged.verify(tree.root_hash()));
+    }
+
+    #[test]
+    fn wrong_number_of_siblings_fails() {
+        let tree: Tree<64, ()> = smirk! { 1, 2, 3 };
+        let mut proof = tree.non_membership_proof(Element::new(4)).unwrap();
+        proof.siblings.pop();
+
+        assert!(!proof.verify(tree.root_hash()));
+    }
+
+    #[test]
+    fn borsh_round_trip() {
+        let tree: Tree<64, ()> = smirk! { 1, 2, 3 };
+        let proof = tree.non_membership_proof(Element::new(4)).unwrap();
+
+        let bytes = borsh::to_vec(&proof).unwrap();
+        let decoded = NonMembershipProof::<64>::try_from_slice(&bytes).unwrap();
+
+        assert_eq!(proof, decoded);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let colliding = Element::new(1) + (Element::new(1) << 100);
+        let tree: Tree<64, ()> = smirk! { 1, 2, 3 };
+        let proof = tree.non_membership_proof(colliding).unwrap();
+
+        let json = serde_json::to_string(&proof).unwrap();
+        let decoded: NonMembershipProof<64> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(proof, decoded);
+    }
+
+    #[proptest]
+    fn proof_exists_iff_element_absent(tree: Tree<16, i32>, element: Element) {
+        let proof = tree.non_membership_proof(element);
+
+        assert_eq!(proof.is_some(), !tree.contains_element(&element));
+
+        if let Some(proof) = proof {
+            assert!(proof.verify(tree.root_hash()));
+        }
+    }
+
+    #[proptest]
+    fn batch_matches_individual_proofs(tree: Tree<16, i32>, elements: Vec<Element>) {
+        let proofs = tree.non_membership_proofs(&elements);
+
+        for (element, proof) in elements.iter().zip(proofs) {
+            assert_eq!(proof, tree.non_membership_proof(*element));
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::iter::zip;
 
-use crate::{Element, Lsb, Tree};
+use rayon::prelude::*;
+
+use crate::{hash::empty_tree_hash, Element, Lsb, Tree};
 
 use super::tree_repr::Node;
 
@@ -214,10 +216,42 @@ impl<const DEPTH: usize, V, C> Tree<DEPTH, V, C> {
     /// (either with a real value or [`Element::NULL_HASH`])
     #[must_use]
     pub fn path_for(&self, element: Element) -> Path<DEPTH> {
+        self.tree.path_for(element)
+    }
+
+    /// Generate [`Path`]s for many elements at once
+    ///
+    /// The result has the same order as `elements`, and each path is the same as the one returned
+    /// by [`Tree::path_for`]. Rather than walking the tree once per element, this walks it once
+    /// for all elements, so the upper parts of the tree that the paths have in common are only
+    /// visited once. Large batches are split across threads with rayon
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// let tree: Tree<64, _> = smirk! { 1, 2, 3 };
+    /// let elements = [1, 2, 4].map(Element::new);
+    ///
+    /// let paths = tree.paths_for(&elements);
+    ///
+    /// assert!(paths[0].proves(Element::new(1)));
+    /// assert!(paths[1].proves(Element::new(2)));
+    /// assert!(paths[2].proves(Element::NULL_HASH));
+    /// ```
+    #[must_use]
+    pub fn paths_for(&self, elements: &[Element]) -> Vec<Path<DEPTH>> {
+        self.tree.paths_for(elements)
+    }
+}
+
+/// The number of elements below which [`Node::fill_siblings`] stops splitting work across threads
+const PARALLEL_THRESHOLD: usize = 64;
+
+impl Node {
+    pub(crate) fn path_for<const DEPTH: usize>(&self, element: Element) -> Path<DEPTH> {
         let bits = element.lsb(DEPTH - 1);
 
         let mut siblings = [Element::NULL_HASH; DEPTH];
-        let mut tree = &self.tree;
+        let mut tree = self;
 
         for (index, bit) in bits.iter().enumerate() {
             match tree {
@@ -259,7 +293,94 @@ impl<const DEPTH: usize, V, C> Tree<DEPTH, V, C> {
 
         Path {
             siblings,
-            root_hash: self.root_hash(),
+            root_hash: self.hash(),
+        }
+    }
+
+    pub(crate) fn paths_for<const DEPTH: usize>(&self, elements: &[Element]) -> Vec<Path<DEPTH>> {
+        // sorting by the path bits means each subtree corresponds to a contiguous range
+        let mut sorted: Vec<_> = elements
+            .iter()
+            .enumerate()
+            .map(|(index, element)| (element.lsb(DEPTH - 1), index))
+            .collect();
+        sorted.par_sort_unstable();
+
+        let bits: Vec<_> = sorted.iter().map(|(bits, _)| *bits).collect();
+        let mut siblings = vec![[Element::NULL_HASH; DEPTH]; sorted.len()];
+        self.fill_siblings(&bits, &mut siblings, 0);
+
+        let root_hash = self.hash();
+        let mut paths: Vec<_> = zip(sorted, siblings)
+            .map(|((_, index), mut siblings)| {
+                let element = elements[index];
+
+                // as in `path_for`, the siblings were filled in root first
+                *siblings.last_mut().unwrap() = element;
+                siblings[0..DEPTH - 1].reverse();
+
+                let path = Path {
+                    siblings,
+                    root_hash,
+                };
+
+                (index, path)
+            })
+            .collect();
+        paths.par_sort_unstable_by_key(|(index, _)| *index);
+
+        paths.into_iter().map(|(_, path)| path).collect()
+    }
+
+    /// Fill in the siblings at `path_depth` and below for the sorted `bits`, root first
+    fn fill_siblings<const DEPTH: usize>(
+        &self,
+        bits: &[Lsb],
+        siblings: &mut [[Element; DEPTH]],
+        path_depth: usize,
+    ) {
+        if bits.is_empty() || path_depth == DEPTH - 1 {
+            return;
+        }
+
+        match self {
+            Node::Parent { left, right, .. } => {
+                let split = bits.partition_point(|bits| !bits[path_depth]);
+                let (left_bits, right_bits) = bits.split_at(split);
+                let (left_siblings, right_siblings) = siblings.split_at_mut(split);
+
+                for siblings in &mut *left_siblings {
+                    siblings[path_depth] = right.hash();
+                }
+
+                for siblings in &mut *right_siblings {
+                    siblings[path_depth] = left.hash();
+                }
+
+                let fill_left =
+                    move || left.fill_siblings(left_bits, left_siblings, path_depth + 1);
+                let fill_right =
+                    move || right.fill_siblings(right_bits, right_siblings, path_depth + 1);
+
+                match bits.len() < PARALLEL_THRESHOLD {
+                    true => {
+                        fill_left();
+                        fill_right();
+                    }
+                    false => {
+                        rayon::join(fill_left, fill_right);
+                    }
+                }
+            }
+            // every sibling below an empty node is also empty
+            Node::Empty { depth } => {
+                for siblings in siblings {
+                    for (i, depth) in (1..*depth).rev().enumerate() {
+                        siblings[path_depth + i] = empty_tree_hash(depth);
+                    }
+                }
+            }
+            Node::Leaf(_) => unreachable!("leaves are only at the bottom of the tree"),
         }
     }
 }
@@ -316,6 +437,26 @@ mod tests {
         assert_eq!(path.siblings_deepest_first().len(), 63);
     }
 
+    #[proptest]
+    fn paths_for_matches_path_for(tree: Tree<16, i32>, elements: Vec<Element>) {
+        // include elements that are in the tree, and repeat some to check duplicates
+        let elements: Vec<_> = elements
+            .into_iter()
+            .chain(tree.elements().map(|(element, _)| *element))
+            .chain(tree.elements().map(|(element, _)| *element).take(2))
+            .collect();
+
+        let paths = tree.paths_for(&elements);
+        assert_eq!(paths.len(), elements.len());
+
+        for (element, path) in elements.iter().zip(paths) {
+            let expected = tree.path_for(*element);
+
+            assert_eq!(path.siblings, expected.siblings);
+            assert_eq!(path.actual_root_hash(), expected.actual_root_hash());
+        }
+    }
+
     #[proptest]
     fn lsb_and_siblings_same_size(tree: Tree<16, i32>, element: Element) {
         let path = tree.path_for(element);
@@ -59,4 +59,34 @@ where
 
         Ok(())
     }
+
+    /// Remove from the tree and btreemap at the same time, without updating the hash
+    ///
+    /// Elements that aren't in the tree are ignored. The removed entries are returned
+    pub(crate) fn remove_without_hashing(&mut self, elements: &[Element]) -> Vec<(Element, V)> {
+        let removed: Vec<_> = elements
+            .iter()
+            .filter_map(|element| self.entries.remove_entry(element))
+            .collect();
+
+        if removed.is_empty() {
+            return removed;
+        }
+
+        let mut elements_and_bits = Vec::with_capacity(removed.len());
+        for (element, _) in &removed {
+            elements_and_bits.push((*element, element.lsb(DEPTH - 1).to_bitvec()));
+        }
+        elements_and_bits.sort_unstable_by(|(_, a_bits), (_, b_bits)| a_bits.cmp(b_bits));
+
+        let (elements, bits): (Vec<_>, Vec<_>) = elements_and_bits.into_iter().unzip();
+
+        let changed = self.tree.remove_without_hashing(&elements, &bits, 0);
+        assert!(
+            changed,
+            "we only remove elements that were in the btreemap, so the tree must change"
+        );
+
+        removed
+    }
 }
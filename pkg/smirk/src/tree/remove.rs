@@ -0,0 +1,156 @@
+use crate::{hash_cache::HashCache, Element, Tree};
+
+impl<const DEPTH: usize, V, C: HashCache> Tree<DEPTH, V, C> {
+    /// Remove an element from the tree, returning its value if it was present
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// let mut tree: Tree<64, i32> = Tree::new();
+    /// tree.insert(Element::new(1), 123).unwrap();
+    ///
+    /// assert_eq!(tree.remove(Element::new(1)), Some(123));
+    /// assert_eq!(tree.remove(Element::new(1)), None);
+    ///
+    /// assert_eq!(tree.root_hash(), Tree::<64, i32>::new().root_hash());
+    /// ```
+    ///
+    /// Since this function recalculates all hashes after each removal, it can be quite slow. If you
+    /// need to remove many elements at the same time, use [`Tree::remove_batch`]
+    pub fn remove(&mut self, element: Element) -> Option<V> {
+        self.remove_batch([element], |_| {}, |_| {})
+            .pop()
+            .map(|(_, value)| value)
+    }
+
+    /// Remove multiple elements from the tree, returning the entries that were present
+    ///
+    /// Elements that aren't in the tree are ignored. Like [`Tree::insert_batch`], hashes are only
+    /// recalculated once, after all elements have been removed, and the callbacks are called with
+    /// the hashes that are no longer part of the tree and the hashes that replaced them
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// let mut tree: Tree<64, ()> = smirk! { 1, 2, 3, 4, 5 };
+    ///
+    /// let removed = tree.remove_batch([4, 5, 6].map(Element::new), |_| {}, |_| {});
+    ///
+    /// assert_eq!(removed.len(), 2);
+    /// assert_eq!(tree, smirk! { 1, 2, 3 });
+    /// ```
+    #[tracing::instrument(skip_all)]
+    pub fn remove_batch<I: IntoIterator<Item = Element>>(
+        &mut self,
+        elements: I,
+        hash_remove_callback: impl Fn((&Element, &Element)) + Send + Sync,
+        hash_set_callback: impl Fn((&Element, &Element, &Element)) + Send + Sync,
+    ) -> Vec<(Element, V)> {
+        let elements: Vec<_> = elements.into_iter().collect();
+        let removed = self.remove_without_hashing(&elements);
+
+        tracing::info_span!("recalculate_hashes").in_scope(|| {
+            self.tree
+                .recalculate_hashes(&self.cache, &hash_remove_callback, &hash_set_callback);
+        });
+
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prop_assume;
+    use test_strategy::proptest;
+
+    use crate::{hash_cache::SimpleHashCache, smirk, Batch};
+
+    use super::*;
+
+    #[test]
+    fn simple_remove_example() {
+        let mut tree: Tree<64, i32> = Tree::new();
+        let empty_hash = tree.root_hash();
+
+        tree.insert(Element::new(1), 1).unwrap();
+        let one_hash = tree.root_hash();
+
+        tree.insert(Element::new(2), 2).unwrap();
+
+        assert_eq!(tree.remove(Element::new(2)), Some(2));
+        assert_eq!(tree.root_hash(), one_hash);
+        assert!(!tree.contains_element(&Element::new(2)));
+
+        assert_eq!(tree.remove(Element::new(3)), None);
+        assert_eq!(tree.root_hash(), one_hash);
+
+        assert_eq!(tree.remove(Element::new(1)), Some(1));
+        assert_eq!(tree.root_hash(), empty_hash);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn removing_leaves_collision_free_slot() {
+        let colliding_element = Element::new(1) + (Element::new(1) << 100);
+        let mut tree: Tree<64, ()> = smirk! { 1 };
+
+        assert!(tree.insert(colliding_element, ()).is_err());
+
+        tree.remove(Element::new(1));
+        tree.insert(colliding_element, ()).unwrap();
+
+        assert!(tree.contains_element(&colliding_element));
+    }
+
+    #[test]
+    fn path_after_remove_proves_absence() {
+        let mut tree: Tree<64, ()> = smirk! { 1, 2, 3 };
+        tree.remove(Element::new(2));
+
+        let path = tree.path_for(Element::new(2));
+
+        assert_eq!(path.actual_root_hash(), tree.root_hash());
+        assert_eq!(path.compute_root_hash(Element::NULL_HASH), tree.root_hash());
+    }
+
+    #[proptest]
+    fn remove_matches_tree_without_elements(tree: Tree<16, i32>, remove_mask: Vec<bool>) {
+        let to_remove: HashSet<_> = tree
+            .elements()
+            .zip(remove_mask)
+            .filter_map(|((element, _), remove)| remove.then_some(*element))
+            .collect();
+
+        let mut removed_tree = tree.clone();
+        let removed = removed_tree.remove_batch(to_remove.iter().copied(), |_| {}, |_| {});
+        assert_eq!(removed.len(), to_remove.len());
+
+        let mut expected = Tree::<16, i32>::new();
+        let mut batch = Batch::new();
+        for (element, value) in tree.iter() {
+            if !to_remove.contains(element) {
+                batch.insert(*element, *value).unwrap();
+            }
+        }
+        expected.insert_batch(batch, |_| {}, |_| {}).unwrap();
+
+        assert_eq!(removed_tree.root_hash(), expected.root_hash());
+        assert_eq!(removed_tree.len(), expected.len());
+    }
+
+    #[proptest]
+    fn insert_then_remove_restores_root_hash(
+        mut tree: Tree<16, i32, SimpleHashCache>,
+        batch: Batch<16, i32>,
+    ) {
+        let hash_before = tree.root_hash();
+        let elements: Vec<_> = batch.elements().collect();
+
+        let result = tree.insert_batch(batch, |_| {}, |_| {});
+        prop_assume!(result.is_ok());
+
+        tree.remove_batch(elements, |_| {}, |_| {});
+
+        assert_eq!(tree.root_hash(), hash_before);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{hash_cache::HashCache, Element, Path, Tree};
+
+use super::tree_repr::Node;
+
+/// A read-only view of the structure of a [`Tree`] at the point it was taken
+///
+/// Snapshots share structure with the tree they were taken from, so taking one is cheap, and
+/// later changes to the tree only copy the parts of the tree that they modify. A snapshot doesn't
+/// contain the values of the tree, only what is needed to compute root hashes and [`Path`]s.
+///
+/// ```rust
+/// # use smirk::*;
+/// let mut tree: Tree<64, _> = smirk! { 1, 2, 3 };
+/// let snapshot = tree.snapshot();
+///
+/// tree.insert(Element::new(4), ()).unwrap();
+///
+/// assert_ne!(snapshot.root_hash(), tree.root_hash());
+/// assert!(!snapshot.contains_element(Element::new(4)));
+///
+/// // paths from the snapshot prove against the old root hash
+/// let path = snapshot.path_for(Element::new(1));
+/// assert_eq!(path.actual_root_hash(), snapshot.root_hash());
+/// assert!(path.proves(Element::new(1)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Snapshot<const DEPTH: usize> {
+    pub(super) tree: Node,
+}
+
+impl<const DEPTH: usize> Snapshot<DEPTH> {
+    /// The root hash of the tree when this snapshot was taken
+    #[inline]
+    #[must_use]
+    pub fn root_hash(&self) -> Element {
+        self.tree.hash()
+    }
+
+    /// Generate a [`Path`] for `element` against the tree when this snapshot was taken
+    ///
+    /// See [`Tree::path_for`] for more details
+    #[must_use]
+    pub fn path_for(&self, element: Element) -> Path<DEPTH> {
+        self.tree.path_for(element)
+    }
+
+    /// Generate [`Path`]s for many elements against the tree when this snapshot was taken
+    ///
+    /// See [`Tree::paths_for`] for more details
+    #[must_use]
+    pub fn paths_for(&self, elements: &[Element]) -> Vec<Path<DEPTH>> {
+        self.tree.paths_for(elements)
+    }
+
+    /// Returns `true` if the tree contained `element` when this snapshot was taken
+    #[must_use]
+    pub fn contains_element(&self, element: Element) -> bool {
+        self.tree.leaf_for(element, DEPTH) == Some(element)
+    }
+
+    /// A [`Snapshot`] of the same tree without `elements` (e.g. before they were inserted)
+    ///
+    /// Elements that aren't in the snapshot are ignored. The new snapshot shares the structure
+    /// that didn't change with this one, and its hashes are recalculated using `cache`
+    ///
+    /// ```rust
+    /// # use smirk::*;
+    /// # use smirk::hash_cache::NoopHashCache;
+    /// let mut tree: Tree<64, _> = smirk! { 1, 2 };
+    /// let before = tree.snapshot();
+    ///
+    /// tree.insert(Element::new(3), ()).unwrap();
+    ///
+    /// let snapshot = tree.snapshot().without(&[Element::new(3)], &NoopHashCache);
+    /// assert_eq!(snapshot.root_hash(), before.root_hash());
+    /// ```
+    #[must_use]
+    pub fn without<C: HashCache>(&self, elements: &[Element], cache: &C) -> Self {
+        let mut elements_and_bits: Vec<_> = elements
+            .iter()
+            .filter(|element| self.contains_element(**element))
+            .map(|element| (*element, element.lsb(DEPTH - 1).to_bitvec()))
+            .collect();
+
+        if elements_and_bits.is_empty() {
+            return self.clone();
+        }
+
+        elements_and_bits.sort_unstable_by(|(_, a_bits), (_, b_bits)| a_bits.cmp(b_bits));
+        elements_and_bits.dedup_by_key(|(element, _)| *element);
+        let (elements, bits): (Vec<_>, Vec<_>) = elements_and_bits.into_iter().unzip();
+
+        let mut tree = self.tree.clone();
+        tree.remove_without_hashing(&elements, &bits, 0);
+        tree.recalculate_hashes(cache, &|_| {}, &|_| {});
+
+        Self { tree }
+    }
+}
+
+impl<const DEPTH: usize, V, C> Tree<DEPTH, V, C> {
+    /// Take a [`Snapshot`] of the current structure of the tree
+    #[inline]
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot<DEPTH> {
+        Snapshot {
+            tree: self.tree.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::proptest;
+
+    use crate::{hash_cache::NoopHashCache, Batch};
+
+    use super::*;
+
+    #[proptest]
+    fn snapshot_is_unaffected_by_changes(
+        mut tree: Tree<16, i32>,
+        batch: Batch<16, i32>,
+        element: Element,
+    ) {
+        let before = tree.clone();
+        let snapshot = tree.snapshot();
+
+        let elements: Vec<_> = tree.elements().map(|(e, _)| *e).take(3).collect();
+        tree.remove_batch(elements, |_| {}, |_| {});
+        tree.insert_batch(batch, |_| {}, |_| {}).ok();
+
+        assert_eq!(snapshot.root_hash(), before.root_hash());
+        assert_eq!(
+            snapshot.contains_element(element),
+            before.contains_element(&element)
+        );
+
+        let path = snapshot.path_for(element);
+        let expected = before.path_for(element);
+        assert_eq!(path.siblings, expected.siblings);
+        assert_eq!(path.actual_root_hash(), expected.actual_root_hash());
+    }
+
+    #[proptest]
+    fn without_matches_removing_from_tree(tree: Tree<16, i32>, other: Element) {
+        let snapshot = tree.snapshot();
+
+        let mut elements: Vec<_> = tree.elements().map(|(e, _)| *e).take(3).collect();
+        let mut expected = tree.clone();
+        expected.remove_batch(elements.clone(), |_| {}, |_| {});
+
+        // elements that aren't in the tree are ignored
+        if !tree.contains_element(&other) {
+            elements.push(other);
+        }
+        let without = snapshot.without(&elements, &NoopHashCache);
+
+        assert_eq!(without.root_hash(), expected.root_hash());
+        assert_eq!(snapshot.root_hash(), tree.root_hash());
+        for element in &elements {
+            assert_eq!(
+                without.contains_element(*element),
+                expected.contains_element(element)
+            );
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use bitvec::{prelude::Msb0, vec::BitVec};
 
 use crate::{hash::empty_tree_hash, hash_cache::HashCache, Collision, Element};
@@ -5,6 +7,9 @@ use crate::{hash::empty_tree_hash, hash_cache::HashCache, Collision, Element};
 use super::StructName;
 
 /// A tree-like representation of a sparse tree, for easier computation of merkle paths and hashes
+///
+/// Children are reference counted, so cloning a node is cheap and clones share structure. Mutating
+/// a shared subtree copies only the nodes on the path that changes (see [`Arc::make_mut`])
 #[derive(Debug, Clone)]
 pub(crate) enum Node {
     /// A single leaf at the max depth of the tree
@@ -18,8 +23,8 @@ pub(crate) enum Node {
 
     /// A parent of two nodes with a cached hash
     Parent {
-        left: Box<Self>,
-        right: Box<Self>,
+        left: Arc<Self>,
+        right: Arc<Self>,
         hash: Element,
         /// if true, the children have changed without recalculating the hash
         hash_dirty: bool,
@@ -93,8 +98,8 @@ impl Node {
             }
             Self::Empty { depth } => {
                 let child = Self::Parent {
-                    left: Box::new(Self::Empty { depth: *depth - 1 }),
-                    right: Box::new(Self::Empty { depth: *depth - 1 }),
+                    left: Arc::new(Self::Empty { depth: *depth - 1 }),
+                    right: Arc::new(Self::Empty { depth: *depth - 1 }),
                     hash: empty_tree_hash(*depth),
                     hash_dirty: false,
                 };
@@ -171,15 +176,33 @@ impl Node {
                 let (left, right) = match (lefts.is_empty(), rights.is_empty()) {
                     (true, true) => return Ok(false),
                     (false, true) => (
-                        { left.insert_without_hashing::<N>(lefts_elements, lefts, path_depth + 1) },
+                        {
+                            Arc::make_mut(left).insert_without_hashing::<N>(
+                                lefts_elements,
+                                lefts,
+                                path_depth + 1,
+                            )
+                        },
                         Ok(false),
                     ),
                     (true, false) => (Ok(false), {
-                        right.insert_without_hashing::<N>(rights_elements, rights, path_depth + 1)
+                        Arc::make_mut(right).insert_without_hashing::<N>(
+                            rights_elements,
+                            rights,
+                            path_depth + 1,
+                        )
                     }),
                     (false, false) => (
-                        right.insert_without_hashing::<N>(rights_elements, rights, path_depth + 1),
-                        left.insert_without_hashing::<N>(lefts_elements, lefts, path_depth + 1),
+                        Arc::make_mut(right).insert_without_hashing::<N>(
+                            rights_elements,
+                            rights,
+                            path_depth + 1,
+                        ),
+                        Arc::make_mut(left).insert_without_hashing::<N>(
+                            lefts_elements,
+                            lefts,
+                            path_depth + 1,
+                        ),
                     ),
                 };
 
@@ -199,8 +222,8 @@ impl Node {
             Self::Empty { depth } => {
                 // split an empty tree into two empty subtrees
                 *self = Self::Parent {
-                    left: Box::new(Self::Empty { depth: *depth - 1 }),
-                    right: Box::new(Self::Empty { depth: *depth - 1 }),
+                    left: Arc::new(Self::Empty { depth: *depth - 1 }),
+                    right: Arc::new(Self::Empty { depth: *depth - 1 }),
                     hash: empty_tree_hash(*depth),
                     hash_dirty: false,
                 };
@@ -211,6 +234,69 @@ impl Node {
         }
     }
 
+    /// Remove elements and return whether the tree changed
+    ///
+    /// Like [`Node::insert_without_hashing`], this only marks nodes as "dirty", and subtrees that
+    /// become empty are only collapsed back into [`Node::Empty`] by [`Node::recalculate_hashes`]
+    ///
+    /// The elements and bits should be sorted by the bits before calling this function
+    pub(crate) fn remove_without_hashing(
+        &mut self,
+        elements: &[Element],
+        bits: &[BitVec<u8, Msb0>],
+        path_depth: usize,
+    ) -> bool {
+        match self {
+            Self::Leaf(e) if elements.contains(e) => {
+                *self = Self::Empty { depth: 1 };
+                true
+            }
+            Self::Leaf(_) | Self::Empty { .. } => false,
+            Self::Parent {
+                left,
+                right,
+                hash_dirty,
+                ..
+            } => {
+                let rights_start = bits
+                    .iter()
+                    .position(|b| b[path_depth])
+                    .unwrap_or(bits.len());
+                let lefts = &bits[..rights_start];
+                let rights = &bits[rights_start..];
+                let lefts_elements = &elements[..rights_start];
+                let rights_elements = &elements[rights_start..];
+
+                let left_changed = !lefts.is_empty()
+                    && Arc::make_mut(left).remove_without_hashing(
+                        lefts_elements,
+                        lefts,
+                        path_depth + 1,
+                    );
+                let right_changed = !rights.is_empty()
+                    && Arc::make_mut(right).remove_without_hashing(
+                        rights_elements,
+                        rights,
+                        path_depth + 1,
+                    );
+
+                *hash_dirty |= left_changed || right_changed;
+
+                left_changed || right_changed
+            }
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        matches!(
+            self,
+            Self::Parent {
+                hash_dirty: true,
+                ..
+            }
+        )
+    }
+
     pub fn recalculate_hashes<C: HashCache>(
         &mut self,
         cache: &C,
@@ -235,11 +321,34 @@ impl Node {
         let right_hash_before = right.hash();
         hash_remove_callback((&left_hash_before, &right_hash_before));
 
+        // only dirty children need to be made unique, clean ones can stay shared
         rayon::join(
-            || left.recalculate_hashes(cache, hash_remove_callback, hash_set_callback),
-            || right.recalculate_hashes(cache, hash_remove_callback, hash_set_callback),
+            || {
+                if left.is_dirty() {
+                    Arc::make_mut(left).recalculate_hashes(
+                        cache,
+                        hash_remove_callback,
+                        hash_set_callback,
+                    );
+                }
+            },
+            || {
+                if right.is_dirty() {
+                    Arc::make_mut(right).recalculate_hashes(
+                        cache,
+                        hash_remove_callback,
+                        hash_set_callback,
+                    );
+                }
+            },
         );
 
+        // removals can leave a parent with no elements under it
+        if let (Self::Empty { depth }, Self::Empty { .. }) = (&**left, &**right) {
+            *self = Self::Empty { depth: depth + 1 };
+            return;
+        }
+
         let left_hash = left.hash();
         let right_hash = right.hash();
         *hash = cache.hash(left_hash, right_hash);
@@ -255,6 +364,8 @@ mod tests {
 
     use crate::{Batch, Tree};
 
+    use super::Node;
+
     #[proptest]
     fn root_hash_with_matches_insert(mut tree: Tree<16, i32>, batch: Batch<16, i32>) {
         let hash_with = tree.root_hash_with(&batch.elements().collect::<Vec<_>>());
@@ -264,4 +375,12 @@ mod tests {
 
         assert_eq!(tree.root_hash(), hash_with);
     }
+
+    #[proptest]
+    fn removing_everything_collapses_to_empty(mut tree: Tree<16, i32>) {
+        let elements: Vec<_> = tree.elements().map(|(e, _)| *e).collect();
+        tree.remove_batch(elements, |_| {}, |_| {});
+
+        assert!(matches!(tree.tree, Node::Empty { depth: 16 }));
+    }
 }
@@ -0,0 +1,203 @@
+use core::fmt;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer};
+
+/// A range of IP addresses in CIDR notation (e.g. `10.0.0.0/8` or `fd00::/8`)
+///
+/// A bare IP address (e.g. `10.0.0.1`) is parsed as a range containing only that address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    /// Create a new [`IpNet`], returning `None` if `prefix_len` is longer than the address
+    ///
+    /// Any host bits in `addr` are cleared, so `10.1.2.3/8` is the same range as `10.0.0.0/8`
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<IpNet> {
+        if prefix_len > max_prefix_len(&addr) {
+            return None;
+        }
+
+        let addr = match addr {
+            IpAddr::V4(v4) => IpAddr::V4(Ipv4Addr::from(
+                u32::from(v4)
+                    & u32::MAX
+                        .checked_shl(32 - u32::from(prefix_len))
+                        .unwrap_or(0),
+            )),
+            IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from(
+                u128::from(v6)
+                    & u128::MAX
+                        .checked_shl(128 - u32::from(prefix_len))
+                        .unwrap_or(0),
+            )),
+        };
+
+        Some(IpNet { addr, prefix_len })
+    }
+
+    /// The first address of this range
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The number of leading bits that addresses in this range share
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Is `ip` inside this range
+    ///
+    /// IPv4 addresses are never inside IPv6 ranges and vice versa
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match IpNet::new(*ip, self.prefix_len) {
+            Some(net) => net.addr == self.addr,
+            None => false,
+        }
+    }
+}
+
+fn max_prefix_len(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+impl From<IpAddr> for IpNet {
+    fn from(addr: IpAddr) -> Self {
+        IpNet {
+            addr,
+            prefix_len: max_prefix_len(&addr),
+        }
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIpNetError(String);
+
+impl fmt::Display for ParseIpNetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid IP range: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseIpNetError {}
+
+impl FromStr for IpNet {
+    type Err = ParseIpNetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseIpNetError(s.to_string());
+
+        match s.split_once('/') {
+            None => Ok(IpNet::from(IpAddr::from_str(s).map_err(|_| err())?)),
+            Some((addr, prefix_len)) => {
+                let addr = IpAddr::from_str(addr).map_err(|_| err())?;
+                let prefix_len = u8::from_str(prefix_len).map_err(|_| err())?;
+                IpNet::new(addr, prefix_len).ok_or_else(err)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for IpNet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn bare_ip_is_a_single_address() {
+        assert_eq!(net("10.0.0.1"), IpNet::new(ip("10.0.0.1"), 32).unwrap());
+        assert_eq!(net("fd00::1"), IpNet::new(ip("fd00::1"), 128).unwrap());
+    }
+
+    #[test]
+    fn parse_clears_host_bits() {
+        let net = net("10.1.2.3/8");
+
+        assert_eq!(net.addr(), ip("10.0.0.0"));
+        assert_eq!(net.prefix_len(), 8);
+        assert_eq!(net.to_string(), "10.0.0.0/8");
+        assert_eq!(
+            IpNet::from_str("fd12:3456::1/16").unwrap().to_string(),
+            "fd12::/16"
+        );
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        for s in [
+            "",
+            "10.0.0",
+            "10.0.0.0/",
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0.0/-1",
+            "10.0.0.0/8/8",
+            "example.com/8",
+        ] {
+            assert!(IpNet::from_str(s).is_err(), "{s} should not parse");
+        }
+    }
+
+    #[test]
+    fn contains_addresses_in_range() {
+        let v4 = net("192.168.0.0/16");
+        assert!(v4.contains(&ip("192.168.0.0")));
+        assert!(v4.contains(&ip("192.168.255.255")));
+        assert!(!v4.contains(&ip("192.169.0.0")));
+
+        let v6 = net("fd00::/8");
+        assert!(v6.contains(&ip("fd12::1")));
+        assert!(!v6.contains(&ip("fe80::1")));
+
+        let single = net("10.0.0.1");
+        assert!(single.contains(&ip("10.0.0.1")));
+        assert!(!single.contains(&ip("10.0.0.2")));
+    }
+
+    #[test]
+    fn zero_prefix_contains_its_whole_family() {
+        assert!(net("0.0.0.0/0").contains(&ip("255.255.255.255")));
+        assert!(net("::/0").contains(&ip("ffff::1")));
+
+        assert!(!net("0.0.0.0/0").contains(&ip("::1")));
+        assert!(!net("::/0").contains(&ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn families_never_overlap() {
+        assert!(!net("10.0.0.0/8").contains(&ip("::ffff:10.0.0.1")));
+        assert!(!net("::ffff:0:0/96").contains(&ip("10.0.0.1")));
+    }
+}
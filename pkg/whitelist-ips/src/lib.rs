@@ -1,29 +1,53 @@
 use core::fmt;
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     net::IpAddr,
     task::{Context, Poll, Waker},
 };
 
 use libp2p::{
+    core::Endpoint,
     multiaddr::Protocol,
     swarm::{
-        dummy, CloseConnection, ConnectionDenied, ConnectionId, NetworkBehaviour, PollParameters,
-        THandler, THandlerInEvent, ToSwarm,
+        dummy, CloseConnection, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour,
+        PollParameters, THandler, THandlerInEvent, ToSwarm,
     },
     Multiaddr, PeerId,
 };
 
+pub use ip_net::{IpNet, ParseIpNetError};
+
+mod ip_net;
+
 #[derive(Debug, Clone, Default)]
 #[non_exhaustive]
 pub struct Config {
-    pub whitelisted_ips: HashSet<IpAddr>,
+    /// IP ranges that are allowed to connect
+    ///
+    /// If empty, all IPs that aren't banned are allowed
+    pub whitelisted_ips: HashSet<IpNet>,
+
+    /// IP ranges that are never allowed to connect, even if they are also whitelisted
+    pub banned_ips: HashSet<IpNet>,
+}
+
+impl Config {
+    /// Is a connection from this IP allowed by the configured rules
+    pub fn is_allowed(&self, ip: &IpAddr) -> bool {
+        if self.banned_ips.iter().any(|net| net.contains(ip)) {
+            return false;
+        }
+
+        self.whitelisted_ips.is_empty() || self.whitelisted_ips.iter().any(|net| net.contains(ip))
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Behaviour {
     config: Config,
-    close_connections: VecDeque<PeerId>,
+    /// Established connections, so they can be re-checked when the config changes
+    connections: HashMap<ConnectionId, (PeerId, Multiaddr)>,
+    close_connections: VecDeque<(PeerId, ConnectionId)>,
     waker: Option<Waker>,
 }
 
@@ -39,12 +63,34 @@ impl Behaviour {
         }
     }
 
-    pub fn whitelisted_ips(&self) -> &HashSet<IpAddr> {
+    pub fn whitelisted_ips(&self) -> &HashSet<IpNet> {
         &self.config.whitelisted_ips
     }
 
-    pub fn whitelisted_ips_mut(&mut self) -> &mut HashSet<IpAddr> {
-        &mut self.config.whitelisted_ips
+    pub fn banned_ips(&self) -> &HashSet<IpNet> {
+        &self.config.banned_ips
+    }
+
+    /// Replace the whitelist and banlist
+    ///
+    /// Established connections that are no longer allowed are closed straight away
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+
+        for (connection_id, (peer, addr)) in &self.connections {
+            let connection = (*peer, *connection_id);
+
+            if !allowed(addr, &self.config) && !self.close_connections.contains(&connection) {
+                tracing::info!(peer_id = ?peer, addr = ?addr, "Closing connection that is no longer allowed");
+                self.close_connections.push_back(connection);
+            }
+        }
+
+        if !self.close_connections.is_empty() {
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
     }
 }
 
@@ -52,8 +98,10 @@ impl NetworkBehaviour for Behaviour {
     type ConnectionHandler = libp2p::swarm::dummy::ConnectionHandler;
     type OutEvent = ();
 
-    fn on_swarm_event(&mut self, _event: libp2p::swarm::FromSwarm<Self::ConnectionHandler>) {
-        // do nothing
+    fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
+        if let FromSwarm::ConnectionClosed(closed) = event {
+            self.connections.remove(&closed.connection_id);
+        }
     }
 
     fn on_connection_handler_event(
@@ -75,18 +123,40 @@ impl NetworkBehaviour for Behaviour {
 
     fn handle_established_inbound_connection(
         &mut self,
-        _: ConnectionId,
+        connection_id: ConnectionId,
         peer: PeerId,
         _local_addr: &Multiaddr,
         remote_addr: &Multiaddr,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        if !allowed(remote_addr, &self.config.whitelisted_ips) {
+        if !allowed(remote_addr, &self.config) {
             return Err(ConnectionDenied::new(NotAllowed {
                 peer,
                 addr: remote_addr.clone(),
             }));
         }
 
+        self.connections
+            .insert(connection_id, (peer, remote_addr.clone()));
+
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        _role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        if !allowed(addr, &self.config) {
+            return Err(ConnectionDenied::new(NotAllowed {
+                peer,
+                addr: addr.clone(),
+            }));
+        }
+
+        self.connections.insert(connection_id, (peer, addr.clone()));
+
         Ok(dummy::ConnectionHandler)
     }
 
@@ -95,10 +165,10 @@ impl NetworkBehaviour for Behaviour {
         cx: &mut Context<'_>,
         _: &mut impl PollParameters,
     ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
-        if let Some(peer) = self.close_connections.pop_front() {
+        if let Some((peer, connection_id)) = self.close_connections.pop_front() {
             return Poll::Ready(ToSwarm::CloseConnection {
                 peer_id: peer,
-                connection: CloseConnection::All,
+                connection: CloseConnection::One(connection_id),
             });
         }
 
@@ -107,14 +177,19 @@ impl NetworkBehaviour for Behaviour {
     }
 }
 
-fn allowed(multiaddr: &Multiaddr, ips: &HashSet<IpAddr>) -> bool {
+/// Is a connection to or from this address allowed
+///
+/// Addresses that don't start with an IP are peers we dialed by hostname (e.g. `/dns4/...`). The
+/// resolved IP isn't known here, so they are always allowed, like bootstrap peers configured by
+/// the operator
+fn allowed(multiaddr: &Multiaddr, config: &Config) -> bool {
     let ip = match multiaddr.iter().next() {
         Some(Protocol::Ip4(ip4)) => IpAddr::V4(ip4),
         Some(Protocol::Ip6(ip6)) => IpAddr::V6(ip6),
-        _other => return false,
+        _other => return true,
     };
 
-    ips.contains(&ip)
+    config.is_allowed(&ip)
 }
 
 #[derive(Debug)]
@@ -127,10 +202,67 @@ impl fmt::Display for NotAllowed {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "peer {} (at addr {}) is not in the allow list",
+            "peer {} (at addr {}) is not allowed by the IP filter",
             self.peer, self.addr
         )
     }
 }
 
 impl std::error::Error for NotAllowed {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    fn dial(behaviour: &mut Behaviour, addr: &Multiaddr) -> bool {
+        behaviour
+            .handle_established_outbound_connection(
+                ConnectionId::new_unchecked(0),
+                PeerId::random(),
+                addr,
+                Endpoint::Dialer,
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn dns_dial_allowed_without_rules() {
+        let mut behaviour = Behaviour::new_with_config(Config::default());
+
+        assert!(dial(&mut behaviour, &addr("/dns4/example.com/tcp/5000")));
+        assert!(dial(&mut behaviour, &addr("/dns6/example.com/tcp/5000")));
+        assert!(dial(&mut behaviour, &addr("/ip4/1.2.3.4/tcp/5000")));
+    }
+
+    #[test]
+    fn dns_dial_allowed_with_rules() {
+        let config = Config {
+            whitelisted_ips: ["127.0.0.0/8".parse().unwrap()].into_iter().collect(),
+            banned_ips: ["10.0.0.1/32".parse().unwrap()].into_iter().collect(),
+        };
+        let mut behaviour = Behaviour::new_with_config(config);
+
+        // `localhost` resolves to a whitelisted IP
+        assert!(dial(&mut behaviour, &addr("/dns4/localhost/tcp/5000")));
+        assert!(dial(&mut behaviour, &addr("/ip4/127.0.0.1/tcp/5000")));
+        assert!(!dial(&mut behaviour, &addr("/ip4/1.2.3.4/tcp/5000")));
+        assert!(!dial(&mut behaviour, &addr("/ip4/10.0.0.1/tcp/5000")));
+    }
+
+    #[test]
+    fn set_config_keeps_dns_connections() {
+        let mut behaviour = Behaviour::new_with_config(Config::default());
+        assert!(dial(&mut behaviour, &addr("/dns4/example.com/tcp/5000")));
+
+        behaviour.set_config(Config {
+            whitelisted_ips: HashSet::new(),
+            banned_ips: ["10.0.0.1/32".parse().unwrap()].into_iter().collect(),
+        });
+
+        assert!(behaviour.close_connections.is_empty());
+    }
+}
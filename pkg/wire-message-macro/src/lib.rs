@@ -1,13 +1,14 @@
 use proc_macro2::TokenStream;
 use quote::{quote, quote_spanned};
-use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput};
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Ident};
 
 #[proc_macro_attribute]
 pub fn wire_message(
-    _attr: proc_macro::TokenStream,
+    attr: proc_macro::TokenStream,
     tokens: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
     let input = parse_macro_input!(tokens as DeriveInput);
+    let attr = TokenStream::from(attr);
 
     let extra_attrs = quote::quote! {
         #[derive(::borsh::BorshSerialize, ::borsh::BorshDeserialize, ::wire_message::strum_macros::EnumCount)]
@@ -21,15 +22,61 @@ pub fn wire_message(
 
     let check_enum = check_enum(&input);
 
+    let version_schemas = if attr.is_empty() {
+        quote! {}
+    } else {
+        match syn::parse2::<Ident>(attr.clone()) {
+            Ok(ident) if ident == "schema" => version_schemas(&input),
+            _ => {
+                quote_spanned! { attr.span() => ::core::compile_error!("expected `#[wire_message]` or `#[wire_message(schema)]`")}
+            }
+        }
+    };
+
     quote::quote! {
         #check_enum
         #extra_attrs
         #enum_try_as
         #input
+        #version_schemas
     }
     .into()
 }
 
+// The schema of each version is the schema of the tuple of its variant's fields, since the field
+// names don't change the borsh layout
+fn version_schemas(input: &DeriveInput) -> TokenStream {
+    let Data::Enum(data) = &input.data else {
+        // `check_enum` reports the error
+        return quote! {};
+    };
+
+    if has_generics(input) {
+        return quote_spanned! { input.generics.span() => ::core::compile_error!("`#[wire_message(schema)]` can't be used on a generic enum")};
+    }
+
+    let name = &input.ident;
+    let schemas = data.variants.iter().map(|variant| {
+        let variant_name = variant.ident.to_string();
+        let field_types = variant.fields.iter().map(|field| &field.ty);
+
+        quote! {
+            (
+                #variant_name,
+                ::wire_message::borsh::schema_container_of::<(#(#field_types,)*)>(),
+            )
+        }
+    });
+
+    quote! {
+        impl ::wire_message::schema::VersionSchemas for #name {
+            fn version_schemas() -> ::std::vec::Vec<(&'static str, ::wire_message::borsh::schema::BorshSchemaContainer)> {
+                ::std::vec![#(#schemas),*]
+            }
+        }
+    }
+}
+
 // EnumTryAs doesn't work with generics
 fn has_generics(input: &DeriveInput) -> bool {
     !input.generics.params.is_empty()
@@ -20,6 +20,11 @@ pub enum ErrorKind<T = core::convert::Infallible> {
     Deserialize,
     Upgrade(T),
     MaxVersion { version: u64 },
+    MinVersion,
+    DowngradeUnsupported { version: u64 },
+    Unrepresentable { version: u64, target: u64 },
+    InvalidVersion { version: u64, max_version: u64 },
+    Json,
 }
 
 impl<T> Error<T> {
@@ -60,6 +65,36 @@ impl<T> Error<T> {
     pub fn is_max_version(&self) -> bool {
         matches!(&self.kind, ErrorKind::MaxVersion { .. })
     }
+
+    /// Is this an error from converting a message to or from JSON
+    #[inline]
+    #[must_use]
+    pub fn is_json(&self) -> bool {
+        matches!(&self.kind, ErrorKind::Json)
+    }
+
+    /// Is this an error from downgrading a message that can't be downgraded, either because the
+    /// type doesn't support it or because the value can't be represented at the lower version
+    #[inline]
+    #[must_use]
+    pub fn is_downgrade(&self) -> bool {
+        matches!(
+            &self.kind,
+            ErrorKind::MinVersion
+                | ErrorKind::DowngradeUnsupported { .. }
+                | ErrorKind::Unrepresentable { .. }
+        )
+    }
+}
+
+impl<T> Error<T> {
+    /// Write `message`, followed by the source error if there is one
+    fn fmt_with_source(&self, f: &mut fmt::Formatter<'_>, message: &str) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{message}: {source}"),
+            None => f.write_str(message),
+        }
+    }
 }
 
 impl<T> fmt::Display for Error<T>
@@ -68,15 +103,29 @@ where
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.kind {
-            ErrorKind::Serialize => write!(f, "serialize error: {}", self.source.as_ref().unwrap()),
-            ErrorKind::Deserialize => {
-                write!(f, "deserialize error: {}", self.source.as_ref().unwrap())
-            }
+            ErrorKind::Serialize => self.fmt_with_source(f, "serialize error"),
+            ErrorKind::Deserialize => self.fmt_with_source(f, "deserialize error"),
             ErrorKind::MaxVersion { version } => write!(
                 f,
                 "tried to upgrade, but the version was {version}, which is the max version"
             ),
             ErrorKind::Upgrade(e) => write!(f, "failed to upgrade: {e}"),
+            ErrorKind::MinVersion => write!(f, "tried to downgrade, but the version was 1"),
+            ErrorKind::DowngradeUnsupported { version } => {
+                write!(f, "downgrading from version {version} is not supported")
+            }
+            ErrorKind::Unrepresentable { version, target } => write!(
+                f,
+                "the value at version {version} can't be represented at version {target}"
+            ),
+            ErrorKind::InvalidVersion {
+                version,
+                max_version,
+            } => write!(
+                f,
+                "version {version} doesn't exist, versions are 1 to {max_version}"
+            ),
+            ErrorKind::Json => self.fmt_with_source(f, "json error"),
         }
     }
 }
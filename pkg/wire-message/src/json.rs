@@ -0,0 +1,94 @@
+//! Conversion of [`WireMessage`]s to and from versioned JSON, for debugging
+//!
+//! Messages are stored as borsh, which can't be read without the Rust types. The JSON form of a
+//! message is `{"version": <version>, "message": <serde representation>}`. Messages are converted
+//! at the version they were stored at, so decoding and encoding bytes doesn't change them
+
+use std::{backtrace::Backtrace, fmt};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Error, ErrorKind, WireMessage};
+
+/// Convert `message` to versioned JSON
+pub fn to_json<T: WireMessage + Serialize>(message: &T) -> Result<Value, Error> {
+    Ok(serde_json::json!({
+        "version": message.version(),
+        "message": serde_json::to_value(message).map_err(json_error)?,
+    }))
+}
+
+/// Convert versioned JSON, as returned by [`to_json`], back to a message
+///
+/// Fails if the `version` field doesn't match the version of the message
+pub fn from_json<T: WireMessage + DeserializeOwned>(json: Value) -> Result<T, Error> {
+    #[derive(Deserialize)]
+    struct Versioned<T> {
+        version: u64,
+        message: T,
+    }
+
+    let Versioned { version, message } =
+        serde_json::from_value::<Versioned<T>>(json).map_err(json_error)?;
+
+    if message.version() != version {
+        return Err(json_error(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "the message is version {}, but the version field is {version}",
+                message.version()
+            ),
+        )));
+    }
+
+    Ok(message)
+}
+
+/// A [`WireMessage`] type that can be converted between bytes and JSON, so types can be chosen by
+/// name at runtime (e.g. by a CLI)
+#[derive(Clone, Copy)]
+pub struct Codec {
+    name: &'static str,
+    decode: fn(&[u8]) -> Result<Value, Error>,
+    encode: fn(Value) -> Result<Vec<u8>, Error>,
+}
+
+impl Codec {
+    pub fn new<T: WireMessage + Serialize + DeserializeOwned>(name: &'static str) -> Self {
+        Self {
+            name,
+            decode: |bytes| to_json(&T::from_bytes(bytes)?),
+            encode: |json| from_json::<T>(json)?.to_bytes(),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Decode the bytes of a message to versioned JSON
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value, Error> {
+        (self.decode)(bytes)
+    }
+
+    /// Encode versioned JSON to the bytes of a message
+    pub fn encode(&self, json: Value) -> Result<Vec<u8>, Error> {
+        (self.encode)(json)
+    }
+}
+
+impl fmt::Debug for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Codec").field("name", &self.name).finish()
+    }
+}
+
+fn json_error(e: impl Into<std::io::Error>) -> Error {
+    Error {
+        kind: ErrorKind::Json,
+        backtrace: Backtrace::capture(),
+        source: Some(e.into()),
+    }
+}
@@ -11,8 +11,13 @@ pub use strum_macros;
 pub use error::{Error, ErrorKind};
 
 /// Add required supertrait impls to a [`WireMessage`] implementer
+///
+/// `#[wire_message(schema)]` also implements [`schema::VersionSchemas`]
 pub use wire_message_macro::wire_message;
 mod error;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod schema;
 
 #[cfg(feature = "test-api")]
 pub mod test_api;
@@ -54,6 +59,45 @@ pub trait WireMessage:
         Ok(self)
     }
 
+    /// Downgrade this message to the next lowest version, so it can be read by older code
+    ///
+    /// Implementations should return [`Self::unrepresentable_error`] if this value can't be
+    /// represented at the lower version, and [`Self::min_version_error`] at version 1. The default
+    /// implementation doesn't support downgrading
+    fn downgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, Error> {
+        Err(Error {
+            kind: ErrorKind::DowngradeUnsupported {
+                version: self.version(),
+            },
+            backtrace: Backtrace::capture(),
+            source: None,
+        })
+    }
+
+    /// Upgrade or downgrade this message until it is at `version`
+    fn into_version(mut self, version: u64, ctx: &mut Self::Ctx) -> Result<Self, Error> {
+        if version == 0 || version > Self::MAX_VERSION {
+            return Err(Error {
+                kind: ErrorKind::InvalidVersion {
+                    version,
+                    max_version: Self::MAX_VERSION,
+                },
+                backtrace: Backtrace::capture(),
+                source: None,
+            });
+        }
+
+        while self.version() < version {
+            self = self.upgrade_once(ctx)?;
+        }
+
+        while self.version() > version {
+            self = self.downgrade_once(ctx)?;
+        }
+
+        Ok(self)
+    }
+
     /// Deserialize an instance of `Self` from bytes
     fn from_bytes(mut bytes: &[u8]) -> Result<Self, Error> {
         #[allow(clippy::disallowed_methods)]
@@ -84,6 +128,21 @@ pub trait WireMessage:
         })
     }
 
+    /// Serialize this instance at `version`, so it can be read by code whose maximum version is
+    /// `version` (e.g. a peer running an older release)
+    ///
+    /// Fails if the message can't be converted to `version`, see [`WireMessage::into_version`]
+    fn to_bytes_at_version(&self, version: u64, ctx: &mut Self::Ctx) -> Result<Vec<u8>, Error>
+    where
+        Self: Clone,
+    {
+        if self.version() == version {
+            return self.to_bytes();
+        }
+
+        self.clone().into_version(version, ctx)?.to_bytes()
+    }
+
     /// Serialize this instance and write the bytes to an instance of [`Write`][std::io::Write]
     fn to_bytes_in<W: std::io::Write>(&self, writer: W) -> Result<(), Error> {
         #[allow(clippy::disallowed_methods)]
@@ -105,4 +164,25 @@ pub trait WireMessage:
             source: None,
         }
     }
+
+    /// Construct an [`Error`] representing the case where you are trying to downgrade the minimum
+    /// version of a message type
+    fn min_version_error() -> Error {
+        Error {
+            kind: ErrorKind::MinVersion,
+            backtrace: Backtrace::capture(),
+            source: None,
+        }
+    }
+
+    /// Construct an [`Error`] representing the case where a value at `version` can't be
+    /// represented at the lower version `target` (e.g. because it uses a field that was added
+    /// later)
+    fn unrepresentable_error(version: u64, target: u64) -> Error {
+        Error {
+            kind: ErrorKind::Unrepresentable { version, target },
+            backtrace: Backtrace::capture(),
+            source: None,
+        }
+    }
 }
@@ -0,0 +1,65 @@
+//! Fingerprints of the borsh schema of each version of a [`WireMessage`]
+//!
+//! Stored messages are deserialized with the layout of their version, so a version's layout must
+//! never change once it has been released. Fingerprints make changes to a version easy to detect
+//! (e.g. with [`assert_schema_snapshot`][crate::test_api::assert_schema_snapshot])
+
+use std::fmt;
+
+use borsh::schema::BorshSchemaContainer;
+
+use crate::WireMessage;
+
+/// A [`WireMessage`] with a borsh schema for each version
+///
+/// Implement this by adding `#[wire_message(schema)]` to the enum. Every type in its variants must
+/// implement [`BorshSchema`][borsh::BorshSchema]
+pub trait VersionSchemas: WireMessage {
+    /// The name and schema of each version's variant, oldest first
+    fn version_schemas() -> Vec<(&'static str, BorshSchemaContainer)>;
+
+    /// The fingerprint of each version's schema, oldest first
+    fn schema_fingerprints() -> Vec<Fingerprint> {
+        Self::version_schemas()
+            .into_iter()
+            .zip(1..)
+            .map(|((variant, schema), version)| Fingerprint {
+                version,
+                variant,
+                hash: fingerprint(&schema),
+            })
+            .collect()
+    }
+}
+
+/// The fingerprint of the schema of one version of a [`WireMessage`]
+///
+/// Displayed as `<version> <variant> <hash>`, which is the format of schema snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub version: u64,
+    pub variant: &'static str,
+    pub hash: u64,
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {:016x}", self.version, self.variant, self.hash)
+    }
+}
+
+/// A stable hash of a schema
+///
+/// This is the 64-bit FNV-1a hash of the borsh serialized schema. Type and field names are part
+/// of the schema, so renaming them also changes the fingerprint
+pub fn fingerprint(schema: &BorshSchemaContainer) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    #[allow(clippy::disallowed_methods)]
+    let bytes = borsh::to_vec(schema).expect("serializing to a vec can't fail");
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
@@ -1,6 +1,11 @@
+use std::{fs, path::Path};
+
 use borsh::{BorshDeserialize, BorshSerialize};
 
-use crate::WireMessage;
+use crate::{schema::VersionSchemas, WireMessage};
+
+/// Set this environment variable to write schema snapshots instead of checking them
+pub const UPDATE_SNAPSHOTS_ENV: &str = "WIRE_MESSAGE_UPDATE_SNAPSHOTS";
 
 /// Dummy message type for use in testing
 #[derive(
@@ -35,3 +40,59 @@ impl<T> DummyMsg<T> {
         }
     }
 }
+
+/// Check the schema fingerprint of each version of `T` against the snapshot at `path`
+///
+/// Panics if an existing version's schema has changed, since stored messages of that version
+/// would no longer deserialize. Change the layout by adding a new version instead. After adding a
+/// version, run the test with [`UPDATE_SNAPSHOTS_ENV`] set to update the snapshot, and commit it
+pub fn assert_schema_snapshot<T: VersionSchemas>(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let fingerprints = T::schema_fingerprints()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+
+        let mut snapshot = fingerprints.join("\n");
+        snapshot.push('\n');
+        fs::write(path, snapshot).unwrap();
+        return;
+    }
+
+    let snapshot = fs::read_to_string(path).unwrap_or_else(|err| {
+        panic!(
+            "failed to read schema snapshot {}: {err}, run with {UPDATE_SNAPSHOTS_ENV}=1 to create it",
+            path.display()
+        )
+    });
+    let snapshot = snapshot.lines().collect::<Vec<_>>();
+
+    for (i, expected) in snapshot.iter().enumerate() {
+        match fingerprints.get(i) {
+            Some(got) if got == expected => {}
+            Some(got) => panic!(
+                "the schema of version {} changed from `{expected}` to `{got}` (snapshot {}), add a new version instead of changing an existing one",
+                i + 1,
+                path.display()
+            ),
+            None => panic!(
+                "version {} (`{expected}`) was removed, but is in the schema snapshot {}",
+                i + 1,
+                path.display()
+            ),
+        }
+    }
+
+    if fingerprints.len() > snapshot.len() {
+        panic!(
+            "versions {:?} aren't in the schema snapshot {}, run with {UPDATE_SNAPSHOTS_ENV}=1 to add them",
+            &fingerprints[snapshot.len()..],
+            path.display()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+#![allow(clippy::disallowed_names)]
+use borsh::{BorshDeserialize, BorshSerialize};
+use wire_message::{wire_message, Error, ErrorKind, WireMessage};
+
+#[derive(Debug, Clone, PartialEq)]
+#[wire_message]
+enum ExampleMessage {
+    V1(V1),
+    V2(V2),
+    V3(V3),
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+struct V1 {
+    foo: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+struct V2 {
+    foo: Vec<u8>,
+    bar: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+struct V3 {
+    bar: Vec<u8>,
+}
+
+impl WireMessage for ExampleMessage {
+    type Ctx = ();
+    type Err = core::convert::Infallible;
+
+    fn version(&self) -> u64 {
+        match self {
+            Self::V1(_) => 1,
+            Self::V2(_) => 2,
+            Self::V3(_) => 3,
+        }
+    }
+
+    fn upgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, Error> {
+        match self {
+            Self::V1(V1 { foo }) => Ok(Self::V2(V2 { foo, bar: vec![] })),
+            Self::V2(V2 { bar, .. }) => Ok(Self::V3(V3 { bar })),
+            Self::V3(_) => Err(Self::max_version_error()),
+        }
+    }
+
+    fn downgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, Error> {
+        match self {
+            Self::V1(_) => Err(Self::min_version_error()),
+            Self::V2(V2 { foo, bar }) if bar.is_empty() => Ok(Self::V1(V1 { foo })),
+            Self::V2(_) => Err(Self::unrepresentable_error(2, 1)),
+            Self::V3(V3 { bar }) => Ok(Self::V2(V2 { foo: vec![], bar })),
+        }
+    }
+}
+
+#[test]
+fn to_bytes_at_version() {
+    let message = ExampleMessage::V3(V3 { bar: vec![] });
+
+    let bytes = message.to_bytes_at_version(1, &mut ()).unwrap();
+    assert_eq!(
+        ExampleMessage::from_bytes(&bytes).unwrap(),
+        ExampleMessage::V1(V1 { foo: vec![] })
+    );
+
+    // the current version is serialized as is
+    assert_eq!(
+        message.to_bytes_at_version(3, &mut ()).unwrap(),
+        message.to_bytes().unwrap()
+    );
+}
+
+#[test]
+fn into_version_upgrades_and_downgrades() {
+    let message = ExampleMessage::V2(V2 {
+        foo: vec![1],
+        bar: vec![2],
+    });
+
+    assert_eq!(
+        message.clone().into_version(3, &mut ()).unwrap(),
+        ExampleMessage::V3(V3 { bar: vec![2] })
+    );
+    assert_eq!(message.clone().into_version(2, &mut ()).unwrap(), message);
+}
+
+#[test]
+fn unrepresentable_values() {
+    let message = ExampleMessage::V3(V3 { bar: vec![1] });
+
+    let error = message.into_version(1, &mut ()).unwrap_err();
+    assert!(error.is_downgrade());
+    assert_eq!(
+        *error.kind(),
+        ErrorKind::Unrepresentable {
+            version: 2,
+            target: 1
+        }
+    );
+}
+
+#[test]
+fn invalid_versions() {
+    let message = ExampleMessage::V1(V1 { foo: vec![] });
+
+    for version in [0, 4] {
+        assert_eq!(
+            *message
+                .clone()
+                .into_version(version, &mut ())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidVersion {
+                version,
+                max_version: 3
+            }
+        );
+    }
+
+    assert!(message.downgrade_once(&mut ()).unwrap_err().is_downgrade());
+}
+
+#[derive(Clone)]
+#[wire_message]
+enum NoDowngrade {
+    V1(u8),
+    V2(u16),
+}
+
+impl WireMessage for NoDowngrade {
+    type Ctx = ();
+    type Err = core::convert::Infallible;
+
+    fn version(&self) -> u64 {
+        match self {
+            Self::V1(_) => 1,
+            Self::V2(_) => 2,
+        }
+    }
+
+    fn upgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, Error> {
+        match self {
+            Self::V1(value) => Ok(Self::V2(value.into())),
+            Self::V2(_) => Err(Self::max_version_error()),
+        }
+    }
+}
+
+#[test]
+fn downgrading_is_unsupported_by_default() {
+    let message = NoDowngrade::V2(1);
+
+    assert_eq!(
+        *message.to_bytes_at_version(1, &mut ()).unwrap_err().kind(),
+        ErrorKind::DowngradeUnsupported { version: 2 }
+    );
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "json")]
+#![allow(clippy::disallowed_names)]
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use wire_message::{
+    json::{from_json, to_json, Codec},
+    wire_message, Error, WireMessage,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[wire_message]
+enum ExampleMessage {
+    V1(V1),
+    V2(V2),
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+struct V1 {
+    foo: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+struct V2 {
+    foo: u64,
+    bar: bool,
+}
+
+impl WireMessage for ExampleMessage {
+    type Ctx = ();
+    type Err = core::convert::Infallible;
+
+    fn version(&self) -> u64 {
+        match self {
+            Self::V1(_) => 1,
+            Self::V2(_) => 2,
+        }
+    }
+
+    fn upgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, Error> {
+        match self {
+            Self::V1(V1 { foo }) => Ok(Self::V2(V2 { foo, bar: false })),
+            Self::V2(_) => Err(Self::max_version_error()),
+        }
+    }
+}
+
+#[test]
+fn json_roundtrip_keeps_the_version() {
+    let message = ExampleMessage::V1(V1 { foo: 1 });
+
+    let json = to_json(&message).unwrap();
+    assert_eq!(json, json!({"version": 1, "message": {"V1": {"foo": 1}}}));
+    assert_eq!(from_json::<ExampleMessage>(json).unwrap(), message);
+}
+
+#[test]
+fn mismatched_version_is_rejected() {
+    let json = json!({"version": 2, "message": {"V1": {"foo": 1}}});
+
+    assert!(from_json::<ExampleMessage>(json).unwrap_err().is_json());
+}
+
+#[test]
+fn codec_decodes_and_encodes_bytes() {
+    let codec = Codec::new::<ExampleMessage>("ExampleMessage");
+    let message = ExampleMessage::V2(V2 { foo: 1, bar: true });
+    let bytes = message.to_bytes().unwrap();
+
+    let json = codec.decode(&bytes).unwrap();
+    assert_eq!(
+        json,
+        json!({"version": 2, "message": {"V2": {"foo": 1, "bar": true}}})
+    );
+    assert_eq!(codec.encode(json).unwrap(), bytes);
+    assert_eq!(codec.name(), "ExampleMessage");
+}
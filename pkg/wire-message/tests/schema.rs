@@ -0,0 +1,117 @@
+#![allow(clippy::disallowed_names)]
+use wire_message::schema::{Fingerprint, VersionSchemas};
+
+mod original {
+    use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+    use wire_message::{wire_message, Error, WireMessage};
+
+    #[wire_message(schema)]
+    pub enum Message {
+        V1(Foo),
+        V2(Bar, u32),
+    }
+
+    #[derive(BorshSerialize, BorshDeserialize, BorshSchema)]
+    pub struct Foo {
+        foo: Vec<u8>,
+    }
+
+    #[derive(BorshSerialize, BorshDeserialize, BorshSchema)]
+    pub struct Bar {
+        foo: Vec<u8>,
+        bar: u64,
+    }
+
+    impl WireMessage for Message {
+        type Ctx = ();
+        type Err = core::convert::Infallible;
+
+        fn version(&self) -> u64 {
+            match self {
+                Self::V1(_) => 1,
+                Self::V2(_, _) => 2,
+            }
+        }
+
+        fn upgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, Error> {
+            match self {
+                Self::V1(Foo { foo }) => Ok(Self::V2(Bar { foo, bar: 0 }, 0)),
+                Self::V2(_, _) => Err(Self::max_version_error()),
+            }
+        }
+    }
+}
+
+/// The same message, with a field added to `V1` in place
+mod changed {
+    use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+    use wire_message::{wire_message, Error, WireMessage};
+
+    #[wire_message(schema)]
+    pub enum Message {
+        V1(Foo),
+        V2(Bar, u32),
+    }
+
+    #[derive(BorshSerialize, BorshDeserialize, BorshSchema)]
+    pub struct Foo {
+        foo: Vec<u8>,
+        baz: bool,
+    }
+
+    #[derive(BorshSerialize, BorshDeserialize, BorshSchema)]
+    pub struct Bar {
+        foo: Vec<u8>,
+        bar: u64,
+    }
+
+    impl WireMessage for Message {
+        type Ctx = ();
+        type Err = core::convert::Infallible;
+
+        fn version(&self) -> u64 {
+            match self {
+                Self::V1(_) => 1,
+                Self::V2(_, _) => 2,
+            }
+        }
+
+        fn upgrade_once(self, _ctx: &mut Self::Ctx) -> Result<Self, Error> {
+            match self {
+                Self::V1(Foo { foo, .. }) => Ok(Self::V2(Bar { foo, bar: 0 }, 0)),
+                Self::V2(_, _) => Err(Self::max_version_error()),
+            }
+        }
+    }
+}
+
+#[test]
+fn fingerprints_are_stable() {
+    let fingerprints = original::Message::schema_fingerprints();
+
+    assert_eq!(
+        fingerprints,
+        [
+            Fingerprint {
+                version: 1,
+                variant: "V1",
+                hash: 0xb949_e822_1e66_f80e,
+            },
+            Fingerprint {
+                version: 2,
+                variant: "V2",
+                hash: 0x648a_8f0c_a333_f3be,
+            },
+        ]
+    );
+    assert_eq!(fingerprints[0].to_string(), "1 V1 b949e8221e66f80e");
+}
+
+#[test]
+fn changing_a_version_changes_its_fingerprint() {
+    let original = original::Message::schema_fingerprints();
+    let changed = changed::Message::schema_fingerprints();
+
+    assert_ne!(original[0], changed[0]);
+    assert_eq!(original[1], changed[1]);
+}
@@ -1,6 +1,6 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use serde::{Deserialize, Serialize};
-use smirk::Element;
+use smirk::{CompressedPath, CompressedPathError, Element};
 
 use crate::{aggregate_utxo::AggregateUtxo, Snark, UTXO_INPUTS, UTXO_OUTPUTS};
 
@@ -105,6 +105,32 @@ impl<const DEPTH: usize> Default for MerklePath<DEPTH> {
     }
 }
 
+impl<const DEPTH: usize> MerklePath<DEPTH> {
+    /// Compress this path, so that only the siblings that aren't empty subtree hashes are stored
+    ///
+    /// Returns an error if this path doesn't have exactly `DEPTH - 1` siblings
+    pub fn compress(&self) -> Result<CompressedPath<DEPTH>, CompressedPathError> {
+        CompressedPath::try_compress(&self.siblings)
+    }
+}
+
+impl<const DEPTH: usize> TryFrom<&MerklePath<DEPTH>> for CompressedPath<DEPTH> {
+    type Error = CompressedPathError;
+
+    fn try_from(path: &MerklePath<DEPTH>) -> Result<Self, Self::Error> {
+        path.compress()
+    }
+}
+
+impl<const DEPTH: usize> TryFrom<&CompressedPath<DEPTH>> for MerklePath<DEPTH> {
+    type Error = CompressedPathError;
+
+    fn try_from(path: &CompressedPath<DEPTH>) -> Result<Self, Self::Error> {
+        let siblings = path.decompress()?;
+        Ok(Self { siblings })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Batch<const INSERTS: usize, const MERKLE_D: usize> {
     /// Inserts must link to each other, in other words the new root of the first element must match
@@ -178,6 +204,7 @@ pub enum UtxoKind {
     Deserialize,
     BorshSerialize,
     BorshDeserialize,
+    BorshSchema,
 )]
 pub struct UTXOProof<const MERKLE_D: usize> {
     /// Root hash
@@ -346,3 +373,53 @@ pub fn encode_activity_url_payload(payload: &NoteURLPayload) -> String {
     // Return Base58-encoded string
     bs58::encode(bytes).into_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use smirk::{smirk, Tree};
+
+    use super::*;
+
+    #[test]
+    fn merkle_path_compression_round_trips() {
+        let tree: Tree<64, ()> = smirk! { 1, 2, 4 };
+        let path = MerklePath::<64> {
+            siblings: tree
+                .path_for(Element::ONE)
+                .siblings_deepest_first()
+                .to_vec(),
+        };
+
+        let compressed = CompressedPath::try_from(&path).unwrap();
+        assert_eq!(compressed.siblings.len(), 2);
+
+        let decompressed = MerklePath::<64>::try_from(&compressed).unwrap();
+        assert_eq!(decompressed.siblings, path.siblings);
+
+        let empty = MerklePath::<64>::default().compress().unwrap();
+        assert!(empty.siblings.is_empty());
+        assert_eq!(
+            MerklePath::<64>::try_from(&empty).unwrap().siblings,
+            MerklePath::<64>::default().siblings
+        );
+    }
+
+    #[test]
+    fn merkle_path_with_wrong_length_is_an_error() {
+        let path = MerklePath::<64> {
+            siblings: MerklePath::<64>::default().siblings[1..].to_vec(),
+        };
+
+        assert_eq!(
+            CompressedPath::try_from(&path),
+            Err(CompressedPathError::WrongPathLength {
+                expected: 63,
+                actual: 62
+            })
+        );
+
+        let mut compressed = MerklePath::<64>::default().compress().unwrap();
+        compressed.siblings.push(Element::ONE);
+        assert!(MerklePath::<64>::try_from(&compressed).is_err());
+    }
+}
@@ -1,4 +1,9 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+use std::collections::BTreeMap;
+
+use borsh::{
+    schema::{add_definition, Declaration, Definition, Fields},
+    BorshDeserialize, BorshSchema, BorshSerialize,
+};
 use ethnum::U256;
 
 use super::Element;
@@ -15,3 +20,18 @@ impl BorshDeserialize for Element {
         Ok(Self(U256::from_be_bytes(bytes)))
     }
 }
+
+// Serialized like a tuple struct of the big-endian bytes
+impl BorshSchema for Element {
+    fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+        let definition = Definition::Struct {
+            fields: Fields::UnnamedFields(vec![<[u8; 32]>::declaration()]),
+        };
+        add_definition(Self::declaration(), definition, definitions);
+        <[u8; 32]>::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> Declaration {
+        "Element".into()
+    }
+}